@@ -0,0 +1,74 @@
+// benches/evaluation.rs - 比较字节码虚拟机与语法树遍历求值的性能
+//
+// 运行: cargo bench -p chapter02 --bench evaluation
+
+use std::hint::black_box;
+
+use chapter02::calculator::evaluator::ExpressionEvaluator;
+use chapter02::calculator::parser::ExpressionParser;
+use chapter02::Calculator;
+use criterion::{criterion_group, criterion_main, Criterion};
+
+const FORMULA: &str = "3 * x^2 - 2 * x * y + sin(x) / (1 + y^2) + sqrt(abs(x * y))";
+
+/// 1000 组变量值
+fn data_set() -> Vec<[f64; 2]> {
+    (0..1000)
+        .map(|i| {
+            let t = i as f64 * 0.01;
+            [t - 5.0, 2.0 * t.cos()]
+        })
+        .collect()
+}
+
+fn bench_evaluation(c: &mut Criterion) {
+    let data = data_set();
+    let mut group = c.benchmark_group("evaluate 1000 points");
+
+    // 每次都重新解析字符串
+    let calc = Calculator::new();
+    group.bench_function("parse + tree walk", |b| {
+        b.iter(|| {
+            let mut sum = 0.0;
+            for &[x, y] in &data {
+                calc.set_variable("x", x);
+                calc.set_variable("y", y);
+                sum += calc.evaluate_expression(FORMULA).unwrap();
+            }
+            black_box(sum)
+        })
+    });
+
+    // 解析一次，每次遍历语法树
+    let parser = ExpressionParser::new();
+    let ast = parser.parse(parser.tokenize(FORMULA).unwrap()).unwrap();
+    let evaluator = ExpressionEvaluator::new();
+    group.bench_function("tree walk", |b| {
+        b.iter(|| {
+            let mut sum = 0.0;
+            for &[x, y] in &data {
+                evaluator.set_variable("x", x);
+                evaluator.set_variable("y", y);
+                sum += evaluator.evaluate(black_box(&ast)).unwrap();
+            }
+            black_box(sum)
+        })
+    });
+
+    // 编译一次，在扁平的指令数组上求值
+    let compiled = evaluator.compile(&ast, &["x", "y"]).unwrap();
+    group.bench_function("bytecode", |b| {
+        b.iter(|| {
+            let mut sum = 0.0;
+            for point in &data {
+                sum += compiled.evaluate(black_box(point)).unwrap();
+            }
+            black_box(sum)
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_evaluation);
+criterion_main!(benches);
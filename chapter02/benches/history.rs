@@ -0,0 +1,62 @@
+// benches/history.rs - 大容量历史记录的添加（含淘汰）与按编号查找的性能
+//
+// 运行: cargo bench -p chapter02 --bench history
+
+use std::hint::black_box;
+
+use chapter02::history::{EvictionPolicy, HistoryManager};
+use chapter02::Error;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+// 2^k - 1 的容量下环形缓冲区没有空闲位置，每次添加都会绕回
+const CAPACITIES: [usize; 5] = [1_000, 10_000, 65_535, 100_000, 131_071];
+
+/// 装满的历史记录，每 10 条有一条失败的记录
+fn full_history(capacity: usize, policy: EvictionPolicy) -> HistoryManager {
+    let mut manager = HistoryManager::with_capacity(capacity);
+    manager.set_eviction_policy(policy);
+    for i in 0..capacity {
+        if i % 10 == 9 {
+            manager.add_error("1 / 0", Error::DivisionByZero);
+        } else {
+            manager.add_record("x + 1", i as f64);
+        }
+    }
+    manager
+}
+
+fn bench_add(c: &mut Criterion) {
+    let mut group = c.benchmark_group("add at capacity");
+    for policy in [
+        EvictionPolicy::DropOldest,
+        EvictionPolicy::DropFailuresFirst,
+        EvictionPolicy::KeepPinned,
+    ] {
+        for capacity in CAPACITIES {
+            let mut manager = full_history(capacity, policy);
+            let id = BenchmarkId::new(format!("{:?}", policy), capacity);
+            // 每次添加都会淘汰一条记录
+            group.bench_function(id, |b| b.iter(|| manager.add_record("x + 1", 1.0)));
+        }
+    }
+    group.finish();
+}
+
+fn bench_lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get_record_by_index");
+    for capacity in CAPACITIES {
+        let manager = full_history(capacity, EvictionPolicy::DropOldest);
+        let indices: Vec<usize> = (1..=capacity).step_by(capacity / 100).collect();
+        group.bench_function(BenchmarkId::from_parameter(capacity), |b| {
+            b.iter(|| {
+                for &index in &indices {
+                    black_box(manager.get_record_by_index(black_box(index)));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_add, bench_lookup);
+criterion_main!(benches);
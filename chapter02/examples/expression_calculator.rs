@@ -1,18 +1,38 @@
 // examples/expression_calculator.rs - 表达式计算器示例
 
-use chapter02::calculator::Calculator;
+use chapter02::calculator::{
+    AngleMode, Calculator, ComplexFormat, NumberMode, Outcome, PrintStyle, Value,
+};
+use chapter02::history::{HistoryManager, HistoryRecord};
+use chapter02::utils::render_error;
 use std::io::{self, Write};
 
 fn main() {
     println!("=== 表达式计算器 ===");
-    println!("支持的操作符: +, -, *, /, ^ (幂)");
-    println!("支持的函数: sin, cos, tan, sqrt, ln, abs, ceil, floor, round");
+    println!("支持的操作符: +, -, *, /, ^ (幂), ! (阶乘)");
     println!("支持括号改变优先级");
+    println!("支持变量赋值, 如 x = 3 * 4, 之后可使用 x ^ 2");
+    println!("支持带单位的数值, 如 3 m + 20 cm, 用 to 换算单位, 如 5 km to mi");
+    let mut calc = Calculator::new();
+    let mut history = HistoryManager::new();
+    let functions: Vec<&str> = calc
+        .registry()
+        .functions()
+        .iter()
+        .map(|function| function.signature.as_str())
+        .collect();
+    println!("支持的函数: {}", functions.join(", "));
+    println!(
+        "输入 'mode float'、'mode rational'、'mode strict'、'mode integer'、'mode decimal' 或 'mode complex' 切换数值模式"
+    );
+    println!("输入 'format rect' 或 'format polar' 切换复数的显示格式");
+    println!("输入 'angle rad'、'angle deg' 或 'angle grad' 切换三角函数的角度单位");
+    println!("输入 'precision N' 设置十进制模式的有效位数");
+    println!("输入 'simplify 表达式' 化简表达式, 'latex 表达式' 显示 LaTeX 公式");
+    println!("用 ans 或 _ 引用上一个结果, 用 $n 引用第 n 条记录; 输入 'history' 查看历史记录");
     println!("输入 'quit' 或 'exit' 退出");
     println!();
 
-    let calc = Calculator::new();
-
     // 演示一些示例
     println!("示例表达式:");
     let examples = vec![
@@ -29,10 +49,22 @@ fn main() {
         "abs(-42)",
         "ceil(3.14)",
         "floor(3.99)",
+        "log(8, 2)",
+        "max(3, 9, 4)",
+        "5! / nCr(5, 2)",
+        "mod(-7, 3)",
+        "3 m + 20 cm",
+        "9.81 m/s^2 * 2 s",
+        "sin(30°)",
+        "5 km to mi",
+        "diff(x^3, x, 2)",
+        "solve(x^2 = 2, x, 0, 2)",
+        "integrate(sin(x), x, 0, π)",
+        "sum(1/k^2, k, 1, 100)",
     ];
 
     for expr in &examples {
-        match calc.evaluate_expression(expr) {
+        match calc.evaluate_value(expr) {
             Ok(result) => println!("  {} = {}", expr, result),
             Err(e) => println!("  {} => 错误: {}", expr, e),
         }
@@ -48,7 +80,11 @@ fn main() {
         io::stdout().flush().unwrap();
 
         let mut input = String::new();
-        io::stdin().read_line(&mut input).expect("读取输入失败");
+        // 输入结束（如 Ctrl-D 或管道读完）时退出
+        if io::stdin().read_line(&mut input).expect("读取输入失败") == 0 {
+            println!();
+            break;
+        }
 
         let input = input.trim();
 
@@ -61,9 +97,116 @@ fn main() {
             break;
         }
 
-        match calc.evaluate_expression(input) {
-            Ok(result) => println!("= {}", result),
-            Err(e) => println!("错误: {}", e),
+        if input == "history" {
+            history.display();
+            continue;
+        }
+
+        if let Some(mode) = input.strip_prefix("mode ") {
+            match mode.trim() {
+                "float" => calc.set_number_mode(NumberMode::Float),
+                "rational" => {
+                    calc.set_number_mode(NumberMode::Rational);
+                    calc.set_strict_mode(false);
+                }
+                "strict" => {
+                    calc.set_number_mode(NumberMode::Rational);
+                    calc.set_strict_mode(true);
+                }
+                "integer" => calc.set_number_mode(NumberMode::Integer),
+                "decimal" => calc.set_number_mode(NumberMode::Decimal),
+                "complex" => calc.set_number_mode(NumberMode::Complex),
+                other => println!("未知模式: {}", other),
+            }
+            continue;
+        }
+
+        if let Some(angle) = input.strip_prefix("angle ") {
+            match angle.trim() {
+                "rad" => calc.set_angle_mode(AngleMode::Radians),
+                "deg" => calc.set_angle_mode(AngleMode::Degrees),
+                "grad" => calc.set_angle_mode(AngleMode::Gradians),
+                other => println!("未知角度单位: {}", other),
+            }
+            continue;
+        }
+
+        if let Some(format) = input.strip_prefix("format ") {
+            match format.trim() {
+                "rect" => calc.set_complex_format(ComplexFormat::Rectangular),
+                "polar" => calc.set_complex_format(ComplexFormat::Polar),
+                other => println!("未知格式: {}", other),
+            }
+            continue;
+        }
+
+        if let Some(digits) = input.strip_prefix("precision ") {
+            match digits.trim().parse() {
+                Ok(digits) => calc.set_precision(digits),
+                Err(_) => println!("无效的位数: {}", digits),
+            }
+            continue;
+        }
+
+        if let Some(expression) = input.strip_prefix("simplify ") {
+            // 先解析一次以便语法错误带有位置
+            match calc
+                .parse_expression(expression)
+                .and_then(|_| calc.simplify(expression))
+            {
+                Ok(ast) => println!("= {}", ast.format(PrintStyle::Unicode)),
+                Err(e) => println!("{}", render_error(expression, &e)),
+            }
+            continue;
+        }
+
+        if let Some(expression) = input.strip_prefix("latex ") {
+            match calc.parse_expression(expression) {
+                Ok((ast, _)) => println!("{}", ast.format(PrintStyle::Latex)),
+                Err(e) => println!("{}", render_error(expression, &e)),
+            }
+            continue;
+        }
+
+        // 先检查语法，一次列出所有问题
+        let diagnostics = calc.diagnose(input);
+        if !diagnostics.is_empty() {
+            for e in &diagnostics {
+                println!("{}", render_error(input, e));
+            }
+            continue;
+        }
+
+        let result = match calc.execute_with_recall(input, |r| history.recall(r)) {
+            // 函数定义没有数值，不记入历史
+            Ok(Outcome::Defined(name)) => {
+                println!("已定义函数 {}", name);
+                continue;
+            }
+            Ok(Outcome::Value(value)) => Ok(value),
+            Err(e) => Err(e),
+        };
+        // 历史记录只保存实数结果，复数结果不记录
+        if let Ok(value @ Value::Complex(_)) = &result {
+            println!("= {}  (复数结果不记入历史)", calc.format_value(value));
+            continue;
+        }
+        let index = history.add(HistoryRecord {
+            number_mode: calc.number_mode(),
+            precision: calc.precision() as u32,
+            ..HistoryRecord::with_value(input.to_string(), result.clone(), 0, calc.angle_mode())
+        });
+        match result {
+            // 精确结果同时显示分数和小数形式
+            Ok(value) => {
+                let decimal = value.to_decimal_string(20);
+                if value.is_exact() && decimal != value.fraction() {
+                    println!("= {} ≈ {}  (${})", value, decimal, index)
+                } else {
+                    println!("= {}  (${})", calc.format_value(&value), index)
+                }
+            }
+            Err(e) => println!("{}", render_error(input, &e)),
         }
     }
 }
@@ -0,0 +1,128 @@
+// calculator/batch.rs - 批量求值：对多列数据逐行求值同一个表达式，可分多个线程执行
+
+use std::collections::HashSet;
+use std::thread;
+
+use super::bytecode::CompiledExpr;
+use crate::utils::Error;
+
+/// 每个线程至少处理的行数，行数较少时不值得启动线程
+pub const MIN_ROWS_PER_THREAD: usize = 10_000;
+
+/// 默认线程数：可用的 CPU 核数
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// 检查各列的列名不重复、行数相同，返回行数（没有列时为 0）
+pub fn row_count(columns: &[(&str, &[f64])]) -> Result<usize, Error> {
+    let mut names = HashSet::new();
+    for (name, _) in columns {
+        if !names.insert(*name) {
+            return Err(Error::DuplicateColumn(name.to_string()));
+        }
+    }
+
+    let Some((_, first)) = columns.first() else {
+        return Ok(0);
+    };
+    match columns
+        .iter()
+        .find(|(_, column)| column.len() != first.len())
+    {
+        Some((name, column)) => Err(Error::ColumnLength {
+            column: name.to_string(),
+            expected: first.len(),
+            actual: column.len(),
+        }),
+        None => Ok(first.len()),
+    }
+}
+
+/// 用编译后的表达式逐行求值，columns[i] 对应 `compiled.variables()[i]`
+///
+/// 行数足够多时把行平均分给至多 threads 个线程；结果按行的顺序排列，每行单独报告错误。
+pub fn evaluate_compiled(
+    compiled: &CompiledExpr,
+    columns: &[&[f64]],
+    rows: usize,
+    threads: usize,
+) -> Vec<Result<f64, Error>> {
+    let mut results: Vec<Result<f64, Error>> = (0..rows).map(|_| Ok(0.0)).collect();
+    let threads = threads.clamp(1, rows.div_ceil(MIN_ROWS_PER_THREAD).max(1));
+    if threads == 1 {
+        evaluate_rows(compiled, columns, 0, &mut results);
+        return results;
+    }
+
+    let chunk = rows.div_ceil(threads);
+    thread::scope(|scope| {
+        for (i, out) in results.chunks_mut(chunk).enumerate() {
+            scope.spawn(move || evaluate_rows(compiled, columns, i * chunk, out));
+        }
+    });
+    results
+}
+
+/// 从第 start 行开始求值，结果写入 out
+fn evaluate_rows(
+    compiled: &CompiledExpr,
+    columns: &[&[f64]],
+    start: usize,
+    out: &mut [Result<f64, Error>],
+) {
+    let mut bindings = vec![0.0; columns.len()];
+    for (row, result) in (start..).zip(out) {
+        for (binding, column) in bindings.iter_mut().zip(columns) {
+            *binding = column[row];
+        }
+        *result = compiled.evaluate(&bindings);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculator::evaluator::ExpressionEvaluator;
+    use crate::calculator::parser::ExpressionParser;
+
+    fn compile(expression: &str, variables: &[&str]) -> CompiledExpr {
+        let parser = ExpressionParser::new();
+        let ast = parser.parse(parser.tokenize(expression).unwrap()).unwrap();
+        ExpressionEvaluator::new().compile(&ast, variables).unwrap()
+    }
+
+    #[test]
+    fn test_row_count() {
+        let (a, b) = ([1.0, 2.0], [3.0]);
+        assert_eq!(row_count(&[("a", &a), ("b", &a)]), Ok(2));
+        assert_eq!(row_count(&[]), Ok(0));
+        assert_eq!(
+            row_count(&[("a", &a), ("b", &b)]),
+            Err(Error::ColumnLength {
+                column: "b".to_string(),
+                expected: 2,
+                actual: 1
+            })
+        );
+        assert_eq!(
+            row_count(&[("a", &a), ("a", &a)]),
+            Err(Error::DuplicateColumn("a".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_threads_preserve_order() {
+        let compiled = compile("x / (y - 3)", &["x", "y"]);
+        let rows = 5 * MIN_ROWS_PER_THREAD + 7;
+        let x: Vec<f64> = (0..rows).map(|i| i as f64).collect();
+        let y: Vec<f64> = (0..rows).map(|i| (i % 5) as f64).collect();
+
+        let serial = evaluate_compiled(&compiled, &[&x, &y], rows, 1);
+        let parallel = evaluate_compiled(&compiled, &[&x, &y], rows, 4);
+        assert_eq!(serial, parallel);
+        assert_eq!(parallel[1], Ok(1.0 / -2.0));
+        assert_eq!(parallel[3], Err(Error::DivisionByZero));
+        assert_eq!(parallel[rows - 4], Err(Error::DivisionByZero));
+    }
+}
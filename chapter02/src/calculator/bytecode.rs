@@ -0,0 +1,371 @@
+// calculator/bytecode.rs - 字节码编译器与栈式虚拟机：一次编译，对不同的变量值反复求值
+
+use std::sync::Arc;
+
+use super::evaluator::{
+    ExpressionEvaluator, INVERSE_TRIG_FUNCTIONS, SPECIAL_FUNCTIONS, TRIG_FUNCTIONS,
+};
+use super::parser::AstNode;
+use super::registry::BuiltinFunction;
+use super::value::{self, AngleMode, NumberMode, Value};
+use crate::utils::Error;
+
+/// 虚拟机操作数栈的容量，编译时检查表达式所需的栈深度不超过它
+pub const MAX_STACK: usize = 64;
+
+/// 虚拟机指令
+#[derive(Debug, Clone)]
+pub enum Instruction {
+    /// 压入常数
+    Const(f64),
+    /// 压入第 i 个变量的值
+    Load(usize),
+    /// 弹出两个操作数，压入运算结果
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    /// 栈顶取相反数
+    Neg,
+    /// 弹出 argc 个参数调用内置函数，压入结果
+    Call {
+        function: Arc<BuiltinFunction>,
+        argc: usize,
+    },
+}
+
+/// 二元运算指令的浮点语义（与浮点模式下的 `Value::binary` 一致）
+#[inline]
+fn arithmetic(instruction: &Instruction, a: f64, b: f64) -> Result<f64, Error> {
+    match instruction {
+        Instruction::Add => Ok(a + b),
+        Instruction::Sub => Ok(a - b),
+        Instruction::Mul => Ok(a * b),
+        Instruction::Div if b == 0.0 => Err(Error::DivisionByZero),
+        Instruction::Div => Ok(a / b),
+        Instruction::Pow => value::real_result("^", &[a, b], a.powf(b)),
+        _ => Err(Error::InvalidOperator),
+    }
+}
+
+/// 编译后的表达式
+///
+/// 指令是扁平的数组，求值时只使用固定大小的栈，不分配内存。
+/// 无论计算器处于哪种数值模式，都按浮点数计算。
+#[derive(Debug, Clone)]
+pub struct CompiledExpr {
+    variables: Vec<String>,
+    code: Vec<Instruction>,
+}
+
+impl CompiledExpr {
+    /// 变量名，顺序与 `evaluate` 的参数一致
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    /// 指令序列
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.code
+    }
+
+    /// 用给定的变量值求值，bindings[i] 是第 i 个变量的值
+    pub fn evaluate(&self, bindings: &[f64]) -> Result<f64, Error> {
+        if bindings.len() != self.variables.len() {
+            return Err(Error::InvalidArgumentCount {
+                function: format!("compiled({})", self.variables.join(", ")),
+                expected: self.variables.len(),
+                actual: bindings.len(),
+            });
+        }
+
+        let mut stack = [0.0; MAX_STACK];
+        let mut top = 0;
+        for instruction in &self.code {
+            match instruction {
+                Instruction::Const(x) => {
+                    stack[top] = *x;
+                    top += 1;
+                }
+                Instruction::Load(i) => {
+                    stack[top] = bindings[*i];
+                    top += 1;
+                }
+                Instruction::Neg => stack[top - 1] = -stack[top - 1],
+                Instruction::Call { function, argc } => {
+                    let args = &stack[top - argc..top];
+                    let result = value::real_result(&function.name, args, function.call(args)?)?;
+                    top -= argc;
+                    stack[top] = result;
+                    top += 1;
+                }
+                op => {
+                    top -= 1;
+                    stack[top - 1] = arithmetic(op, stack[top - 1], stack[top])?;
+                }
+            }
+        }
+        Ok(stack[0])
+    }
+}
+
+/// 把（已展开用户函数的）语法树编译为字节码
+///
+/// variables 中的变量编译为 `Load`；其它变量和常量取编译时的值。
+pub(crate) fn compile(
+    evaluator: &ExpressionEvaluator,
+    ast: &AstNode,
+    variables: &[&str],
+) -> Result<CompiledExpr, Error> {
+    let mut compiler = Compiler {
+        evaluator,
+        variables,
+        code: Vec::new(),
+        depth: 0,
+    };
+    compiler.node(ast)?;
+    Ok(CompiledExpr {
+        variables: variables.iter().map(|v| v.to_string()).collect(),
+        code: compiler.code,
+    })
+}
+
+struct Compiler<'a> {
+    evaluator: &'a ExpressionEvaluator,
+    variables: &'a [&'a str],
+    code: Vec<Instruction>,
+    /// 当前的栈深度
+    depth: usize,
+}
+
+impl Compiler<'_> {
+    fn node(&mut self, ast: &AstNode) -> Result<(), Error> {
+        match ast {
+            AstNode::Number(n) => self.push(*n),
+
+            AstNode::Literal(text) => {
+                let value = Value::from_literal(text, NumberMode::Float, 0)?;
+                self.push(value.to_f64())
+            }
+
+            AstNode::Constant(name) => self.push(constant(name)?),
+
+            AstNode::Variable(name) => match self.variables.iter().position(|v| v == name) {
+                Some(index) => self.emit(Instruction::Load(index), 1),
+                None => self.push(self.global(name)?),
+            },
+
+            AstNode::BinaryOp { op, left, right } => {
+                let instruction = match op {
+                    '+' => Instruction::Add,
+                    '-' => Instruction::Sub,
+                    '*' => Instruction::Mul,
+                    '/' => Instruction::Div,
+                    '^' => Instruction::Pow,
+                    '=' => return Err(Error::NotCompilable("方程".to_string())),
+                    _ => return Err(Error::InvalidOperator),
+                };
+                self.node(left)?;
+                self.node(right)?;
+                self.binary(instruction)
+            }
+
+            AstNode::UnaryOp { op: '-', operand } => {
+                self.node(operand)?;
+                match self.code.last_mut() {
+                    Some(Instruction::Const(x)) => *x = -*x,
+                    _ => self.code.push(Instruction::Neg),
+                }
+                Ok(())
+            }
+            AstNode::UnaryOp { op: '+', operand } => self.node(operand),
+            AstNode::UnaryOp { .. } => Err(Error::InvalidOperator),
+
+            AstNode::FunctionCall { name, args } => self.call(name, args),
+
+            AstNode::Imaginary(_) => Err(Error::NotCompilable("复数".to_string())),
+            AstNode::Quantity { .. } | AstNode::Convert { .. } => {
+                Err(Error::NotCompilable("带单位的数值".to_string()))
+            }
+            AstNode::Assign { .. } | AstNode::FunctionDef { .. } => {
+                Err(Error::NotCompilable("赋值和函数定义".to_string()))
+            }
+            AstNode::Invalid => Err(Error::InvalidExpression),
+        }
+    }
+
+    /// 追加一条指令，stack_effect 是它使栈深度增加的量
+    fn emit(&mut self, instruction: Instruction, stack_effect: isize) -> Result<(), Error> {
+        self.depth = self.depth.saturating_add_signed(stack_effect);
+        if self.depth > MAX_STACK {
+            return Err(Error::NotCompilable(format!(
+                "所需栈深度超过 {} 的表达式",
+                MAX_STACK
+            )));
+        }
+        self.code.push(instruction);
+        Ok(())
+    }
+
+    fn push(&mut self, x: f64) -> Result<(), Error> {
+        self.emit(Instruction::Const(x), 1)
+    }
+
+    /// 二元运算；两个操作数都是常数时在编译时折叠（会出错的运算留到求值时）
+    fn binary(&mut self, instruction: Instruction) -> Result<(), Error> {
+        if let [.., Instruction::Const(a), Instruction::Const(b)] = self.code[..] {
+            if let Ok(result) = arithmetic(&instruction, a, b) {
+                self.code.truncate(self.code.len() - 2);
+                self.depth -= 2;
+                return self.push(result);
+            }
+        }
+        self.emit(instruction, -1)
+    }
+
+    /// 内置函数调用；三角函数的参数和反三角函数的结果按编译时的角度模式换算
+    fn call(&mut self, name: &str, args: &[AstNode]) -> Result<(), Error> {
+        let registry = self.evaluator.registry();
+        let Some(function) = registry.get(name) else {
+            return Err(if SPECIAL_FUNCTIONS.contains(&name) {
+                Error::NotCompilable(format!("函数 {}", name))
+            } else {
+                Error::UndefinedFunction(name.to_string())
+            });
+        };
+        function.arity.check(&function.signature, args.len())?;
+        let function = Arc::new(function.clone());
+
+        let factor = match self.evaluator.angle_mode() {
+            AngleMode::Radians => None,
+            mode => Some(std::f64::consts::PI / mode.half_turn()),
+        };
+        let trig = TRIG_FUNCTIONS.contains(&function.name.as_str());
+        let inverse_trig = INVERSE_TRIG_FUNCTIONS.contains(&function.name.as_str());
+
+        for arg in args {
+            self.node(arg)?;
+            if let (true, Some(factor)) = (trig, factor) {
+                self.push(factor)?;
+                self.binary(Instruction::Mul)?;
+            }
+        }
+        let argc = args.len();
+        self.emit(Instruction::Call { function, argc }, 1 - argc as isize)?;
+        if let (true, Some(factor)) = (inverse_trig, factor) {
+            self.push(factor)?;
+            self.binary(Instruction::Div)?;
+        }
+        Ok(())
+    }
+
+    /// 不在变量列表中的变量取编译时的值，未定义的 pi 和 e 视为常量
+    fn global(&self, name: &str) -> Result<f64, Error> {
+        let value = self.evaluator.environment().get_value(name);
+        match value {
+            Some(Value::Quantity(_)) => Err(Error::NotCompilable("带单位的数值".to_string())),
+            Some(Value::Complex(z)) if z.im != 0.0 => Err(Error::NotCompilable("复数".to_string())),
+            Some(value) => Ok(value.to_f64()),
+            None => constant(name).map_err(|_| Error::UndefinedVariable(name.to_string())),
+        }
+    }
+}
+
+fn constant(name: &str) -> Result<f64, Error> {
+    match name {
+        "pi" => Ok(std::f64::consts::PI),
+        "e" => Ok(std::f64::consts::E),
+        _ => Err(Error::UndefinedVariable(name.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculator::parser::ExpressionParser;
+
+    fn parse(expression: &str) -> AstNode {
+        let parser = ExpressionParser::new();
+        parser.parse(parser.tokenize(expression).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_matches_tree_walker() {
+        let evaluator = ExpressionEvaluator::new();
+        evaluator.set_variable("k", 2.5);
+        let expressions = [
+            "3 * x^2 - 2 * x * y + 1",
+            "-x^2 + -(y - 4) / 3",
+            "sin(x) * cos(y) + sqrt(abs(x * y)) / (1 + y^2)",
+            "max(x, y, 0.5) + log(8, 2) + atan2(y, x)",
+            "k * x + pi - e + 5!",
+            "2^3^2 / x",
+        ];
+        for expression in expressions {
+            let ast = parse(expression);
+            let compiled = evaluator.compile(&ast, &["x", "y"]).unwrap();
+            for (x, y) in [(0.5, -1.0), (2.0, 3.0), (-7.25, 0.125)] {
+                evaluator.set_variable("x", x);
+                evaluator.set_variable("y", y);
+                let expected = evaluator.evaluate(&ast).unwrap();
+                assert_eq!(
+                    compiled.evaluate(&[x, y]).unwrap(),
+                    expected,
+                    "{}",
+                    expression
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_constant_folding() {
+        let evaluator = ExpressionEvaluator::new();
+        let compiled = evaluator
+            .compile(&parse("x * (2 + 3) - -4"), &["x"])
+            .unwrap();
+        assert_eq!(compiled.instructions().len(), 5);
+        assert_eq!(compiled.evaluate(&[2.0]).unwrap(), 14.0);
+
+        // 除以 0 留到求值时报错
+        let compiled = evaluator.compile(&parse("1 / 0 + x"), &["x"]).unwrap();
+        assert_eq!(compiled.evaluate(&[1.0]), Err(Error::DivisionByZero));
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        let evaluator = ExpressionEvaluator::new();
+        assert_eq!(
+            evaluator.compile(&parse("x + z"), &["x"]).unwrap_err(),
+            Error::UndefinedVariable("z".to_string())
+        );
+        assert!(matches!(
+            evaluator.compile(&parse("sum(k, k, 1, x)"), &["x"]),
+            Err(Error::NotCompilable(_))
+        ));
+        assert!(matches!(
+            evaluator.compile(&parse("3 m * x"), &["x"]),
+            Err(Error::NotCompilable(_))
+        ));
+        assert!(evaluator.compile(&parse("nope(x)"), &["x"]).is_err());
+        assert!(evaluator.compile(&parse("sqrt(x, 2)"), &["x"]).is_err());
+
+        let compiled = evaluator.compile(&parse("x"), &["x"]).unwrap();
+        assert!(matches!(
+            compiled.evaluate(&[1.0, 2.0]),
+            Err(Error::InvalidArgumentCount {
+                expected: 1,
+                actual: 2,
+                ..
+            })
+        ));
+
+        // 栈深度超过容量
+        let deep = format!("{}x{}", "(1 + ".repeat(MAX_STACK), ")".repeat(MAX_STACK));
+        assert!(matches!(
+            evaluator.compile(&parse(&deep), &["x"]),
+            Err(Error::NotCompilable(_))
+        ));
+    }
+}
@@ -0,0 +1,183 @@
+// calculator/complex.rs - 复数运算与显示
+
+use num_complex::Complex64;
+
+use crate::utils::Error;
+
+/// 复数的显示格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ComplexFormat {
+    /// 直角坐标形式，如 `3 + 4i`
+    #[default]
+    Rectangular,
+    /// 极坐标形式，如 `5∠0.9272952180016122`（辐角为弧度）
+    Polar,
+}
+
+/// 相对模长小于该值的实部或虚部在显示时视为 0，如 `exp(iπ)` 显示为 `-1`
+const DISPLAY_EPSILON: f64 = 1e-15;
+
+/// 复数二元运算
+pub fn binary(op: char, a: Complex64, b: Complex64) -> Result<Complex64, Error> {
+    match op {
+        '+' => Ok(a + b),
+        '-' => Ok(a - b),
+        '*' => Ok(a * b),
+        '/' => {
+            if b == Complex64::new(0.0, 0.0) {
+                return Err(Error::DivisionByZero);
+            }
+            Ok(a / b)
+        }
+        '^' => power(a, b),
+        _ => Err(Error::InvalidOperator),
+    }
+}
+
+/// 复数幂（取主值）：整数次幂用乘法计算，结果更精确，如 `i^2 = -1`
+pub fn power(base: Complex64, exponent: Complex64) -> Result<Complex64, Error> {
+    if exponent.im == 0.0 && exponent.re.fract() == 0.0 && exponent.re.abs() <= i32::MAX as f64 {
+        let n = exponent.re as i32;
+        if n < 0 && base == Complex64::new(0.0, 0.0) {
+            return Err(Error::DivisionByZero);
+        }
+        return Ok(base.powi(n));
+    }
+    if base == Complex64::new(0.0, 0.0) {
+        return if exponent.re > 0.0 {
+            Ok(base)
+        } else {
+            Err(Error::DivisionByZero)
+        };
+    }
+    Ok(base.powc(exponent))
+}
+
+/// 按名称计算复数函数；不支持复数参数时返回 None
+pub(crate) fn complex_function(name: &str, args: &[Complex64]) -> Option<Result<Complex64, Error>> {
+    let zero = Complex64::new(0.0, 0.0);
+    let result = match (name, args) {
+        ("sqrt", [z]) => Ok(z.sqrt()),
+        ("ln", [z]) if *z == zero => Err(Error::InvalidLogarithm),
+        ("ln", [z]) => Ok(z.ln()),
+        ("log", [z, base]) => {
+            let denominator = base.ln();
+            if *z == zero || *base == zero || denominator == zero {
+                Err(Error::InvalidLogarithm)
+            } else {
+                Ok(z.ln() / denominator)
+            }
+        }
+        ("log10" | "log2", [z]) if *z == zero => Err(Error::InvalidLogarithm),
+        ("log10", [z]) => Ok(z.log10()),
+        ("log2", [z]) => Ok(z.log2()),
+        ("exp", [z]) => Ok(z.exp()),
+        ("cbrt", [z]) => Ok(z.cbrt()),
+        ("sin", [z]) => Ok(z.sin()),
+        ("cos", [z]) => Ok(z.cos()),
+        ("tan", [z]) => Ok(z.tan()),
+        ("asin", [z]) => Ok(z.asin()),
+        ("acos", [z]) => Ok(z.acos()),
+        ("atan", [z]) => Ok(z.atan()),
+        ("sinh", [z]) => Ok(z.sinh()),
+        ("cosh", [z]) => Ok(z.cosh()),
+        ("tanh", [z]) => Ok(z.tanh()),
+        ("asinh", [z]) => Ok(z.asinh()),
+        ("acosh", [z]) => Ok(z.acosh()),
+        ("atanh", [z]) if z.im == 0.0 && z.re.abs() == 1.0 => Err(Error::DomainError {
+            function: "atanh".to_string(),
+            reason: "±1 是反双曲正切的极点".to_string(),
+        }),
+        ("atanh", [z]) => Ok(z.atanh()),
+        ("abs", [z]) => Ok(Complex64::new(z.norm(), 0.0)),
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// 按指定格式显示复数，`part` 决定实部、虚部和模长的显示方式
+pub(crate) fn format_with<F>(z: Complex64, format: ComplexFormat, part: F) -> String
+where
+    F: Fn(f64) -> String,
+{
+    if format == ComplexFormat::Polar {
+        return format!("{}∠{}", part(z.norm()), part(z.arg()));
+    }
+
+    let scale = z.norm() * DISPLAY_EPSILON;
+    let re = if z.re.abs() < scale { 0.0 } else { z.re };
+    let im = if z.im.abs() < scale { 0.0 } else { z.im };
+    let imaginary = |im: f64| match part(im).as_str() {
+        "1" => "i".to_string(),
+        text => format!("{}i", text),
+    };
+
+    if im == 0.0 {
+        return part(re);
+    }
+    let sign = if im < 0.0 { "-" } else { "+" };
+    if re == 0.0 {
+        let sign = if im < 0.0 { "-" } else { "" };
+        return format!("{}{}", sign, imaginary(im.abs()));
+    }
+    format!("{} {} {}", part(re), sign, imaginary(im.abs()))
+}
+
+/// 按指定格式显示复数
+pub fn format(z: Complex64, format: ComplexFormat) -> String {
+    format_with(z, format, |x| x.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let rect = |re, im| format(Complex64::new(re, im), ComplexFormat::Rectangular);
+        assert_eq!(rect(3.0, 4.0), "3 + 4i");
+        assert_eq!(rect(3.0, -4.0), "3 - 4i");
+        assert_eq!(rect(0.0, 1.0), "i");
+        assert_eq!(rect(0.0, -2.5), "-2.5i");
+        assert_eq!(rect(-1.0, 1.2246467991473532e-16), "-1");
+        assert_eq!(
+            format(Complex64::new(0.0, 2.0), ComplexFormat::Polar),
+            format!("2∠{}", std::f64::consts::FRAC_PI_2)
+        );
+    }
+
+    #[test]
+    fn test_power() {
+        let i = Complex64::new(0.0, 1.0);
+        assert_eq!(
+            power(i, Complex64::new(2.0, 0.0)).unwrap(),
+            Complex64::new(-1.0, 0.0)
+        );
+        // (-8)^(1/3) 的主值是 1 + √3 i
+        let root = power(Complex64::new(-8.0, 0.0), Complex64::new(1.0 / 3.0, 0.0)).unwrap();
+        assert!((root - Complex64::new(1.0, 3f64.sqrt())).norm() < 1e-12);
+        assert_eq!(
+            power(Complex64::new(0.0, 0.0), Complex64::new(-1.0, 0.0)),
+            Err(Error::DivisionByZero)
+        );
+    }
+
+    #[test]
+    fn test_complex_function() {
+        let z = |re| vec![Complex64::new(re, 0.0)];
+        let sqrt = complex_function("sqrt", &z(-4.0)).unwrap().unwrap();
+        assert_eq!(sqrt, Complex64::new(0.0, 2.0));
+
+        let ln = complex_function("ln", &z(-2.0)).unwrap().unwrap();
+        assert!((ln - Complex64::new(2f64.ln(), std::f64::consts::PI)).norm() < 1e-12);
+
+        assert_eq!(
+            complex_function("ln", &z(0.0)),
+            Some(Err(Error::InvalidLogarithm))
+        );
+        // 实数域无定义的 acosh(0) 在复数域为 iπ/2
+        let acosh = complex_function("acosh", &z(0.0)).unwrap().unwrap();
+        assert!((acosh - Complex64::new(0.0, std::f64::consts::FRAC_PI_2)).norm() < 1e-12);
+        assert_eq!(complex_function("floor", &z(1.5)), None);
+    }
+}
@@ -0,0 +1,864 @@
+// calculator/decimal.rs - 任意精度十进制数
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{Pow, Signed, ToPrimitive, Zero};
+
+use crate::utils::Error;
+
+/// 中间计算额外保留的位数，保证最终结果按有效位数正确舍入
+pub(crate) const GUARD_DIGITS: u32 = 10;
+
+/// 精确整数次幂允许的最大指数绝对值
+const MAX_INTEGER_EXPONENT: i64 = 100_000;
+
+/// 指数函数参数的上限，超出后结果的位数过多
+const MAX_EXP_ARGUMENT: f64 = 1e6;
+
+/// 三角函数参数数量级（十进制指数）的上限，按 2π 取模需要同样多位的 π
+const MAX_TRIG_MAGNITUDE: i64 = 1000;
+
+/// 转换为有理数时允许的最大十进制指数绝对值
+const MAX_RATIONAL_EXPONENT: i64 = 100_000;
+
+/// 任意精度十进制数：`coefficient × 10^exponent`
+///
+/// 每个数都带有有效位数 `precision`，运算结果按较大的有效位数做“四舍六入五成双”舍入。
+/// 所有运算都只用整数完成，因此结果与平台无关、可以复现。
+#[derive(Debug, Clone)]
+pub struct Decimal {
+    coefficient: BigInt,
+    exponent: i64,
+    precision: u32,
+}
+
+impl Decimal {
+    /// 创建十进制数，并按有效位数舍入
+    pub fn new(coefficient: BigInt, exponent: i64, precision: u32) -> Self {
+        let precision = precision.max(1);
+        let (coefficient, exponent) = round_digits(coefficient, exponent, precision);
+        let (coefficient, exponent) = strip_zeros(coefficient, exponent);
+        Self {
+            coefficient,
+            exponent,
+            precision,
+        }
+    }
+
+    /// 由整数创建
+    pub fn from_integer(value: BigInt, precision: u32) -> Self {
+        Self::new(value, 0, precision)
+    }
+
+    /// 解析十进制字面量，如 `-12.50`
+    pub fn parse(text: &str, precision: u32) -> Option<Self> {
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if integer.is_empty() && fraction.is_empty() {
+            return None;
+        }
+
+        let coefficient: BigInt = format!("{}{}", integer, fraction).parse().ok()?;
+        let coefficient = if negative { -coefficient } else { coefficient };
+        Some(Self::new(coefficient, -(fraction.len() as i64), precision))
+    }
+
+    /// 由有理数创建（按有效位数舍入）
+    pub fn from_rational(value: &BigRational, precision: u32) -> Self {
+        let (numer, denom) = (value.numer(), value.denom());
+        let shift = (precision + GUARD_DIGITS) as i64 + digit_count(denom) as i64
+            - digit_count(numer) as i64;
+        let shift = shift.max(0) as u32;
+        let (quotient, remainder) = (numer * pow10(shift)).div_rem(denom);
+        let (quotient, exponent) = sticky(quotient, &remainder, -(shift as i64));
+        Self::new(quotient, exponent, precision)
+    }
+
+    /// 圆周率 π
+    pub fn pi(precision: u32) -> Self {
+        let scale = precision + GUARD_DIGITS;
+        Self::from_fixed(pi_fixed(scale), scale, precision)
+    }
+
+    /// 自然常数 e
+    pub fn e(precision: u32) -> Self {
+        let one = Self::from_integer(BigInt::from(1), precision);
+        one.exp().unwrap_or(one)
+    }
+
+    /// 有效位数
+    pub fn precision(&self) -> u32 {
+        self.precision
+    }
+
+    /// 以新的有效位数重新舍入
+    pub fn with_precision(&self, precision: u32) -> Self {
+        Self::new(self.coefficient.clone(), self.exponent, precision)
+    }
+
+    /// 判断是否为零
+    pub fn is_zero(&self) -> bool {
+        self.coefficient.is_zero()
+    }
+
+    /// 判断是否为负数
+    pub fn is_negative(&self) -> bool {
+        self.coefficient.is_negative()
+    }
+
+    /// 判断是否为整数
+    pub fn is_integer(&self) -> bool {
+        self.exponent >= 0
+    }
+
+    /// 转换为浮点数
+    pub fn to_f64(&self) -> f64 {
+        format!("{}e{}", self.coefficient, self.exponent)
+            .parse()
+            .unwrap_or(f64::NAN)
+    }
+
+    /// 转换为精确的有理数；指数的绝对值过大时返回 None
+    pub fn to_rational(&self) -> Option<BigRational> {
+        if self.exponent.abs() > MAX_RATIONAL_EXPONENT {
+            return None;
+        }
+        let rational = if self.exponent >= 0 {
+            BigRational::from_integer(&self.coefficient * pow10(self.exponent as u32))
+        } else {
+            BigRational::new(
+                self.coefficient.clone(),
+                pow10(self.exponent.unsigned_abs() as u32),
+            )
+        };
+        Some(rational)
+    }
+
+    // ===== 四则运算 =====
+
+    /// 加法
+    pub fn add(&self, other: &Decimal) -> Decimal {
+        // 一方的最高位落在另一方的舍入位之后时不影响结果，不必对齐
+        let precision = self.precision.max(other.precision);
+        let limit = (precision + GUARD_DIGITS) as i64;
+        if other.is_zero() || self.adjusted_exponent() - other.adjusted_exponent() > limit {
+            return self.with_precision(precision);
+        }
+        if self.is_zero() || other.adjusted_exponent() - self.adjusted_exponent() > limit {
+            return other.with_precision(precision);
+        }
+
+        let exponent = self.exponent.min(other.exponent);
+        let left = &self.coefficient * pow10((self.exponent - exponent) as u32);
+        let right = &other.coefficient * pow10((other.exponent - exponent) as u32);
+        Decimal::new(left + right, exponent, precision)
+    }
+
+    /// 减法
+    pub fn sub(&self, other: &Decimal) -> Decimal {
+        self.add(&other.neg())
+    }
+
+    /// 乘法
+    pub fn mul(&self, other: &Decimal) -> Decimal {
+        Decimal::new(
+            &self.coefficient * &other.coefficient,
+            self.exponent + other.exponent,
+            self.precision.max(other.precision),
+        )
+    }
+
+    /// 除法
+    pub fn div(&self, other: &Decimal) -> Result<Decimal, Error> {
+        if other.is_zero() {
+            return Err(Error::DivisionByZero);
+        }
+
+        let precision = self.precision.max(other.precision);
+        let shift = (precision + GUARD_DIGITS) as i64 + digit_count(&other.coefficient) as i64
+            - digit_count(&self.coefficient) as i64;
+        let shift = shift.max(0) as u32;
+        let (quotient, remainder) = (&self.coefficient * pow10(shift)).div_rem(&other.coefficient);
+        let exponent = self.exponent - other.exponent - shift as i64;
+        let (quotient, exponent) = sticky(quotient, &remainder, exponent);
+        Ok(Decimal::new(quotient, exponent, precision))
+    }
+
+    /// 取相反数
+    pub fn neg(&self) -> Decimal {
+        Decimal {
+            coefficient: -&self.coefficient,
+            exponent: self.exponent,
+            precision: self.precision,
+        }
+    }
+
+    /// 幂运算：整数次幂用连乘，其余情况用 `exp(y · ln x)`
+    ///
+    /// 负数的非整数次幂不是实数，返回 None。
+    pub fn pow(&self, exponent: &Decimal) -> Result<Option<Decimal>, Error> {
+        let precision = self.precision.max(exponent.precision);
+
+        if exponent.is_integer() {
+            if let Some(n) = exponent.to_rational().and_then(|r| r.to_integer().to_i64()) {
+                if n.abs() <= MAX_INTEGER_EXPONENT {
+                    return self.pow_integer(n, precision).map(Some);
+                }
+            }
+        }
+
+        if self.is_negative() {
+            return Ok(None);
+        }
+        if self.is_zero() {
+            return if exponent.is_negative() {
+                Err(Error::DivisionByZero)
+            } else {
+                Ok(Some(self.clone()))
+            };
+        }
+
+        let working = precision + GUARD_DIGITS + digit_count(&exponent.coefficient) as u32;
+        let ln = self.with_precision(working).ln()?;
+        let result = exponent.with_precision(working).mul(&ln).exp()?;
+        Ok(Some(result.with_precision(precision)))
+    }
+
+    /// 整数次幂（二进制快速幂）
+    fn pow_integer(&self, n: i64, precision: u32) -> Result<Decimal, Error> {
+        let working = precision + GUARD_DIGITS + digit_count(&BigInt::from(n)) as u32;
+        let mut base = self.with_precision(working);
+        let mut result = Decimal::from_integer(BigInt::from(1), working);
+        let mut remaining = n.unsigned_abs();
+
+        while remaining > 0 {
+            if remaining & 1 == 1 {
+                result = result.mul(&base);
+            }
+            base = base.mul(&base);
+            remaining >>= 1;
+        }
+
+        if n < 0 {
+            result = Decimal::from_integer(BigInt::from(1), working).div(&result)?;
+        }
+        Ok(result.with_precision(precision))
+    }
+
+    // ===== 超越函数 =====
+
+    /// 平方根
+    pub fn sqrt(&self) -> Result<Decimal, Error> {
+        if self.is_negative() {
+            return Err(Error::NegativeSquareRoot);
+        }
+        if self.is_zero() {
+            return Ok(self.clone());
+        }
+
+        let mut coefficient = self.coefficient.clone();
+        let mut exponent = self.exponent;
+        if exponent % 2 != 0 {
+            coefficient *= 10;
+            exponent -= 1;
+        }
+
+        // 放大系数，使平方根至少有 precision + GUARD_DIGITS 位
+        let target = 2 * (self.precision + GUARD_DIGITS) as usize;
+        let digits = digit_count(&coefficient);
+        let shift = if target > digits {
+            (target - digits).div_ceil(2) * 2
+        } else {
+            0
+        };
+        coefficient *= pow10(shift as u32);
+        exponent -= shift as i64;
+
+        let root = coefficient.sqrt();
+        let remainder = &coefficient - &root * &root;
+        let (root, exponent) = sticky(root, &remainder, exponent / 2);
+        Ok(Decimal::new(root, exponent, self.precision))
+    }
+
+    /// 自然对数
+    pub fn ln(&self) -> Result<Decimal, Error> {
+        if self.is_negative() || self.is_zero() {
+            return Err(Error::InvalidLogarithm);
+        }
+
+        // x = m × 10^k，m ∈ [1, 10)，ln x = ln m + k · ln 10
+        let k = self.adjusted_exponent();
+        let scale = self.precision + GUARD_DIGITS + digit_count(&BigInt::from(k)) as u32;
+        let m = shift_fixed(
+            &self.coefficient,
+            scale as i64 - (digit_count(&self.coefficient) as i64 - 1),
+        );
+
+        let ln10 = ln_fixed(&(pow10(scale) * 10), scale);
+        let result = ln_fixed(&m, scale) + ln10 * k;
+        Ok(Decimal::from_fixed(result, scale, self.precision))
+    }
+
+    /// 以 base 为底的对数
+    pub fn log(&self, base: &Decimal) -> Result<Decimal, Error> {
+        let one = Decimal::from_integer(BigInt::from(1), base.precision);
+        if base.is_negative() || base.is_zero() || *base == one {
+            return Err(Error::InvalidLogarithm);
+        }
+
+        let working = self.precision.max(base.precision) + GUARD_DIGITS;
+        let value = self.with_precision(working).ln()?;
+        let base_ln = base.with_precision(working).ln()?;
+        Ok(value.div(&base_ln)?.with_precision(working - GUARD_DIGITS))
+    }
+
+    /// 指数函数 e^x
+    pub fn exp(&self) -> Result<Decimal, Error> {
+        let approx = self.to_f64();
+        if approx.abs() > MAX_EXP_ARGUMENT {
+            return Err(Error::Other("指数函数的参数过大".to_string()));
+        }
+
+        // x = k · ln 2 + r，|r| ≤ ln 2 / 2，e^x = e^r × 2^k
+        let k = (approx / std::f64::consts::LN_2).round() as i64;
+        let scale = self.precision + GUARD_DIGITS + digit_count(&BigInt::from(k)) as u32;
+        let one = pow10(scale);
+        let r = self.to_fixed(scale) - ln2_fixed(scale) * k;
+
+        let mut sum = one.clone();
+        let mut term = one.clone();
+        let mut i = 1u32;
+        loop {
+            term = &term * &r / &one / i;
+            if term.is_zero() {
+                break;
+            }
+            sum += &term;
+            i += 1;
+        }
+
+        let working = self.precision + GUARD_DIGITS;
+        let result = Decimal::from_fixed(sum, scale, working);
+        let power = Decimal::from_integer(BigInt::from(2).pow(k.unsigned_abs()), working);
+        let result = if k >= 0 {
+            result.mul(&power)
+        } else {
+            result.div(&power)?
+        };
+        Ok(result.with_precision(self.precision))
+    }
+
+    /// 正弦（弧度）
+    pub fn sin(&self) -> Result<Decimal, Error> {
+        Ok(self.sin_cos()?.0)
+    }
+
+    /// 余弦（弧度）
+    pub fn cos(&self) -> Result<Decimal, Error> {
+        Ok(self.sin_cos()?.1)
+    }
+
+    /// 正切（弧度）
+    pub fn tan(&self) -> Result<Decimal, Error> {
+        let working = self.precision + GUARD_DIGITS;
+        let (sin, cos) = self.with_precision(working).sin_cos()?;
+        Ok(sin.div(&cos)?.with_precision(self.precision))
+    }
+
+    /// 同时计算正弦和余弦：先按 2π 取模，再用泰勒级数
+    fn sin_cos(&self) -> Result<(Decimal, Decimal), Error> {
+        if self.adjusted_exponent() > MAX_TRIG_MAGNITUDE {
+            return Err(Error::Other("三角函数的参数过大".to_string()));
+        }
+        // |x| < 10^-precision 时 x³/6 和 x²/2 都在舍入位之后：sin x = x，cos x = 1
+        if self.is_zero() || self.adjusted_exponent() < -(self.precision as i64) {
+            let one = Decimal::from_integer(BigInt::from(1), self.precision);
+            return Ok((self.clone(), one));
+        }
+
+        // 参数很大时需要更多位的 π 来取模；很小时需要更多位来保留有效数字
+        let extra = self.adjusted_exponent().unsigned_abs() as u32 + 1;
+        let scale = self.precision + GUARD_DIGITS + extra;
+        let one = pow10(scale);
+
+        let x = self.to_fixed(scale);
+        let two_pi = pi_fixed(scale) * 2;
+        let half_turn: BigInt = &two_pi / 2;
+        let turns = (&x + half_turn).div_floor(&two_pi);
+        let r = x - turns * &two_pi;
+        let r2 = &r * &r / &one;
+
+        let mut sin = r.clone();
+        let mut term = r;
+        let mut i = 1u32;
+        loop {
+            term = -(&term * &r2 / &one) / ((2 * i) * (2 * i + 1));
+            if term.is_zero() {
+                break;
+            }
+            sin += &term;
+            i += 1;
+        }
+
+        let mut cos = one.clone();
+        let mut term = one.clone();
+        let mut i = 1u32;
+        loop {
+            term = -(&term * &r2 / &one) / ((2 * i - 1) * (2 * i));
+            if term.is_zero() {
+                break;
+            }
+            cos += &term;
+            i += 1;
+        }
+
+        Ok((
+            Decimal::from_fixed(sin, scale, self.precision),
+            Decimal::from_fixed(cos, scale, self.precision),
+        ))
+    }
+
+    // ===== 取整 =====
+
+    /// 向下取整
+    pub fn floor(&self) -> Decimal {
+        self.round_with(|quotient, remainder, _| {
+            if remainder.is_negative() {
+                quotient - 1
+            } else {
+                quotient
+            }
+        })
+    }
+
+    /// 向上取整
+    pub fn ceil(&self) -> Decimal {
+        self.round_with(|quotient, remainder, _| {
+            if remainder.is_positive() {
+                quotient + 1
+            } else {
+                quotient
+            }
+        })
+    }
+
+    /// 四舍五入（0.5 远离零舍入，与 `f64::round` 一致）
+    pub fn round(&self) -> Decimal {
+        self.round_with(|quotient, remainder, divisor| {
+            if remainder.abs() * 2 < *divisor {
+                quotient
+            } else if remainder.is_negative() {
+                quotient - 1
+            } else {
+                quotient + 1
+            }
+        })
+    }
+
+    /// 绝对值
+    pub fn abs(&self) -> Decimal {
+        if self.is_negative() {
+            self.neg()
+        } else {
+            self.clone()
+        }
+    }
+
+    /// 按截断后的商和余数决定取整结果
+    fn round_with<F>(&self, adjust: F) -> Decimal
+    where
+        F: FnOnce(BigInt, BigInt, &BigInt) -> BigInt,
+    {
+        if self.is_integer() {
+            return self.clone();
+        }
+        // |x| < 0.1 时只有符号影响结果，用 ±0.1 代替，避免构造 10 的巨大次幂
+        if self.adjusted_exponent() < -1 {
+            let remainder = self.coefficient.signum();
+            let adjusted = adjust(BigInt::zero(), remainder, &BigInt::from(10));
+            return Decimal::from_integer(adjusted, self.precision);
+        }
+        let divisor = pow10(self.exponent.unsigned_abs() as u32);
+        let quotient = &self.coefficient / &divisor;
+        let remainder = &self.coefficient % &divisor;
+        Decimal::from_integer(adjust(quotient, remainder, &divisor), self.precision)
+    }
+
+    // ===== 定点数转换 =====
+
+    /// 最高位的十进制指数，如 123.4 为 2，0.05 为 -2
+    fn adjusted_exponent(&self) -> i64 {
+        self.exponent + digit_count(&self.coefficient) as i64 - 1
+    }
+
+    /// 转换为以 10^scale 为单位的定点整数（截断）
+    fn to_fixed(&self, scale: u32) -> BigInt {
+        shift_fixed(&self.coefficient, self.exponent + scale as i64)
+    }
+
+    /// 由定点整数创建
+    fn from_fixed(value: BigInt, scale: u32, precision: u32) -> Decimal {
+        Decimal::new(value, -(scale as i64), precision)
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.coefficient == other.coefficient && self.exponent == other.exponent
+    }
+}
+
+impl PartialOrd for Decimal {
+    /// 先比较符号和数量级，数量级相同时才对齐系数
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        let sign = self.coefficient.signum().cmp(&other.coefficient.signum());
+        if sign != Ordering::Equal || self.is_zero() {
+            return Some(sign);
+        }
+
+        let magnitude = match self.adjusted_exponent().cmp(&other.adjusted_exponent()) {
+            Ordering::Equal => {
+                let exponent = self.exponent.min(other.exponent);
+                let left = shift_fixed(&self.coefficient.abs(), self.exponent - exponent);
+                let right = shift_fixed(&other.coefficient.abs(), other.exponent - exponent);
+                left.cmp(&right)
+            }
+            ordering => ordering,
+        };
+        Some(if self.is_negative() {
+            magnitude.reverse()
+        } else {
+            magnitude
+        })
+    }
+}
+
+impl fmt::Display for Decimal {
+    /// 一般用普通小数形式；数量级过大或过小时用科学计数法
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.is_negative() { "-" } else { "" };
+        let digits = self.coefficient.abs().to_string();
+        let adjusted = self.adjusted_exponent();
+
+        if !(-10..40).contains(&adjusted) {
+            let (first, rest) = digits.split_at(1);
+            return if rest.is_empty() {
+                write!(f, "{}{}e{}", sign, first, adjusted)
+            } else {
+                write!(f, "{}{}.{}e{}", sign, first, rest, adjusted)
+            };
+        }
+
+        if self.exponent >= 0 {
+            write!(
+                f,
+                "{}{}{}",
+                sign,
+                digits,
+                "0".repeat(self.exponent as usize)
+            )
+        } else if adjusted >= 0 {
+            let (integer, fraction) = digits.split_at(adjusted as usize + 1);
+            write!(f, "{}{}.{}", sign, integer, fraction)
+        } else {
+            let zeros = "0".repeat((-adjusted - 1) as usize);
+            write!(f, "{}0.{}{}", sign, zeros, digits)
+        }
+    }
+}
+
+/// 十进制模式下可以直接计算的内置函数；不支持时返回 None
+pub(crate) fn decimal_function(name: &str, args: &[Decimal]) -> Option<Result<Decimal, Error>> {
+    let result = match (name, args) {
+        ("sqrt", [x]) => x.sqrt(),
+        ("ln", [x]) => x.ln(),
+        ("log", [x, base]) => x.log(base),
+        ("log10", [x]) => x.log(&Decimal::from_integer(10.into(), x.precision())),
+        ("log2", [x]) => x.log(&Decimal::from_integer(2.into(), x.precision())),
+        ("exp", [x]) => x.exp(),
+        ("sin", [x]) => x.sin(),
+        ("cos", [x]) => x.cos(),
+        ("tan", [x]) => x.tan(),
+        ("abs", [x]) => Ok(x.abs()),
+        ("floor", [x]) => Ok(x.floor()),
+        ("ceil", [x]) => Ok(x.ceil()),
+        ("round", [x]) => Ok(x.round()),
+        ("trunc", [x]) if x.is_negative() => Ok(x.ceil()),
+        ("trunc", [x]) => Ok(x.floor()),
+        ("sign", [x]) => {
+            let sign = if x.is_zero() {
+                0
+            } else if x.is_negative() {
+                -1
+            } else {
+                1
+            };
+            Ok(Decimal::from_integer(sign.into(), x.precision()))
+        }
+        ("max", [first, rest @ ..]) => Ok(rest
+            .iter()
+            .fold(first, |a, b| if b > a { b } else { a })
+            .clone()),
+        ("min", [first, rest @ ..]) => Ok(rest
+            .iter()
+            .fold(first, |a, b| if b < a { b } else { a })
+            .clone()),
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// 10 的 n 次方
+fn pow10(n: u32) -> BigInt {
+    BigInt::from(10).pow(n)
+}
+
+/// 整数的十进制位数（0 记为 1 位）
+fn digit_count(value: &BigInt) -> usize {
+    if value.is_zero() {
+        1
+    } else {
+        value.abs().to_string().len()
+    }
+}
+
+/// value × 10^shift（shift 为负时截断）
+fn shift_fixed(value: &BigInt, shift: i64) -> BigInt {
+    if shift < -(digit_count(value) as i64) {
+        BigInt::zero()
+    } else if shift >= 0 {
+        value * pow10(shift as u32)
+    } else {
+        value / pow10(shift.unsigned_abs() as u32)
+    }
+}
+
+/// 截断除法有余数时，在末尾追加一个非零位，使之后的舍入不会把它当作恰好一半
+fn sticky(quotient: BigInt, remainder: &BigInt, exponent: i64) -> (BigInt, i64) {
+    if remainder.is_zero() {
+        (quotient, exponent)
+    } else {
+        let digit = if quotient.is_negative() || remainder.is_negative() {
+            -1
+        } else {
+            1
+        };
+        (quotient * 10 + digit, exponent - 1)
+    }
+}
+
+/// 按有效位数舍入（四舍六入五成双）
+fn round_digits(coefficient: BigInt, exponent: i64, precision: u32) -> (BigInt, i64) {
+    let digits = digit_count(&coefficient);
+    if digits <= precision as usize {
+        return (coefficient, exponent);
+    }
+
+    let drop = (digits - precision as usize) as u32;
+    let divisor = pow10(drop);
+    let mut quotient = &coefficient / &divisor;
+    let remainder = &coefficient % &divisor;
+
+    let twice: BigInt = remainder.abs() * 2;
+    let round_up = match twice.cmp(&divisor) {
+        Ordering::Greater => true,
+        Ordering::Equal => quotient.is_odd(),
+        Ordering::Less => false,
+    };
+    if round_up {
+        if coefficient.is_negative() {
+            quotient -= 1;
+        } else {
+            quotient += 1;
+        }
+    }
+
+    // 进位可能多出一位，如 999 → 1000
+    let exponent = exponent + drop as i64;
+    if digit_count(&quotient) > precision as usize {
+        (quotient / 10, exponent + 1)
+    } else {
+        (quotient, exponent)
+    }
+}
+
+/// 去掉系数末尾的 0
+fn strip_zeros(mut coefficient: BigInt, mut exponent: i64) -> (BigInt, i64) {
+    if coefficient.is_zero() {
+        return (coefficient, 0);
+    }
+    let ten = BigInt::from(10);
+    while (&coefficient % &ten).is_zero() {
+        coefficient /= &ten;
+        exponent += 1;
+    }
+    (coefficient, exponent)
+}
+
+/// arctan(1/n)（hyperbolic 为 true 时计算 artanh(1/n)），定点数
+fn arctan_inverse(n: u32, scale: u32, hyperbolic: bool) -> BigInt {
+    let n2 = BigInt::from(n) * n;
+    let mut power = pow10(scale) / n;
+    let mut sum = BigInt::zero();
+    let mut k = 0u32;
+
+    while !power.is_zero() {
+        let term = &power / (2 * k + 1);
+        if hyperbolic || k.is_multiple_of(2) {
+            sum += term;
+        } else {
+            sum -= term;
+        }
+        power /= &n2;
+        k += 1;
+    }
+    sum
+}
+
+/// π（Machin 公式：π = 16·arctan(1/5) − 4·arctan(1/239)），定点数
+fn pi_fixed(scale: u32) -> BigInt {
+    let working = scale + 5;
+    let pi = arctan_inverse(5, working, false) * 16 - arctan_inverse(239, working, false) * 4;
+    pi / pow10(5)
+}
+
+/// ln 2 = 2·artanh(1/3)，定点数
+fn ln2_fixed(scale: u32) -> BigInt {
+    let working = scale + 5;
+    arctan_inverse(3, working, true) * 2 / pow10(5)
+}
+
+/// ln x（x 为正的定点数），先除以 2 的幂使 x 落在 [0.75, 1.5) 内
+fn ln_fixed(x: &BigInt, scale: u32) -> BigInt {
+    let one = pow10(scale);
+    let upper = &one * 3 / 2;
+    let lower = &one * 3 / 4;
+
+    let mut y = x.clone();
+    let mut k: i64 = 0;
+    while y >= upper {
+        y /= 2;
+        k += 1;
+    }
+    while y < lower {
+        y *= 2;
+        k -= 1;
+    }
+
+    // ln y = 2·artanh(z)，z = (y − 1) / (y + 1)，|z| ≤ 0.2
+    let z = (&y - &one) * &one / (&y + &one);
+    let z2 = &z * &z / &one;
+    let mut power = z;
+    let mut sum = BigInt::zero();
+    let mut i = 0u32;
+    while !power.is_zero() {
+        sum += &power / (2 * i + 1);
+        power = &power * &z2 / &one;
+        i += 1;
+    }
+
+    sum * 2 + ln2_fixed(scale) * k
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(text: &str, precision: u32) -> Decimal {
+        Decimal::parse(text, precision).unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        assert_eq!(dec("12.500", 10).to_string(), "12.5");
+        assert_eq!(dec("-0.0012", 10).to_string(), "-0.0012");
+        assert_eq!(dec("1200", 10).to_string(), "1200");
+        assert_eq!(dec("1.23456", 3).to_string(), "1.23");
+    }
+
+    #[test]
+    fn test_round_half_even() {
+        assert_eq!(dec("2.5", 1).to_string(), "2");
+        assert_eq!(dec("3.5", 1).to_string(), "4");
+        assert_eq!(dec("9.96", 2).to_string(), "10");
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let sum = dec("0.1", 30).add(&dec("0.2", 30));
+        assert_eq!(sum.to_string(), "0.3");
+
+        // 数量级相差悬殊时直接返回较大的一方
+        let huge = Decimal::new(BigInt::from(1), 10_000_000_000, 30);
+        assert_eq!(huge.add(&dec("1", 30)), huge);
+        assert!(huge > dec("1", 30) && huge.neg() < dec("-1", 30));
+        assert_eq!(huge.to_rational(), None);
+
+        let third = dec("1", 30).div(&dec("3", 30)).unwrap();
+        assert_eq!(third.to_string(), format!("0.{}", "3".repeat(30)));
+
+        assert_eq!(dec("1", 10).div(&dec("0", 10)), Err(Error::DivisionByZero));
+    }
+
+    #[test]
+    fn test_constants() {
+        assert_eq!(
+            Decimal::pi(30).to_string(),
+            "3.14159265358979323846264338328"
+        );
+        assert_eq!(
+            Decimal::e(30).to_string(),
+            "2.71828182845904523536028747135"
+        );
+    }
+
+    #[test]
+    fn test_transcendental_functions() {
+        assert_eq!(
+            dec("2", 30).sqrt().unwrap().to_string(),
+            "1.41421356237309504880168872421"
+        );
+        assert_eq!(
+            dec("2", 30).ln().unwrap().to_string(),
+            "0.693147180559945309417232121458"
+        );
+        assert_eq!(
+            dec("1", 30).sin().unwrap().to_string(),
+            "0.84147098480789650665250232163"
+        );
+        assert_eq!(dec("0", 30).cos().unwrap().to_string(), "1");
+        let tiny = Decimal::new(BigInt::from(1), -50, 30);
+        assert_eq!(tiny.sin().unwrap(), tiny);
+        assert!(Decimal::new(BigInt::from(1), 100_000, 30).sin().is_err());
+        assert_eq!(
+            dec("1000", 20).log(&dec("10", 20)).unwrap().to_string(),
+            "3"
+        );
+    }
+
+    #[test]
+    fn test_pow() {
+        let result = dec("1.01", 30).pow(&dec("12", 30)).unwrap().unwrap();
+        assert_eq!(result.to_string(), "1.126825030131969720661201");
+
+        let root = dec("2", 30).pow(&dec("0.5", 30)).unwrap().unwrap();
+        assert_eq!(root, dec("2", 30).sqrt().unwrap());
+
+        assert_eq!(dec("-8", 10).pow(&dec("0.5", 10)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_rounding_functions() {
+        assert_eq!(dec("-3.5", 10).floor().to_string(), "-4");
+        assert_eq!(dec("-3.5", 10).ceil().to_string(), "-3");
+        assert_eq!(dec("2.5", 10).round().to_string(), "3");
+        assert_eq!(dec("-2.5", 10).round().to_string(), "-3");
+    }
+}
@@ -0,0 +1,385 @@
+// calculator/derivative.rs - 表达式的符号求导
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+
+use super::parser::AstNode;
+use super::value::AngleMode;
+use crate::utils::Error;
+
+/// 对表达式关于变量 `var` 求导，返回导数的语法树（三角函数按弧度求导）
+///
+/// 依次应用和差、乘积、商、幂以及链式法则，并在构造结果时消去 `0 + u`、`1 * u`
+/// 这类平凡的项：
+///
+/// ```
+/// use chapter02::calculator::derivative::derive;
+/// use chapter02::calculator::parser::ExpressionParser;
+/// use chapter02::calculator::AstNode;
+///
+/// let parser = ExpressionParser::new();
+/// let ast = parser.parse(parser.tokenize("x^3").unwrap()).unwrap();
+/// let expected = parser.parse(parser.tokenize("3 * x^2").unwrap()).unwrap();
+/// assert_eq!(derive(&ast, "x").unwrap(), expected);
+/// ```
+pub fn derive(ast: &AstNode, var: &str) -> Result<AstNode, Error> {
+    derive_in(ast, var, AngleMode::Radians)
+}
+
+/// 按指定的角度模式求导：三角函数的导数乘以 π / 半周，反三角函数的导数除以它
+pub fn derive_in(ast: &AstNode, var: &str, angle_mode: AngleMode) -> Result<AstNode, Error> {
+    let angle_factor = match angle_mode {
+        AngleMode::Radians => num(1.0),
+        mode => div(constant("pi"), num(mode.half_turn())),
+    };
+    Differentiator { var, angle_factor }.derive(ast)
+}
+
+/// 判断表达式是否含有变量 `var`
+pub fn depends_on(ast: &AstNode, var: &str) -> bool {
+    match ast {
+        AstNode::Variable(name) => name == var,
+        AstNode::Number(_)
+        | AstNode::Literal(_)
+        | AstNode::Imaginary(_)
+        | AstNode::Constant(_)
+        | AstNode::Invalid => false,
+        AstNode::BinaryOp { left, right, .. } => depends_on(left, var) || depends_on(right, var),
+        AstNode::UnaryOp { operand, .. }
+        | AstNode::Quantity { value: operand, .. }
+        | AstNode::Convert { value: operand, .. }
+        | AstNode::Assign { value: operand, .. } => depends_on(operand, var),
+        AstNode::FunctionCall { args, .. } => args.iter().any(|arg| depends_on(arg, var)),
+        AstNode::FunctionDef { .. } => false,
+    }
+}
+
+/// 把表达式中的变量替换为对应的子表达式（同时替换，替换结果中的变量不再展开）
+pub fn substitute(ast: &AstNode, bindings: &HashMap<String, AstNode>) -> AstNode {
+    match ast {
+        AstNode::Variable(name) => bindings.get(name).cloned().unwrap_or_else(|| ast.clone()),
+        _ => ast
+            .map_children(|child| Ok::<_, Infallible>(substitute(child, bindings)))
+            .unwrap_or_else(|never| match never {}),
+    }
+}
+
+/// 求导时的上下文
+struct Differentiator<'a> {
+    /// 求导变量
+    var: &'a str,
+    /// 角度单位到弧度的换算系数（弧度模式下为 1）
+    angle_factor: AstNode,
+}
+
+impl Differentiator<'_> {
+    fn derive(&self, ast: &AstNode) -> Result<AstNode, Error> {
+        match ast {
+            AstNode::Number(_)
+            | AstNode::Literal(_)
+            | AstNode::Imaginary(_)
+            | AstNode::Constant(_) => Ok(num(0.0)),
+
+            AstNode::Variable(name) => Ok(num(if name == self.var { 1.0 } else { 0.0 })),
+
+            AstNode::UnaryOp { op: '-', operand } => Ok(neg(self.derive(operand)?)),
+            AstNode::UnaryOp { op: '+', operand } => self.derive(operand),
+            AstNode::UnaryOp { .. } => Err(Error::InvalidOperator),
+
+            AstNode::BinaryOp { op, left, right } => self.derive_binary(*op, left, right),
+
+            AstNode::FunctionCall { name, args } => self.derive_function(name, args),
+
+            AstNode::Quantity { .. } | AstNode::Convert { .. } => {
+                Err(Error::Other("带单位的表达式不能求导".to_string()))
+            }
+
+            AstNode::Assign { .. } | AstNode::FunctionDef { .. } | AstNode::Invalid => {
+                Err(Error::InvalidExpression)
+            }
+        }
+    }
+
+    /// 和差、乘积、商与幂的求导法则
+    fn derive_binary(&self, op: char, u: &AstNode, v: &AstNode) -> Result<AstNode, Error> {
+        let (du, dv) = (self.derive(u)?, self.derive(v)?);
+        let (u, v) = (u.clone(), v.clone());
+        Ok(match op {
+            '+' => add(du, dv),
+            '-' => sub(du, dv),
+            // (uv)' = u'v + uv'
+            '*' => add(mul(du, v), mul(u, dv)),
+            // (u/v)' = (u'v − uv') / v²
+            '/' => div(sub(mul(du, v.clone()), mul(u, dv)), pow(v, num(2.0))),
+            // 指数是常数：(uⁿ)' = n·uⁿ⁻¹·u'
+            '^' if !depends_on(&v, self.var) => {
+                let exponent = sub(v.clone(), num(1.0));
+                mul(mul(v, pow(u, exponent)), du)
+            }
+            // 底数是常数：(aᵛ)' = aᵛ·ln(a)·v'
+            '^' if !depends_on(&u, self.var) => {
+                mul(mul(pow(u.clone(), v), call("ln", vec![u])), dv)
+            }
+            // 一般情形：(uᵛ)' = uᵛ·(v'·ln(u) + v·u'/u)
+            '^' => {
+                let inner = add(
+                    mul(dv, call("ln", vec![u.clone()])),
+                    div(mul(v.clone(), du), u.clone()),
+                );
+                mul(pow(u, v), inner)
+            }
+            _ => return Err(Error::InvalidOperator),
+        })
+    }
+
+    /// 内置函数的求导：外层函数的导数乘以参数的导数（链式法则）
+    fn derive_function(&self, name: &str, args: &[AstNode]) -> Result<AstNode, Error> {
+        let name = name.to_lowercase();
+        match (name.as_str(), args) {
+            ("atan2", [y, x]) => {
+                // atan2(y, x)' = (x·y' − y·x') / (x² + y²)
+                let numerator = sub(
+                    mul(x.clone(), self.derive(y)?),
+                    mul(y.clone(), self.derive(x)?),
+                );
+                let denominator = add(pow(x.clone(), num(2.0)), pow(y.clone(), num(2.0)));
+                Ok(div(div(numerator, denominator), self.angle_factor.clone()))
+            }
+            ("hypot", [x, y]) => {
+                // hypot(x, y)' = (x·x' + y·y') / hypot(x, y)
+                let numerator = add(
+                    mul(x.clone(), self.derive(x)?),
+                    mul(y.clone(), self.derive(y)?),
+                );
+                Ok(div(numerator, call("hypot", args.to_vec())))
+            }
+            // log(u, b) = ln(u) / ln(b)，root(u, n) = u^(1/n)
+            ("log", [u, base]) => self.derive(&div(
+                call("ln", vec![u.clone()]),
+                call("ln", vec![base.clone()]),
+            )),
+            ("root", [u, n]) => self.derive(&pow(u.clone(), div(num(1.0), n.clone()))),
+            (_, [u]) => {
+                let du = self.derive(u)?;
+                if du == num(0.0) {
+                    return Ok(du);
+                }
+                Ok(mul(self.outer_derivative(&name, u)?, du))
+            }
+            _ => Err(Error::NotDifferentiable(name)),
+        }
+    }
+
+    /// 单参数函数 f 在 u 处的导数 f'(u)
+    fn outer_derivative(&self, name: &str, u: &AstNode) -> Result<AstNode, Error> {
+        let u = u.clone();
+        let f = |name: &str| call(name, vec![u.clone()]);
+        let square = || pow(u.clone(), num(2.0));
+        let k = self.angle_factor.clone();
+
+        Ok(match name {
+            "sin" => mul(f("cos"), k),
+            "cos" => neg(mul(f("sin"), k)),
+            "tan" => div(k, pow(f("cos"), num(2.0))),
+            "asin" => div(num(1.0), mul(sqrt(sub(num(1.0), square())), k)),
+            "acos" => neg(div(num(1.0), mul(sqrt(sub(num(1.0), square())), k))),
+            "atan" => div(num(1.0), mul(add(num(1.0), square()), k)),
+            "sinh" => f("cosh"),
+            "cosh" => f("sinh"),
+            "tanh" => div(num(1.0), pow(f("cosh"), num(2.0))),
+            "asinh" => div(num(1.0), sqrt(add(square(), num(1.0)))),
+            "acosh" => div(num(1.0), sqrt(sub(square(), num(1.0)))),
+            "atanh" => div(num(1.0), sub(num(1.0), square())),
+            "sqrt" => div(num(1.0), mul(num(2.0), f("sqrt"))),
+            "cbrt" => div(num(1.0), mul(num(3.0), pow(f("cbrt"), num(2.0)))),
+            "exp" => f("exp"),
+            "ln" => div(num(1.0), u),
+            "log10" => div(num(1.0), mul(u, call("ln", vec![num(10.0)]))),
+            "log2" => div(num(1.0), mul(u, call("ln", vec![num(2.0)]))),
+            "abs" => f("sign"),
+            // 分段常数的函数，导数几乎处处为 0
+            "sign" | "ceil" | "floor" | "round" | "trunc" => num(0.0),
+            "gamma" => mul(f("gamma"), f("digamma")),
+            "lgamma" => f("digamma"),
+            // n! = Γ(n + 1)
+            "factorial" => {
+                let shifted = add(u, num(1.0));
+                mul(
+                    call("gamma", vec![shifted.clone()]),
+                    call("digamma", vec![shifted]),
+                )
+            }
+            // erf(u)' = 2/√π · e^(−u²)
+            "erf" => mul(
+                div(num(2.0), sqrt(constant("pi"))),
+                call("exp", vec![neg(square())]),
+            ),
+            _ => return Err(Error::NotDifferentiable(name.to_string())),
+        })
+    }
+}
+
+// ===== 构造结果的辅助函数：顺带消去平凡的项 =====
+
+fn num(n: f64) -> AstNode {
+    AstNode::Number(n)
+}
+
+fn constant(name: &str) -> AstNode {
+    AstNode::Constant(name.to_string())
+}
+
+fn call(name: &str, args: Vec<AstNode>) -> AstNode {
+    AstNode::FunctionCall {
+        name: name.to_string(),
+        args,
+    }
+}
+
+fn sqrt(u: AstNode) -> AstNode {
+    call("sqrt", vec![u])
+}
+
+fn binary(op: char, left: AstNode, right: AstNode) -> AstNode {
+    AstNode::BinaryOp {
+        op,
+        left: Box::new(left),
+        right: Box::new(right),
+    }
+}
+
+fn neg(u: AstNode) -> AstNode {
+    match u {
+        AstNode::Number(n) => num(-n),
+        AstNode::UnaryOp { op: '-', operand } => *operand,
+        u => AstNode::UnaryOp {
+            op: '-',
+            operand: Box::new(u),
+        },
+    }
+}
+
+fn add(a: AstNode, b: AstNode) -> AstNode {
+    match (a, b) {
+        (AstNode::Number(x), AstNode::Number(y)) => num(x + y),
+        (AstNode::Number(0.0), b) => b,
+        (a, AstNode::Number(0.0)) => a,
+        (a, AstNode::UnaryOp { op: '-', operand }) => binary('-', a, *operand),
+        (a, b) => binary('+', a, b),
+    }
+}
+
+fn sub(a: AstNode, b: AstNode) -> AstNode {
+    match (a, b) {
+        (AstNode::Number(x), AstNode::Number(y)) => num(x - y),
+        (a, AstNode::Number(0.0)) => a,
+        (AstNode::Number(0.0), b) => neg(b),
+        (a, b) => binary('-', a, b),
+    }
+}
+
+fn mul(a: AstNode, b: AstNode) -> AstNode {
+    match (a, b) {
+        (AstNode::Number(x), AstNode::Number(y)) => num(x * y),
+        (AstNode::Number(0.0), _) | (_, AstNode::Number(0.0)) => num(0.0),
+        (AstNode::Number(1.0), b) => b,
+        (a, AstNode::Number(1.0)) => a,
+        (AstNode::Number(-1.0), b) => neg(b),
+        (a, AstNode::Number(-1.0)) => neg(a),
+        (AstNode::UnaryOp { op: '-', operand }, b) => neg(mul(*operand, b)),
+        (a, AstNode::UnaryOp { op: '-', operand }) => neg(mul(a, *operand)),
+        // 常数因子放在前面：u * 3 → 3 * u
+        (a, b @ AstNode::Number(_)) => binary('*', b, a),
+        // (1 / d) * u → u / d
+        (
+            AstNode::BinaryOp {
+                op: '/',
+                left,
+                right,
+            },
+            b,
+        ) if *left == num(1.0) => div(b, *right),
+        (a, b) => binary('*', a, b),
+    }
+}
+
+fn div(a: AstNode, b: AstNode) -> AstNode {
+    match (a, b) {
+        (AstNode::Number(0.0), _) => num(0.0),
+        (a, AstNode::Number(1.0)) => a,
+        (AstNode::Number(x), b) if x < 0.0 => neg(div(num(-x), b)),
+        (AstNode::UnaryOp { op: '-', operand }, b) => neg(div(*operand, b)),
+        (a, b) => binary('/', a, b),
+    }
+}
+
+fn pow(a: AstNode, b: AstNode) -> AstNode {
+    match b {
+        AstNode::Number(0.0) => num(1.0),
+        AstNode::Number(1.0) => a,
+        b => binary('^', a, b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculator::parser::ExpressionParser;
+
+    fn parse(expression: &str) -> AstNode {
+        let parser = ExpressionParser::new();
+        parser.parse(parser.tokenize(expression).unwrap()).unwrap()
+    }
+
+    fn assert_derivative(expression: &str, expected: &str) {
+        assert_eq!(derive(&parse(expression), "x").unwrap(), parse(expected));
+    }
+
+    #[test]
+    fn test_basic_rules() {
+        assert_derivative("5", "0");
+        assert_derivative("x", "1");
+        assert_derivative("y", "0");
+        assert_derivative("3 * x + 2", "3");
+        assert_derivative("x^3", "3 * x^2");
+        assert_derivative("x * y", "y");
+        assert_derivative("1 / x", "-(1 / x^2)");
+        assert_derivative("2^x", "2^x * ln(2)");
+        assert_derivative("x^x", "x^x * (ln(x) + x / x)");
+    }
+
+    #[test]
+    fn test_chain_rule() {
+        assert_derivative("sin(x)", "cos(x)");
+        assert_derivative("cos(2 * x)", "-(2 * sin(2 * x))");
+        assert_derivative("exp(x^2)", "exp(x^2) * (2 * x)");
+        assert_derivative("ln(x)", "1 / x");
+        assert_derivative("sqrt(x)", "1 / (2 * sqrt(x))");
+        assert_derivative("√x", "1 / (2 * sqrt(x))");
+        assert_derivative("floor(x)", "0");
+    }
+
+    #[test]
+    fn test_degrees() {
+        let derivative = derive_in(&parse("sin(x)"), "x", AngleMode::Degrees).unwrap();
+        assert_eq!(derivative, parse("cos(x) * (π / 180)"));
+    }
+
+    #[test]
+    fn test_not_differentiable() {
+        assert_eq!(
+            derive(&parse("max(x, 1)"), "x"),
+            Err(Error::NotDifferentiable("max".to_string()))
+        );
+        assert_eq!(
+            derive(&parse("gcd(x, 4)"), "x"),
+            Err(Error::NotDifferentiable("gcd".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_substitute() {
+        let bindings = HashMap::from([("x".to_string(), parse("y + 1"))]);
+        assert_eq!(substitute(&parse("x^2"), &bindings), parse("(y + 1)^2"));
+    }
+}
@@ -0,0 +1,251 @@
+// calculator/environment.rs - 变量环境
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::parser::AstNode;
+use super::value::Value;
+
+/// 用户自定义函数，如 `f(x, y) = x * y + 1`
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserFunction {
+    /// 函数名
+    pub name: String,
+    /// 形参列表
+    pub params: Vec<String>,
+    /// 函数体
+    pub body: AstNode,
+}
+
+impl UserFunction {
+    /// 函数签名，如 `f(x, y)`
+    pub fn signature(&self) -> String {
+        format!("{}({})", self.name, self.params.join(", "))
+    }
+}
+
+/// 变量环境：保存表达式中定义的变量和函数
+///
+/// 环境由求值器持有，在多次 `Calculator::evaluate_expression` 调用之间保持不变，
+/// 因此 `x = 3 * 4` 之后可以继续计算 `x ^ 2`。
+///
+/// 调用用户函数时会压入一个调用帧保存实参；查找变量时只看最内层调用帧，
+/// 然后直接回到全局变量，因此函数体看不到调用者的形参。
+///
+/// 变量以 [`Value`] 保存，有理数模式下赋值不会丢失精度。
+#[derive(Debug, Clone, Default)]
+pub struct Environment {
+    variables: HashMap<String, Value>,
+    functions: HashMap<String, Arc<UserFunction>>,
+    frames: Vec<HashMap<String, Value>>,
+}
+
+impl Environment {
+    /// 创建空的变量环境
+    pub fn new() -> Self {
+        Self {
+            variables: HashMap::new(),
+            functions: HashMap::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    /// 读取变量的值（先查当前调用帧，再查全局变量）
+    pub fn get(&self, name: &str) -> Option<f64> {
+        self.get_value(name).map(|value| value.to_f64())
+    }
+
+    /// 读取变量的原始值（保留精确值）
+    pub fn get_value(&self, name: &str) -> Option<Value> {
+        if let Some(value) = self.frames.last().and_then(|frame| frame.get(name)) {
+            return Some(value.clone());
+        }
+        self.variables.get(name).cloned()
+    }
+
+    /// 设置变量的值（已存在则覆盖）
+    pub fn set(&mut self, name: &str, value: f64) {
+        self.set_value(name, Value::Float(value));
+    }
+
+    /// 设置变量的原始值
+    pub fn set_value(&mut self, name: &str, value: Value) {
+        self.variables.insert(name.to_string(), value);
+    }
+
+    /// 赋值表达式写入变量：在调用帧或约束变量的作用域内写入最内层的帧，否则写入全局变量
+    pub fn assign(&mut self, name: &str, value: Value) {
+        match self.frames.last_mut() {
+            Some(frame) => {
+                frame.insert(name.to_string(), value);
+            }
+            None => self.set_value(name, value),
+        }
+    }
+
+    /// 删除变量，返回原来的值
+    pub fn remove(&mut self, name: &str) -> Option<f64> {
+        self.variables.remove(name).map(|value| value.to_f64())
+    }
+
+    /// 判断变量是否已定义
+    pub fn contains(&self, name: &str) -> bool {
+        self.variables.contains_key(name)
+    }
+
+    /// 清除所有变量和函数
+    pub fn clear(&mut self) {
+        self.variables.clear();
+        self.functions.clear();
+        self.frames.clear();
+    }
+
+    /// 获取已定义变量的数量
+    pub fn len(&self) -> usize {
+        self.variables.len()
+    }
+
+    /// 判断环境是否为空
+    pub fn is_empty(&self) -> bool {
+        self.variables.is_empty()
+    }
+
+    /// 按名称排序列出所有变量
+    pub fn variables(&self) -> Vec<(&str, f64)> {
+        let mut vars: Vec<(&str, f64)> = self
+            .variables
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.to_f64()))
+            .collect();
+        vars.sort_by(|a, b| a.0.cmp(b.0));
+        vars
+    }
+
+    // ===== 用户函数 =====
+
+    /// 定义（或重新定义）用户函数
+    pub fn define_function(&mut self, function: UserFunction) {
+        self.functions
+            .insert(function.name.clone(), Arc::new(function));
+    }
+
+    /// 查找用户函数
+    pub fn function(&self, name: &str) -> Option<Arc<UserFunction>> {
+        self.functions.get(name).cloned()
+    }
+
+    /// 删除用户函数
+    pub fn remove_function(&mut self, name: &str) -> Option<Arc<UserFunction>> {
+        self.functions.remove(name)
+    }
+
+    /// 按名称排序列出所有用户函数
+    pub fn functions(&self) -> Vec<Arc<UserFunction>> {
+        let mut functions: Vec<Arc<UserFunction>> = self.functions.values().cloned().collect();
+        functions.sort_by(|a, b| a.name.cmp(&b.name));
+        functions
+    }
+
+    // ===== 调用帧 =====
+
+    /// 压入调用帧（保存函数实参）
+    pub fn push_frame(&mut self, frame: HashMap<String, Value>) {
+        self.frames.push(frame);
+    }
+
+    /// 压入绑定变量的作用域（用于 `sum`、`prod`、`integrate` 的约束变量）
+    ///
+    /// 新作用域继承当前调用帧，函数体内的求和仍能看到函数的形参。
+    pub fn push_scope(&mut self, name: &str, value: Value) {
+        let mut frame = self.frames.last().cloned().unwrap_or_default();
+        frame.insert(name.to_string(), value);
+        self.frames.push(frame);
+    }
+
+    /// 弹出调用帧
+    pub fn pop_frame(&mut self) {
+        self.frames.pop();
+    }
+
+    /// 当前调用深度
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_and_get() {
+        let mut env = Environment::new();
+        env.set("x", 12.0);
+
+        assert_eq!(env.get("x"), Some(12.0));
+        assert_eq!(env.get("y"), None);
+    }
+
+    #[test]
+    fn test_overwrite_and_remove() {
+        let mut env = Environment::new();
+        env.set("x", 1.0);
+        env.set("x", 2.0);
+
+        assert_eq!(env.len(), 1);
+        assert_eq!(env.remove("x"), Some(2.0));
+        assert!(env.is_empty());
+    }
+
+    #[test]
+    fn test_frame_shadows_globals() {
+        let mut env = Environment::new();
+        env.set("x", 1.0);
+        env.set("y", 2.0);
+        env.push_frame(HashMap::from([("x".to_string(), Value::Float(10.0))]));
+
+        assert_eq!(env.get("x"), Some(10.0));
+        assert_eq!(env.get("y"), Some(2.0));
+        assert_eq!(env.depth(), 1);
+
+        env.pop_frame();
+        assert_eq!(env.get("x"), Some(1.0));
+    }
+
+    #[test]
+    fn test_scope_inherits_frame() {
+        let mut env = Environment::new();
+        env.set("k", 1.0);
+        env.push_frame(HashMap::from([("x".to_string(), Value::Float(10.0))]));
+        env.push_scope("k", Value::Float(3.0));
+
+        assert_eq!(env.get("x"), Some(10.0));
+        assert_eq!(env.get("k"), Some(3.0));
+
+        env.pop_frame();
+        env.pop_frame();
+        assert_eq!(env.get("k"), Some(1.0));
+    }
+
+    #[test]
+    fn test_define_function() {
+        let mut env = Environment::new();
+        env.define_function(UserFunction {
+            name: "f".to_string(),
+            params: vec!["x".to_string(), "y".to_string()],
+            body: AstNode::Number(1.0),
+        });
+
+        assert_eq!(env.function("f").unwrap().signature(), "f(x, y)");
+        assert!(env.function("g").is_none());
+    }
+
+    #[test]
+    fn test_variables_sorted() {
+        let mut env = Environment::new();
+        env.set("b", 2.0);
+        env.set("a", 1.0);
+
+        assert_eq!(env.variables(), vec![("a", 1.0), ("b", 2.0)]);
+    }
+}
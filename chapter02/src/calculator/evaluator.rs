@@ -1,197 +1,861 @@
 // calculator/evaluator.rs - 表达式求值器（简化版）
 
+use std::cell::{Cell, Ref, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use num_bigint::BigInt;
+use num_complex::Complex64;
+
+use super::batch;
+use super::bytecode::{self, CompiledExpr};
+use super::complex;
+use super::decimal::{self, Decimal};
+use super::derivative;
+use super::environment::{Environment, UserFunction};
+use super::integer;
 use super::parser::AstNode;
+use super::quadrature::{self, Integral};
+use super::registry::{Arity, BuiltinFunction, FunctionRegistry};
+use super::solver;
+use super::units::{self, Quantity};
+use super::value::{self, AngleMode, NumberMode, Value};
 use crate::utils::Error;
 
+/// 用户函数调用的最大嵌套深度
+pub const MAX_CALL_DEPTH: usize = 100;
+
+/// 十进制模式默认的有效位数
+pub const DEFAULT_PRECISION: u32 = 10;
+
+/// 参数是角度的三角函数
+pub(crate) const TRIG_FUNCTIONS: [&str; 3] = ["sin", "cos", "tan"];
+
+/// 结果是角度的反三角函数
+pub(crate) const INVERSE_TRIG_FUNCTIONS: [&str; 4] = ["asin", "acos", "atan", "atan2"];
+
+/// 表达式中的求导函数 `diff(expr, var)`：在 var 的当前值处求导数，
+/// `diff(expr, var, at)` 在 var = at 处求值
+const DIFF_FUNCTION: &str = "diff";
+const DIFF_SIGNATURE: &str = "diff(expr, var[, at])";
+
+/// 表达式中的解方程函数：`solve(eq, var)` 从 var 的当前值（未定义时为 1）出发用牛顿法，
+/// `solve(eq, var, guess)` 从 guess 出发，`solve(eq, var, a, b)` 在区间 [a, b] 内用 Brent 方法
+const SOLVE_FUNCTION: &str = "solve";
+const SOLVE_SIGNATURE: &str = "solve(eq, var[, guess | a, b])";
+
+/// 表达式中的定积分函数 `integrate(expr, var, a, b)`，用自适应 Gauss–Kronrod 方法计算
+const INTEGRATE_FUNCTION: &str = "integrate";
+const INTEGRATE_SIGNATURE: &str = "integrate(expr, var, a, b)";
+
+/// 表达式中的求和与连乘 `sum(expr, k, from, to)`、`prod(expr, k, from, to)`，
+/// k 依次取 from 到 to 的整数
+const SUM_FUNCTION: &str = "sum";
+const SUM_SIGNATURE: &str = "sum(expr, k, from, to)";
+const PROD_FUNCTION: &str = "prod";
+const PROD_SIGNATURE: &str = "prod(expr, k, from, to)";
+
+/// 不在注册表中、由求值器特殊处理的函数（参数不预先求值）
+pub(crate) const SPECIAL_FUNCTIONS: [&str; 5] = [
+    DIFF_FUNCTION,
+    SOLVE_FUNCTION,
+    INTEGRATE_FUNCTION,
+    SUM_FUNCTION,
+    PROD_FUNCTION,
+];
+
+/// 求和与连乘最多的项数（嵌套的求和与连乘合计）
+pub const MAX_TERMS: u64 = 1_000_000;
+
+/// 执行一条输入的结果：表达式的值，或者函数定义
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// 表达式的值
+    Value(Value),
+    /// 定义了函数（函数名），函数定义本身没有数值
+    Defined(String),
+}
+
 /// 表达式求值器
 pub struct ExpressionEvaluator {
-    // 预留字段供将来扩展
-    _strict_mode: bool,
+    /// 严格模式：精确数值模式下遇到无法精确计算的运算时报错，而不是退回浮点数
+    strict_mode: bool,
+    /// 数值模式
+    mode: NumberMode,
+    /// 三角函数的角度单位
+    angle_mode: AngleMode,
+    /// 十进制模式的有效位数
+    precision: u32,
+    /// 变量环境（赋值表达式需要在 `&self` 下修改它）
+    env: RefCell<Environment>,
+    /// 内置函数注册表
+    registry: Arc<FunctionRegistry>,
+    /// 正在求值的求和与连乘已经计算的总项数，嵌套的求和共用 `MAX_TERMS` 的额度
+    series_terms: Cell<Option<u64>>,
 }
 
 impl ExpressionEvaluator {
-    /// 创建新的求值器
+    /// 创建新的求值器（使用默认函数集合）
     pub fn new() -> Self {
+        Self::with_environment(Environment::new())
+    }
+
+    /// 使用已有的变量环境创建求值器
+    pub fn with_environment(env: Environment) -> Self {
         Self {
-            _strict_mode: false,
+            strict_mode: false,
+            mode: NumberMode::Float,
+            angle_mode: AngleMode::Radians,
+            precision: DEFAULT_PRECISION,
+            env: RefCell::new(env),
+            registry: Arc::new(FunctionRegistry::with_defaults()),
+            series_terms: Cell::new(None),
         }
     }
 
+    /// 使用指定的函数注册表创建求值器
+    pub fn with_registry(registry: FunctionRegistry) -> Self {
+        Self {
+            strict_mode: false,
+            mode: NumberMode::Float,
+            angle_mode: AngleMode::Radians,
+            precision: DEFAULT_PRECISION,
+            env: RefCell::new(Environment::new()),
+            registry: Arc::new(registry),
+            series_terms: Cell::new(None),
+        }
+    }
+
+    /// 获取函数注册表
+    pub fn registry(&self) -> &FunctionRegistry {
+        &self.registry
+    }
+
+    /// 设置数值模式
+    pub fn set_number_mode(&mut self, mode: NumberMode) {
+        self.mode = mode;
+    }
+
+    /// 获取数值模式
+    pub fn number_mode(&self) -> NumberMode {
+        self.mode
+    }
+
+    /// 设置三角函数及其反函数使用的角度单位
+    pub fn set_angle_mode(&mut self, mode: AngleMode) {
+        self.angle_mode = mode;
+    }
+
+    /// 获取角度单位
+    pub fn angle_mode(&self) -> AngleMode {
+        self.angle_mode
+    }
+
+    /// 设置十进制模式的有效位数
+    pub fn set_precision(&mut self, precision: u32) {
+        self.precision = precision.max(1);
+    }
+
+    /// 获取十进制模式的有效位数
+    pub fn precision(&self) -> u32 {
+        self.precision
+    }
+
+    /// 设置严格模式
+    pub fn set_strict_mode(&mut self, strict: bool) {
+        self.strict_mode = strict;
+    }
+
+    /// 是否处于严格模式
+    pub fn is_strict(&self) -> bool {
+        self.strict_mode
+    }
+
+    /// 获取变量环境
+    pub fn environment(&self) -> Ref<'_, Environment> {
+        self.env.borrow()
+    }
+
+    /// 设置变量
+    pub fn set_variable(&self, name: &str, value: f64) {
+        self.env.borrow_mut().set(name, value);
+    }
+
+    /// 读取变量
+    pub fn get_variable(&self, name: &str) -> Option<f64> {
+        self.env.borrow().get(name)
+    }
+
+    /// 清除所有变量
+    pub fn clear_variables(&self) {
+        self.env.borrow_mut().clear();
+    }
+
     /// 对抽象语法树进行求值
+    ///
+    /// 结果是复数时返回 `Error::ComplexResult`，需要复数结果请使用 `evaluate_value`。
     pub fn evaluate(&self, ast: &AstNode) -> Result<f64, Error> {
+        self.evaluate_value(ast)?.to_real()
+    }
+
+    /// 执行语法树：函数定义返回 `Outcome::Defined`，其他表达式返回求出的值
+    pub fn execute(&self, ast: &AstNode) -> Result<Outcome, Error> {
+        match ast {
+            AstNode::FunctionDef { name, .. } => {
+                self.evaluate_value(ast)?;
+                Ok(Outcome::Defined(name.clone()))
+            }
+            _ => self.evaluate_value(ast).map(Outcome::Value),
+        }
+    }
+
+    /// 按当前数值模式求值，保留精确结果
+    pub fn evaluate_value(&self, ast: &AstNode) -> Result<Value, Error> {
         match ast {
-            AstNode::Number(n) => Ok(*n),
+            AstNode::Number(n) => self.number(*n),
+
+            AstNode::Literal(text) => Value::from_literal(text, self.mode, self.precision),
+
+            AstNode::Imaginary(n) => Ok(Value::complex(Complex64::new(0.0, *n))),
+
+            AstNode::Quantity { value, unit } => {
+                let amount = self.evaluate_value(value)?.to_f64();
+                Ok(Value::quantity(Quantity::new(amount, unit.clone())))
+            }
+
+            AstNode::Convert { value, unit } => {
+                let quantity = self.evaluate_value(value)?.to_quantity()?;
+                quantity.convert(unit.clone()).map(Value::Quantity)
+            }
+
+            AstNode::Constant(name) => self
+                .constant(name)
+                .unwrap_or_else(|| Err(Error::UndefinedVariable(name.clone()))),
+
+            AstNode::BinaryOp { op: '=', .. } => Err(Error::Other(format!(
+                "方程只能作为 {} 的参数",
+                SOLVE_SIGNATURE
+            ))),
 
             AstNode::BinaryOp { op, left, right } => {
-                let left_val = self.evaluate(left)?;
-                let right_val = self.evaluate(right)?;
+                let mut left_val = self.evaluate_value(left)?;
+                let right_val = self.evaluate_value(right)?;
 
-                match op {
-                    '+' => Ok(left_val + right_val),
-                    '-' => Ok(left_val - right_val),
-                    '*' => Ok(left_val * right_val),
-                    '/' => {
-                        if right_val == 0.0 {
-                            Err(Error::DivisionByZero)
-                        } else {
-                            Ok(left_val / right_val)
-                        }
-                    }
-                    '^' => Ok(left_val.powf(right_val)),
-                    _ => Err(Error::InvalidOperator),
+                // 复数模式下负数的非整数次幂取复数主值，如 (-8)^(1/3)
+                let negative_root = left_val.to_f64() < 0.0 && right_val.to_f64().fract() != 0.0;
+                if self.mode == NumberMode::Complex && *op == '^' && negative_root {
+                    left_val = Value::Complex(left_val.to_complex());
                 }
+
+                let result = Value::binary(*op, &left_val, &right_val)?;
+                self.check_exact(&[left_val, right_val], &result, || format!("{}", op))?;
+                Ok(result)
             }
 
             AstNode::UnaryOp { op, operand } => {
-                let val = self.evaluate(operand)?;
+                let val = self.evaluate_value(operand)?;
 
                 match op {
-                    '-' => Ok(-val),
+                    '-' => Ok(val.neg()),
                     '+' => Ok(val),
                     _ => Err(Error::InvalidOperator),
                 }
             }
 
             AstNode::FunctionCall { name, args } => self.evaluate_function(name, args),
+
+            // 未定义的 pi 和 e 视为常量
+            AstNode::Variable(name) => {
+                let value = self.env.borrow().get_value(name);
+                match value {
+                    Some(value) => Ok(value),
+                    None => self
+                        .constant(name)
+                        .unwrap_or_else(|| Err(Error::UndefinedVariable(name.clone()))),
+                }
+            }
+
+            AstNode::Assign { name, value } => {
+                let val = self.evaluate_value(value)?;
+                self.env.borrow_mut().assign(name, val.clone());
+                Ok(val)
+            }
+
+            AstNode::Invalid => Err(Error::InvalidExpression),
+
+            // 函数定义本身没有数值，求值结果记为 0；需要区分时用 `execute`
+            AstNode::FunctionDef { name, params, body } => {
+                self.define_function(UserFunction {
+                    name: name.clone(),
+                    params: params.clone(),
+                    body: (**body).clone(),
+                })?;
+                self.number(0.0)
+            }
+        }
+    }
+
+    /// 按数值模式转换数字
+    fn number(&self, n: f64) -> Result<Value, Error> {
+        match self.mode {
+            NumberMode::Float => Ok(Value::Float(n)),
+            _ => Value::from_literal(&n.to_string(), self.mode, self.precision),
+        }
+    }
+
+    /// 常量 pi 和 e 的值；有理数模式下它们无法精确表示
+    fn constant(&self, name: &str) -> Option<Result<Value, Error>> {
+        let value = match (name, self.mode) {
+            ("pi", NumberMode::Decimal) => Value::Decimal(Decimal::pi(self.precision)),
+            ("e", NumberMode::Decimal) => Value::Decimal(Decimal::e(self.precision)),
+            ("pi", _) => Value::Float(std::f64::consts::PI),
+            ("e", _) => Value::Float(std::f64::consts::E),
+            _ => return None,
+        };
+        Some(
+            self.check_exact(&[], &value, || name.to_string())
+                .map(|_| value),
+        )
+    }
+
+    /// 严格模式下检查：精确的输入不能得到不精确的结果
+    fn check_exact<F>(&self, inputs: &[Value], result: &Value, what: F) -> Result<(), Error>
+    where
+        F: FnOnce() -> String,
+    {
+        let lost = !inputs.iter().any(Value::is_float) && result.is_float();
+        if self.strict_mode && self.mode != NumberMode::Float && lost {
+            return Err(Error::InexactResult(what()));
         }
+        Ok(())
+    }
+
+    /// 定义用户函数
+    ///
+    /// 不允许覆盖内置函数，也不允许函数直接或间接调用自身。
+    pub fn define_function(&self, function: UserFunction) -> Result<(), Error> {
+        let special = SPECIAL_FUNCTIONS.contains(&function.name.as_str());
+        if self.registry.contains(&function.name) || special {
+            return Err(Error::Other(format!(
+                "不能重新定义内置函数: {}",
+                function.name
+            )));
+        }
+
+        let env = self.env.borrow();
+        let mut visited = HashSet::new();
+        if calls_function(&function.body, &function.name, &env, &mut visited) {
+            return Err(Error::RecursiveFunction(function.name));
+        }
+        drop(env);
+
+        self.env.borrow_mut().define_function(function);
+        Ok(())
+    }
+
+    /// 调用用户函数：在新的调用帧中绑定实参后求值函数体
+    fn call_user_function(
+        &self,
+        function: &UserFunction,
+        args: &[AstNode],
+    ) -> Result<Value, Error> {
+        if args.len() != function.params.len() {
+            return Err(Error::InvalidArgumentCount {
+                function: function.signature(),
+                expected: function.params.len(),
+                actual: args.len(),
+            });
+        }
+
+        if self.env.borrow().depth() >= MAX_CALL_DEPTH {
+            return Err(Error::RecursionLimitExceeded(MAX_CALL_DEPTH));
+        }
+
+        // 实参在调用者的环境中求值
+        let mut frame = HashMap::new();
+        for (param, arg) in function.params.iter().zip(args) {
+            frame.insert(param.clone(), self.evaluate_value(arg)?);
+        }
+
+        self.env.borrow_mut().push_frame(frame);
+        let result = self.evaluate_value(&function.body);
+        self.env.borrow_mut().pop_frame();
+        result
     }
 
     /// 求值函数调用
-    fn evaluate_function(&self, name: &str, args: &[AstNode]) -> Result<f64, Error> {
-        match name.to_lowercase().as_str() {
-            "sin" => {
-                if args.len() != 1 {
-                    return Err(Error::InvalidArgumentCount {
-                        function: name.to_string(),
-                        expected: 1,
-                        actual: args.len(),
-                    });
-                }
-                let arg = self.evaluate(&args[0])?;
-                Ok(arg.sin())
+    fn evaluate_function(&self, name: &str, args: &[AstNode]) -> Result<Value, Error> {
+        // 注册表中的内置函数优先，其次是求导、解方程、积分等特殊函数，最后查找用户定义的函数
+        let Some(function) = self.registry.get(name) else {
+            match name {
+                DIFF_FUNCTION => return self.evaluate_derivative(args),
+                SOLVE_FUNCTION => return self.evaluate_solve(args),
+                INTEGRATE_FUNCTION => return self.evaluate_integral(args),
+                SUM_FUNCTION => return self.evaluate_series('+', SUM_SIGNATURE, args),
+                PROD_FUNCTION => return self.evaluate_series('*', PROD_SIGNATURE, args),
+                _ => {}
             }
+            let function = self.env.borrow().function(name);
+            return match function {
+                Some(function) => self.call_user_function(&function, args),
+                None => Err(Error::UndefinedFunction(name.to_string())),
+            };
+        };
 
-            "cos" => {
-                if args.len() != 1 {
-                    return Err(Error::InvalidArgumentCount {
-                        function: name.to_string(),
-                        expected: 1,
-                        actual: args.len(),
-                    });
-                }
-                let arg = self.evaluate(&args[0])?;
-                Ok(arg.cos())
+        function.arity.check(&function.signature, args.len())?;
+        let values = args
+            .iter()
+            .map(|arg| self.evaluate_value(arg))
+            .collect::<Result<Vec<Value>, Error>>()?;
+
+        // 三角函数的参数按角度模式换算为弧度，反三角函数的结果换算回该单位
+        let name = function.name.as_str();
+        if TRIG_FUNCTIONS.contains(&name) {
+            let radians = values
+                .into_iter()
+                .map(|v| self.angle_to_radians(v, function))
+                .collect::<Result<Vec<Value>, Error>>()?;
+            // 换算时多保留的保护位在结果中舍去
+            return match self.call_builtin(function, radians)? {
+                Value::Decimal(d) => Ok(Value::Decimal(d.with_precision(self.precision))),
+                result => Ok(result),
+            };
+        }
+        if INVERSE_TRIG_FUNCTIONS.contains(&name) {
+            let result = self.call_builtin(function, values)?;
+            return self.radians_to_angle(result);
+        }
+        self.call_builtin(function, values)
+    }
+
+    /// 对表达式关于变量符号求导
+    ///
+    /// 用户函数先展开为函数体，三角函数按当前角度模式求导。
+    pub fn derive(&self, ast: &AstNode, var: &str) -> Result<AstNode, Error> {
+        let expanded = self.expand_functions(ast)?;
+        derivative::derive_in(&expanded, var, self.angle_mode)
+    }
+
+    /// 把表达式编译为字节码，variables 依次对应 `CompiledExpr::evaluate` 的参数
+    ///
+    /// 用户函数在编译时展开；不在 variables 中的变量和角度模式取编译时的值。
+    pub fn compile(&self, ast: &AstNode, variables: &[&str]) -> Result<CompiledExpr, Error> {
+        let expanded = self.expand_functions(ast)?;
+        bytecode::compile(self, &expanded, variables)
+    }
+
+    /// 对多列数据逐行求值，每列的列名是表达式中的变量名，每行单独报告错误
+    ///
+    /// 浮点数模式下能编译为字节码时在至多 threads 个线程上并行执行；否则（其它数值模式，
+    /// 或含有 `sum`、带单位的数值）在当前线程中逐行遍历语法树，列的值作为浮点数在调用帧中
+    /// 绑定。字节码总按浮点数计算，因此其它数值模式下只用编译来检查表达式。
+    pub fn evaluate_batch(
+        &self,
+        ast: &AstNode,
+        columns: &[(&str, &[f64])],
+        threads: usize,
+    ) -> Result<Vec<Result<f64, Error>>, Error> {
+        let rows = batch::row_count(columns)?;
+        let names: Vec<&str> = columns.iter().map(|(name, _)| *name).collect();
+        let data: Vec<&[f64]> = columns.iter().map(|(_, column)| *column).collect();
+
+        match self.compile(ast, &names) {
+            Ok(compiled) if self.mode == NumberMode::Float => {
+                Ok(batch::evaluate_compiled(&compiled, &data, rows, threads))
             }
+            Ok(_) | Err(Error::NotCompilable(_)) => Ok((0..rows)
+                .map(|row| {
+                    let frame = names
+                        .iter()
+                        .zip(&data)
+                        .map(|(name, column)| (name.to_string(), Value::Float(column[row])))
+                        .collect();
+                    self.env.borrow_mut().push_frame(frame);
+                    let result = self.evaluate(ast);
+                    self.env.borrow_mut().pop_frame();
+                    result
+                })
+                .collect()),
+            Err(error) => Err(error),
+        }
+    }
 
-            "tan" => {
-                if args.len() != 1 {
-                    return Err(Error::InvalidArgumentCount {
-                        function: name.to_string(),
-                        expected: 1,
-                        actual: args.len(),
-                    });
-                }
-                let arg = self.evaluate(&args[0])?;
-                Ok(arg.tan())
+    /// 把用户函数调用展开为代入实参后的函数体，并把嵌套的 `diff(expr, var)` 替换为导数
+    ///
+    /// 用户函数不允许递归，因此展开总会结束。
+    fn expand_functions(&self, ast: &AstNode) -> Result<AstNode, Error> {
+        let expanded = ast.map_children(|child| self.expand_functions(child))?;
+        let AstNode::FunctionCall { name, args } = &expanded else {
+            return Ok(expanded);
+        };
+        if self.registry.contains(name) {
+            return Ok(expanded);
+        }
+        if let (DIFF_FUNCTION, [expr, AstNode::Variable(var)]) = (name.as_str(), args.as_slice()) {
+            return derivative::derive_in(expr, var, self.angle_mode);
+        }
+
+        let function = self.env.borrow().function(name);
+        match function {
+            Some(function) if function.params.len() == args.len() => {
+                let bindings = function
+                    .params
+                    .iter()
+                    .cloned()
+                    .zip(args.iter().cloned())
+                    .collect();
+                self.expand_functions(&derivative::substitute(&function.body, &bindings))
             }
+            Some(function) => Err(Error::InvalidArgumentCount {
+                function: function.signature(),
+                expected: function.params.len(),
+                actual: args.len(),
+            }),
+            None => Ok(expanded),
+        }
+    }
 
-            "sqrt" => {
-                if args.len() != 1 {
-                    return Err(Error::InvalidArgumentCount {
-                        function: name.to_string(),
-                        expected: 1,
-                        actual: args.len(),
-                    });
-                }
-                let arg = self.evaluate(&args[0])?;
-                if arg < 0.0 {
-                    return Err(Error::NegativeSquareRoot);
-                }
-                Ok(arg.sqrt())
+    /// 求值 `diff(expr, var[, at])`
+    fn evaluate_derivative(&self, args: &[AstNode]) -> Result<Value, Error> {
+        Arity::Range(2, 3).check(DIFF_SIGNATURE, args.len())?;
+        let AstNode::Variable(var) = &args[1] else {
+            return Err(Error::Other("diff 的第二个参数必须是变量名".to_string()));
+        };
+
+        let mut derivative = self.derive(&args[0], var)?;
+        if let Some(at) = args.get(2) {
+            let bindings = HashMap::from([(var.clone(), at.clone())]);
+            derivative = derivative::substitute(&derivative, &bindings);
+        }
+        self.evaluate_value(&derivative)
+    }
+
+    /// 用牛顿法从 guess 出发求解方程 `equation` 中的变量 var
+    ///
+    /// 方程写作 `lhs = rhs`（也可以是赋值形式 `x = rhs`），单独的表达式视为 `expr = 0`。
+    /// 方程能符号求导时使用精确的导数，否则用中心差分近似。
+    pub fn solve_newton(&self, equation: &AstNode, var: &str, guess: f64) -> Result<f64, Error> {
+        let residual = residual(equation);
+        let float = self.float_evaluator();
+        let evaluator = float.as_ref().unwrap_or(self);
+        let f = |x: f64| evaluator.evaluate_at(&residual, var, x);
+        match self.derive(&residual, var) {
+            Ok(derivative) => {
+                let df = |x: f64| evaluator.evaluate_at(&derivative, var, x);
+                solver::newton(f, df, guess)
             }
+            Err(_) => solver::newton(f, |x| solver::numeric_derivative(&f, x), guess),
+        }
+    }
 
-            "ln" => {
-                if args.len() != 1 {
-                    return Err(Error::InvalidArgumentCount {
-                        function: name.to_string(),
-                        expected: 1,
-                        actual: args.len(),
-                    });
-                }
-                let arg = self.evaluate(&args[0])?;
-                if arg <= 0.0 {
-                    return Err(Error::InvalidLogarithm);
-                }
-                Ok(arg.ln())
+    /// 用 Brent 方法在区间 [lower, upper] 内求解方程，要求两端的函数值异号
+    pub fn solve_bracketed(
+        &self,
+        equation: &AstNode,
+        var: &str,
+        lower: f64,
+        upper: f64,
+    ) -> Result<f64, Error> {
+        let residual = residual(equation);
+        let float = self.float_evaluator();
+        let evaluator = float.as_ref().unwrap_or(self);
+        solver::brent(|x| evaluator.evaluate_at(&residual, var, x), lower, upper)
+    }
+
+    /// 找出方程在区间 [lower, upper] 内的全部根（从小到大）
+    pub fn find_roots(
+        &self,
+        equation: &AstNode,
+        var: &str,
+        lower: f64,
+        upper: f64,
+    ) -> Result<Vec<f64>, Error> {
+        let residual = residual(equation);
+        let float = self.float_evaluator();
+        let evaluator = float.as_ref().unwrap_or(self);
+        solver::find_roots(|x| evaluator.evaluate_at(&residual, var, x), lower, upper)
+    }
+
+    /// 求解器使用的浮点数求值器（当前已是浮点数模式时返回 None）
+    ///
+    /// 求解器按 f64 的精度判断收敛，十进制模式的有效位数较少时残差只能算到相应的精度，
+    /// 迭代无法收敛；精确模式下逐步求值又很慢。因此求解时总是按浮点数计算。
+    fn float_evaluator(&self) -> Option<ExpressionEvaluator> {
+        (self.mode != NumberMode::Float).then(|| ExpressionEvaluator {
+            strict_mode: false,
+            mode: NumberMode::Float,
+            angle_mode: self.angle_mode,
+            precision: self.precision,
+            env: RefCell::new(self.env.borrow().clone()),
+            registry: Arc::clone(&self.registry),
+            series_terms: Cell::new(self.series_terms.get()),
+        })
+    }
+
+    /// 把 var 代换为 x 后求值，结果必须是实数
+    fn evaluate_at(&self, ast: &AstNode, var: &str, x: f64) -> Result<f64, Error> {
+        let bindings = HashMap::from([(var.to_string(), AstNode::Number(x))]);
+        self.evaluate(&derivative::substitute(ast, &bindings))
+    }
+
+    /// 求值 `solve(eq, var[, guess | a, b])`
+    fn evaluate_solve(&self, args: &[AstNode]) -> Result<Value, Error> {
+        Arity::Range(2, 4).check(SOLVE_SIGNATURE, args.len())?;
+        let AstNode::Variable(var) = &args[1] else {
+            return Err(Error::Other("solve 的第二个参数必须是变量名".to_string()));
+        };
+
+        let bounds = args[2..]
+            .iter()
+            .map(|arg| self.evaluate(arg))
+            .collect::<Result<Vec<f64>, Error>>()?;
+        let root = match bounds.as_slice() {
+            [lower, upper] => self.solve_bracketed(&args[0], var, *lower, *upper)?,
+            [guess] => self.solve_newton(&args[0], var, *guess)?,
+            _ => {
+                let guess = self.get_variable(var).unwrap_or(1.0);
+                self.solve_newton(&args[0], var, guess)?
             }
+        };
+        Ok(Value::Float(root))
+    }
 
-            "log" => {
-                if args.len() != 2 {
-                    return Err(Error::InvalidArgumentCount {
-                        function: name.to_string(),
-                        expected: 2,
-                        actual: args.len(),
-                    });
-                }
-                let value = self.evaluate(&args[0])?;
-                let base = self.evaluate(&args[1])?;
+    /// 在绑定了 var = value 的新作用域中求值 body（约束变量遮蔽同名的全局变量和形参）
+    fn evaluate_scoped(&self, body: &AstNode, var: &str, value: Value) -> Result<Value, Error> {
+        if self.env.borrow().depth() >= MAX_CALL_DEPTH {
+            return Err(Error::RecursionLimitExceeded(MAX_CALL_DEPTH));
+        }
+        self.env.borrow_mut().push_scope(var, value);
+        let result = self.evaluate_value(body);
+        self.env.borrow_mut().pop_frame();
+        result
+    }
 
-                if value <= 0.0 || base <= 0.0 || base == 1.0 {
-                    return Err(Error::InvalidLogarithm);
+    /// 用自适应 Gauss–Kronrod 方法计算 body 关于 var 从 lower 到 upper 的定积分
+    ///
+    /// 被积函数的值必须是实数；结果附带绝对误差的估计。
+    pub fn integrate(
+        &self,
+        body: &AstNode,
+        var: &str,
+        lower: f64,
+        upper: f64,
+    ) -> Result<Integral, Error> {
+        let f = |x: f64| {
+            let value = self.evaluate_scoped(body, var, Value::Float(x))?;
+            match value {
+                Value::Complex(z) if z.im != 0.0 => {
+                    Err(Error::Other("被积函数的值必须是实数".to_string()))
                 }
-                Ok(value.log(base))
+                value => Ok(value.to_f64()),
             }
+        };
+        quadrature::gauss_kronrod(f, lower, upper)
+    }
 
-            "abs" => {
-                if args.len() != 1 {
-                    return Err(Error::InvalidArgumentCount {
-                        function: name.to_string(),
-                        expected: 1,
-                        actual: args.len(),
-                    });
-                }
-                let arg = self.evaluate(&args[0])?;
-                Ok(arg.abs())
+    /// 求值 `integrate(expr, var, a, b)`
+    fn evaluate_integral(&self, args: &[AstNode]) -> Result<Value, Error> {
+        Arity::Fixed(4).check(INTEGRATE_SIGNATURE, args.len())?;
+        let var = bound_variable(INTEGRATE_FUNCTION, &args[1])?;
+        let (lower, upper) = (self.evaluate(&args[2])?, self.evaluate(&args[3])?);
+        let integral = self.integrate(&args[0], var, lower, upper)?;
+        Ok(Value::Float(integral.value))
+    }
+
+    /// 求值 `sum(expr, k, from, to)`（op 为 '+'）或 `prod(expr, k, from, to)`（op 为 '*'）
+    ///
+    /// 各项按当前数值模式累加或累乘，精确模式下结果仍是精确值；范围为空时结果是 0 或 1。
+    fn evaluate_series(&self, op: char, signature: &str, args: &[AstNode]) -> Result<Value, Error> {
+        Arity::Fixed(4).check(signature, args.len())?;
+        let name = if op == '+' {
+            SUM_FUNCTION
+        } else {
+            PROD_FUNCTION
+        };
+        let var = bound_variable(name, &args[1])?;
+        let from = self.integer_bound(name, &args[2])?;
+        let to = self.integer_bound(name, &args[3])?;
+        if to >= from && (to - from) as u64 >= MAX_TERMS {
+            return Err(too_many_terms(signature));
+        }
+
+        // 最外层的求和开始计数，嵌套的求和（包括函数体内的）累加到同一个计数上
+        let outermost = self.series_terms.get().is_none();
+        if outermost {
+            self.series_terms.set(Some(0));
+        }
+        let result = self.accumulate_series(op, signature, var, from, to, &args[0]);
+        if outermost {
+            self.series_terms.set(None);
+        }
+        let result = result?;
+        if result.is_float() && !result.to_complex().is_finite() {
+            return Err(Error::Divergent(name.to_string()));
+        }
+        Ok(result)
+    }
+
+    /// 逐项求和或连乘，每一项都计入总项数
+    fn accumulate_series(
+        &self,
+        op: char,
+        signature: &str,
+        var: &str,
+        from: i64,
+        to: i64,
+        body: &AstNode,
+    ) -> Result<Value, Error> {
+        let mut result = self.number(if op == '+' { 0.0 } else { 1.0 })?;
+        for k in from..=to {
+            let terms = self.series_terms.get().unwrap_or(0) + 1;
+            if terms > MAX_TERMS {
+                return Err(too_many_terms(signature));
             }
+            self.series_terms.set(Some(terms));
+            let term = self.evaluate_scoped(body, var, self.number(k as f64)?)?;
+            result = Value::binary(op, &result, &term)?;
+        }
+        Ok(result)
+    }
 
-            "ceil" => {
-                if args.len() != 1 {
-                    return Err(Error::InvalidArgumentCount {
-                        function: name.to_string(),
-                        expected: 1,
-                        actual: args.len(),
-                    });
-                }
-                let arg = self.evaluate(&args[0])?;
-                Ok(arg.ceil())
+    /// 求和与连乘的上下限必须是整数
+    fn integer_bound(&self, function: &str, bound: &AstNode) -> Result<i64, Error> {
+        let n = self.evaluate(bound)?;
+        if n.fract() != 0.0 || n.abs() > (1u64 << 53) as f64 {
+            return Err(Error::DomainError {
+                function: function.to_string(),
+                reason: format!("上下限必须是整数，而不是 {}", n),
+            });
+        }
+        Ok(n as i64)
+    }
+
+    /// 弧度与当前角度单位的换算系数 π / 半周，弧度模式下为 None
+    fn angle_factor(&self) -> Result<Option<Value>, Error> {
+        if self.angle_mode == AngleMode::Radians {
+            return Ok(None);
+        }
+        let pi = match self.mode {
+            NumberMode::Decimal => {
+                Value::Decimal(Decimal::pi(self.precision + decimal::GUARD_DIGITS))
             }
+            _ => Value::Float(std::f64::consts::PI),
+        };
+        // 角度和百分度的半周都是整数（180、200）
+        let half_turn = Value::Integer(BigInt::from(self.angle_mode.half_turn() as i64));
+        Value::binary('/', &pi, &half_turn).map(Some)
+    }
 
-            "floor" => {
-                if args.len() != 1 {
-                    return Err(Error::InvalidArgumentCount {
-                        function: name.to_string(),
-                        expected: 1,
-                        actual: args.len(),
-                    });
-                }
-                let arg = self.evaluate(&args[0])?;
-                Ok(arg.floor())
+    /// 把三角函数的参数换算为弧度；带角度单位的参数（如 `30°`）已经是弧度
+    fn angle_to_radians(&self, angle: Value, function: &BuiltinFunction) -> Result<Value, Error> {
+        let factor = match self.angle_factor()? {
+            Some(factor) if !matches!(angle, Value::Quantity(_)) => factor,
+            _ => return Ok(angle),
+        };
+        // 十进制数按 π 的精度换算，避免换算本身的舍入误差
+        let angle = match angle {
+            Value::Decimal(d) => {
+                Value::Decimal(d.with_precision(self.precision + decimal::GUARD_DIGITS))
             }
+            angle => angle,
+        };
+        let radians = Value::binary('*', &angle, &factor)?;
+        self.check_exact(std::slice::from_ref(&angle), &radians, || {
+            function.signature.clone()
+        })?;
+        Ok(radians)
+    }
 
-            "round" => {
-                if args.len() != 1 {
-                    return Err(Error::InvalidArgumentCount {
-                        function: name.to_string(),
-                        expected: 1,
-                        actual: args.len(),
-                    });
-                }
-                let arg = self.evaluate(&args[0])?;
-                Ok(arg.round())
+    /// 把反三角函数的结果从弧度换算为当前角度单位
+    fn radians_to_angle(&self, radians: Value) -> Result<Value, Error> {
+        match self.angle_factor()? {
+            Some(factor) => Value::binary('/', &radians, &factor),
+            None => Ok(radians),
+        }
+    }
+
+    /// 按参数类型选择内置函数的实现
+    fn call_builtin(&self, function: &BuiltinFunction, values: Vec<Value>) -> Result<Value, Error> {
+        // 按函数名选择的精确、十进制和复数实现只替代默认实现，用户覆盖的函数按浮点数调用
+        let name = if function.is_default() {
+            function.name.as_str()
+        } else {
+            ""
+        };
+
+        // 带单位的参数：abs、sqrt、max、min 保留单位，其余函数要求参数无量纲（角度按弧度传入）
+        if values.iter().any(|v| matches!(v, Value::Quantity(_))) {
+            let quantities = values
+                .iter()
+                .map(Value::to_quantity)
+                .collect::<Result<Vec<Quantity>, Error>>()?;
+            if let Some(result) = units::quantity_function(name, &quantities) {
+                return result.map(Value::quantity);
+            }
+            if let Some(q) = quantities
+                .iter()
+                .find(|q| !q.dimension().is_dimensionless())
+            {
+                return Err(units::mismatch(q.unit(), &units::UnitExpr::new()));
+            }
+            let floats: Vec<f64> = quantities.iter().map(Quantity::si_value).collect();
+            return function.call(&floats).map(Value::Float);
+        }
+
+        // 参数都是整数值（且不是浮点数）时，阶乘、组合数等按任意精度整数计算
+        let integers: Option<Vec<_>> = values.iter().map(Value::to_integer).collect();
+        if let Some(result) = integers.and_then(|args| integer::integer_function(name, &args)) {
+            return result.map(Value::Integer);
+        }
+
+        // 参数都是精确值时，优先使用精确实现
+        let exact: Option<Vec<_>> = values.iter().map(Value::to_rational).collect();
+        if let Some(result) = exact.and_then(|args| value::rational_function(name, &args)) {
+            // 整数参数的取整、最值等结果仍是整数
+            let all_integers = values.iter().all(|v| matches!(v, Value::Integer(_)));
+            if all_integers && result.is_integer() {
+                return Ok(Value::Integer(result.to_integer()));
             }
+            return Ok(Value::Rational(result));
+        }
+
+        // 十进制模式下使用高精度实现
+        if self.mode == NumberMode::Decimal {
+            let decimals: Option<Vec<Decimal>> = values
+                .iter()
+                .map(|v| v.to_decimal(self.precision))
+                .collect();
+            if let Some(result) = decimals.and_then(|args| decimal::decimal_function(name, &args)) {
+                return result.map(Value::Decimal);
+            }
+        }
 
-            _ => Err(Error::UndefinedFunction(name.to_string())),
+        // 有复数参数时按复数计算
+        let complex_args: Vec<Complex64> = values.iter().map(Value::to_complex).collect();
+        if values.iter().any(|v| matches!(v, Value::Complex(_))) {
+            return match complex::complex_function(name, &complex_args) {
+                Some(result) => result.map(Value::complex),
+                None => Err(Error::Other(format!(
+                    "函数 {} 不支持复数参数",
+                    function.signature
+                ))),
+            };
         }
+
+        let floats: Vec<f64> = values.iter().map(Value::to_f64).collect();
+        let result = match function.call(&floats) {
+            Ok(n) if !n.is_nan() => Value::Float(n),
+            // 复数模式下实数无定义的情况（如 sqrt(-1)、ln(-2)）改按复数计算
+            result if self.mode == NumberMode::Complex => {
+                match complex::complex_function(name, &complex_args) {
+                    Some(result) => return result.map(Value::complex),
+                    None => Value::Float(value::real_result(&function.name, &floats, result?)?),
+                }
+            }
+            result => Value::Float(value::real_result(&function.name, &floats, result?)?),
+        };
+        self.check_exact(&values, &result, || function.signature.clone())?;
+        Ok(result)
     }
 }
 
@@ -201,6 +865,87 @@ impl Default for ExpressionEvaluator {
     }
 }
 
+/// 求和与连乘（包括嵌套的）总项数超过 `MAX_TERMS`
+fn too_many_terms(signature: &str) -> Error {
+    Error::DomainError {
+        function: signature.to_string(),
+        reason: format!("项数不能超过 {}", MAX_TERMS),
+    }
+}
+
+/// 积分、求和与连乘的第二个参数必须是变量名
+fn bound_variable<'a>(function: &str, arg: &'a AstNode) -> Result<&'a str, Error> {
+    match arg {
+        AstNode::Variable(var) => Ok(var),
+        _ => Err(Error::Other(format!(
+            "{} 的第二个参数必须是变量名",
+            function
+        ))),
+    }
+}
+
+/// 方程 `lhs = rhs` 的残差 `lhs - rhs`，求根即求残差的零点
+fn residual(equation: &AstNode) -> AstNode {
+    let (left, right) = match equation {
+        AstNode::BinaryOp {
+            op: '=',
+            left,
+            right,
+        } => ((**left).clone(), (**right).clone()),
+        AstNode::Assign { name, value } => (AstNode::Variable(name.clone()), (**value).clone()),
+        expr => return expr.clone(),
+    };
+    AstNode::BinaryOp {
+        op: '-',
+        left: Box::new(left),
+        right: Box::new(right),
+    }
+}
+
+/// 判断 node 是否（经由其它用户函数间接地）调用了名为 target 的函数
+fn calls_function(
+    node: &AstNode,
+    target: &str,
+    env: &Environment,
+    visited: &mut HashSet<String>,
+) -> bool {
+    match node {
+        AstNode::Number(_)
+        | AstNode::Literal(_)
+        | AstNode::Imaginary(_)
+        | AstNode::Constant(_)
+        | AstNode::Variable(_)
+        | AstNode::Invalid => false,
+        AstNode::BinaryOp { left, right, .. } => {
+            calls_function(left, target, env, visited)
+                || calls_function(right, target, env, visited)
+        }
+        AstNode::UnaryOp { operand, .. }
+        | AstNode::Quantity { value: operand, .. }
+        | AstNode::Convert { value: operand, .. } => calls_function(operand, target, env, visited),
+        AstNode::Assign { value, .. } => calls_function(value, target, env, visited),
+        AstNode::FunctionDef { body, .. } => calls_function(body, target, env, visited),
+        AstNode::FunctionCall { name, args } => {
+            if name == target {
+                return true;
+            }
+            if args
+                .iter()
+                .any(|arg| calls_function(arg, target, env, visited))
+            {
+                return true;
+            }
+            // 沿着已定义的用户函数继续查找（每个函数只展开一次）
+            match env.function(name) {
+                Some(function) if visited.insert(name.clone()) => {
+                    calls_function(&function.body, target, env, visited)
+                }
+                _ => false,
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -275,6 +1020,157 @@ mod tests {
         assert!(evaluator.evaluate(&ast).is_err());
     }
 
+    #[test]
+    fn test_evaluate_variable() {
+        let evaluator = ExpressionEvaluator::new();
+        evaluator.set_variable("x", 3.0);
+
+        let ast = AstNode::BinaryOp {
+            op: '*',
+            left: Box::new(AstNode::Variable("x".to_string())),
+            right: Box::new(AstNode::Number(2.0)),
+        };
+        assert_eq!(evaluator.evaluate(&ast).unwrap(), 6.0);
+    }
+
+    #[test]
+    fn test_evaluate_undefined_variable() {
+        let evaluator = ExpressionEvaluator::new();
+        let ast = AstNode::Variable("y".to_string());
+        assert_eq!(
+            evaluator.evaluate(&ast),
+            Err(Error::UndefinedVariable("y".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_evaluate_assign() {
+        let evaluator = ExpressionEvaluator::new();
+        let ast = AstNode::Assign {
+            name: "x".to_string(),
+            value: Box::new(AstNode::Number(12.0)),
+        };
+        assert_eq!(evaluator.evaluate(&ast).unwrap(), 12.0);
+        assert_eq!(evaluator.get_variable("x"), Some(12.0));
+    }
+
+    #[test]
+    fn test_evaluate_function_log_two_args() {
+        let evaluator = ExpressionEvaluator::new();
+        let ast = AstNode::FunctionCall {
+            name: "log".to_string(),
+            args: vec![AstNode::Number(8.0), AstNode::Number(2.0)],
+        };
+        assert_eq!(evaluator.evaluate(&ast).unwrap(), 3.0);
+    }
+
+    #[test]
+    fn test_evaluate_argument_count_cites_signature() {
+        let evaluator = ExpressionEvaluator::new();
+        let ast = AstNode::FunctionCall {
+            name: "log".to_string(),
+            args: vec![AstNode::Number(8.0)],
+        };
+        assert_eq!(
+            evaluator.evaluate(&ast),
+            Err(Error::InvalidArgumentCount {
+                function: "log(value, base)".to_string(),
+                expected: 2,
+                actual: 1,
+            })
+        );
+
+        let ast = AstNode::FunctionCall {
+            name: "max".to_string(),
+            args: vec![],
+        };
+        assert_eq!(
+            evaluator.evaluate(&ast),
+            Err(Error::InvalidArgumentRange {
+                function: "max(x, ...)".to_string(),
+                min: 1,
+                max: None,
+                actual: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn test_user_function_call() {
+        let evaluator = ExpressionEvaluator::new();
+        evaluator.set_variable("x", 100.0);
+        evaluator
+            .define_function(UserFunction {
+                name: "sq".to_string(),
+                params: vec!["x".to_string()],
+                body: AstNode::BinaryOp {
+                    op: '*',
+                    left: Box::new(AstNode::Variable("x".to_string())),
+                    right: Box::new(AstNode::Variable("x".to_string())),
+                },
+            })
+            .unwrap();
+
+        let ast = AstNode::FunctionCall {
+            name: "sq".to_string(),
+            args: vec![AstNode::Number(3.0)],
+        };
+        assert_eq!(evaluator.evaluate(&ast).unwrap(), 9.0);
+        // 形参不会覆盖全局变量
+        assert_eq!(evaluator.get_variable("x"), Some(100.0));
+    }
+
+    #[test]
+    fn test_user_function_rejects_self_reference() {
+        let evaluator = ExpressionEvaluator::new();
+        let result = evaluator.define_function(UserFunction {
+            name: "f".to_string(),
+            params: vec!["x".to_string()],
+            body: AstNode::FunctionCall {
+                name: "f".to_string(),
+                args: vec![AstNode::Variable("x".to_string())],
+            },
+        });
+        assert_eq!(result, Err(Error::RecursiveFunction("f".to_string())));
+    }
+
+    #[test]
+    fn test_user_function_depth_limit() {
+        let evaluator = ExpressionEvaluator::new();
+        let param = || vec!["x".to_string()];
+        evaluator
+            .define_function(UserFunction {
+                name: "f0".to_string(),
+                params: param(),
+                body: AstNode::Variable("x".to_string()),
+            })
+            .unwrap();
+        for i in 1..=MAX_CALL_DEPTH {
+            evaluator
+                .define_function(UserFunction {
+                    name: format!("f{}", i),
+                    params: param(),
+                    body: AstNode::FunctionCall {
+                        name: format!("f{}", i - 1),
+                        args: vec![AstNode::Variable("x".to_string())],
+                    },
+                })
+                .unwrap();
+        }
+
+        let call = |name: String| AstNode::FunctionCall {
+            name,
+            args: vec![AstNode::Number(1.0)],
+        };
+        assert_eq!(evaluator.evaluate(&call("f99".to_string())).unwrap(), 1.0);
+        assert_eq!(
+            evaluator.evaluate(&call(format!("f{}", MAX_CALL_DEPTH))),
+            Err(Error::RecursionLimitExceeded(MAX_CALL_DEPTH))
+        );
+        // 出错后调用帧已全部弹出
+        assert_eq!(evaluator.environment().depth(), 0);
+    }
+
     #[test]
     fn test_nested_operations() {
         let evaluator = ExpressionEvaluator::new();
@@ -0,0 +1,182 @@
+// calculator/integer.rs - 任意精度整数运算：阶乘、组合数与整除
+
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{FromPrimitive, One, Signed, ToPrimitive, Zero};
+
+use crate::utils::Error;
+
+/// 精确阶乘允许的最大参数（100000! 约有 45 万位）
+pub const MAX_FACTORIAL: u64 = 100_000;
+
+/// n!
+pub fn factorial(n: u64) -> Result<BigInt, Error> {
+    if n > MAX_FACTORIAL {
+        return Err(Error::FactorialTooLarge);
+    }
+    Ok(product(1, n))
+}
+
+/// 排列数 nPr = n! / (n − r)!（r > n 时为 0）
+pub fn permutations(n: u64, r: u64) -> Result<BigInt, Error> {
+    if r > n {
+        return Ok(BigInt::zero());
+    }
+    if r > MAX_FACTORIAL {
+        return Err(Error::ResultOverflow("npr".to_string()));
+    }
+    Ok(product(n - r + 1, n))
+}
+
+/// 组合数 nCr = n! / (r! · (n − r)!)（r > n 时为 0）
+pub fn combinations(n: u64, r: u64) -> Result<BigInt, Error> {
+    if r > n {
+        return Ok(BigInt::zero());
+    }
+    // C(n, r) = C(n, n − r)，取较小的一边
+    let r = r.min(n - r);
+    if r > MAX_FACTORIAL {
+        return Err(Error::ResultOverflow("ncr".to_string()));
+    }
+    Ok(permutations(n, r)? / product(1, r))
+}
+
+/// 最大公约数（结果非负）
+pub fn gcd(a: &BigInt, b: &BigInt) -> BigInt {
+    a.gcd(b)
+}
+
+/// 最小公倍数（结果非负）
+pub fn lcm(a: &BigInt, b: &BigInt) -> BigInt {
+    a.lcm(b)
+}
+
+/// 取模：结果与除数同号，如 `mod(-7, 3) = 2`
+pub fn modulo(a: &BigInt, b: &BigInt) -> Result<BigInt, Error> {
+    if b.is_zero() {
+        return Err(Error::DivisionByZero);
+    }
+    Ok(a.mod_floor(b))
+}
+
+/// 整除：向下取整，如 `idiv(-7, 2) = -4`
+pub fn int_divide(a: &BigInt, b: &BigInt) -> Result<BigInt, Error> {
+    if b.is_zero() {
+        return Err(Error::DivisionByZero);
+    }
+    Ok(a.div_floor(b))
+}
+
+/// 按名称计算整数函数；不是整数函数时返回 None
+pub(crate) fn integer_function(name: &str, args: &[BigInt]) -> Option<Result<BigInt, Error>> {
+    let result = match (name, args) {
+        ("factorial", [n]) => to_u64(name, n).and_then(factorial),
+        ("npr", [n, r]) => to_u64(name, n).and_then(|n| permutations(n, to_u64(name, r)?)),
+        ("ncr", [n, r]) => to_u64(name, n).and_then(|n| combinations(n, to_u64(name, r)?)),
+        ("gcd", [a, b]) => Ok(gcd(a, b)),
+        ("lcm", [a, b]) => Ok(lcm(a, b)),
+        ("mod", [a, b]) => modulo(a, b),
+        ("idiv", [a, b]) => int_divide(a, b),
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// 将浮点数参数转换为整数，非整数时报错
+pub(crate) fn integer_arg(function: &str, value: f64) -> Result<BigInt, Error> {
+    if value.fract() != 0.0 {
+        return Err(invalid_argument(function));
+    }
+    BigInt::from_f64(value).ok_or_else(|| invalid_argument(function))
+}
+
+/// 将浮点数参数转换为非负整数
+pub(crate) fn natural_arg(function: &str, value: f64) -> Result<u64, Error> {
+    to_u64(function, &integer_arg(function, value)?)
+}
+
+/// 非负整数参数
+fn to_u64(function: &str, value: &BigInt) -> Result<u64, Error> {
+    if value.is_negative() {
+        return Err(invalid_argument(function));
+    }
+    value.to_u64().ok_or_else(|| too_large(function))
+}
+
+/// 结果过大时的错误（阶乘沿用原有的错误类型）
+pub(crate) fn too_large(function: &str) -> Error {
+    if function == "factorial" {
+        Error::FactorialTooLarge
+    } else {
+        Error::ResultOverflow(function.to_string())
+    }
+}
+
+/// 参数无效时的错误（阶乘沿用原有的错误类型）
+fn invalid_argument(function: &str) -> Error {
+    if function == "factorial" {
+        Error::InvalidFactorialArgument
+    } else {
+        Error::InvalidIntegerArgument(function.to_string())
+    }
+}
+
+/// from × (from + 1) × … × to（区间为空时为 1）
+fn product(from: u64, to: u64) -> BigInt {
+    if from > to {
+        return BigInt::one();
+    }
+    // 二分相乘，让大数乘法的两边规模接近
+    if to - from < 16 {
+        return (from..=to).fold(BigInt::one(), |acc, k| acc * k);
+    }
+    let mid = from + (to - from) / 2;
+    product(from, mid) * product(mid + 1, to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_factorial() {
+        assert_eq!(factorial(0).unwrap(), BigInt::one());
+        assert_eq!(factorial(20).unwrap(), BigInt::from(2432902008176640000u64));
+        assert_eq!(
+            factorial(25).unwrap().to_string(),
+            "15511210043330985984000000"
+        );
+        assert_eq!(factorial(MAX_FACTORIAL + 1), Err(Error::FactorialTooLarge));
+    }
+
+    #[test]
+    fn test_combinatorics() {
+        assert_eq!(combinations(5, 2).unwrap(), BigInt::from(10));
+        assert_eq!(combinations(52, 5).unwrap(), BigInt::from(2598960));
+        assert_eq!(combinations(3, 5).unwrap(), BigInt::zero());
+        assert_eq!(permutations(5, 2).unwrap(), BigInt::from(20));
+        assert_eq!(
+            combinations(100, 50).unwrap().to_string(),
+            "100891344545564193334812497256"
+        );
+    }
+
+    #[test]
+    fn test_integer_division() {
+        let (a, b) = (BigInt::from(-7), BigInt::from(3));
+        assert_eq!(modulo(&a, &b).unwrap(), BigInt::from(2));
+        assert_eq!(int_divide(&a, &b).unwrap(), BigInt::from(-3));
+        assert_eq!(modulo(&a, &BigInt::zero()), Err(Error::DivisionByZero));
+        assert_eq!(gcd(&BigInt::from(12), &BigInt::from(-18)), BigInt::from(6));
+        assert_eq!(lcm(&BigInt::from(4), &BigInt::from(6)), BigInt::from(12));
+    }
+
+    #[test]
+    fn test_integer_arg() {
+        assert_eq!(integer_arg("gcd", 12.0).unwrap(), BigInt::from(12));
+        assert_eq!(
+            integer_arg("gcd", 1.5),
+            Err(Error::InvalidIntegerArgument("gcd".to_string()))
+        );
+    }
+}
@@ -1,29 +1,180 @@
 // calculator/mod.rs - 计算器模块
 
+pub mod batch;
+pub mod bytecode;
+pub mod complex;
+pub mod decimal;
+pub mod derivative;
+pub mod environment;
 pub mod evaluator;
+pub mod integer;
 pub mod operations;
 pub mod parser;
+pub mod printer;
+pub mod quadrature;
+pub mod recall;
+pub mod registry;
+pub mod simplify;
+pub mod solver;
+pub mod special;
+pub mod units;
+pub mod value;
+
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
 
 use crate::data::Statistics;
 use crate::utils::Error;
 
 // 重新导出
+pub use bytecode::CompiledExpr;
+pub use complex::ComplexFormat;
+pub use decimal::Decimal;
+pub use environment::{Environment, UserFunction};
+pub use evaluator::Outcome;
 pub use operations::Operation;
+pub use parser::{AstNode, RecoveredParse, SpanTree};
+pub use printer::PrintStyle;
+pub use quadrature::Integral;
+pub use recall::ResultRef;
+pub use registry::{Arity, FunctionRegistry};
+pub use units::{Quantity, UnitExpr};
+pub use value::{AngleMode, NumberMode, Value};
 
 /// 科学计算器
 pub struct Calculator {
     precision: u8,
+    /// 复数结果的显示格式
+    complex_format: ComplexFormat,
+    /// 表达式求值器，持有跨调用保留的变量环境
+    evaluator: evaluator::ExpressionEvaluator,
+    /// 求值前是否先化简语法树
+    auto_simplify: bool,
+    /// 批量求值使用的线程数
+    batch_threads: usize,
 }
 
 impl Calculator {
     /// 创建新的计算器实例
     pub fn new() -> Self {
-        Self { precision: 10 }
+        Self {
+            precision: 10,
+            complex_format: ComplexFormat::default(),
+            evaluator: evaluator::ExpressionEvaluator::new(),
+            auto_simplify: false,
+            batch_threads: batch::default_threads(),
+        }
+    }
+
+    /// 使用自定义函数注册表创建计算器
+    pub fn with_registry(registry: FunctionRegistry) -> Self {
+        Self {
+            precision: 10,
+            complex_format: ComplexFormat::default(),
+            evaluator: evaluator::ExpressionEvaluator::with_registry(registry),
+            auto_simplify: false,
+            batch_threads: batch::default_threads(),
+        }
     }
 
-    /// 设置计算精度
+    /// 获取表达式中可用的内置函数
+    pub fn registry(&self) -> &FunctionRegistry {
+        self.evaluator.registry()
+    }
+
+    /// 设置计算精度（十进制模式下的有效位数）
     pub fn set_precision(&mut self, precision: u8) {
         self.precision = precision;
+        self.evaluator.set_precision(precision as u32);
+    }
+
+    /// 获取计算精度
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    /// 设置表达式的数值模式
+    ///
+    /// ```
+    /// # use chapter02::Calculator;
+    /// use chapter02::calculator::NumberMode;
+    ///
+    /// let mut calc = Calculator::new();
+    /// calc.set_number_mode(NumberMode::Rational);
+    /// let value = calc.evaluate_value("1/3 + 1/6").unwrap();
+    /// assert_eq!(value.to_string(), "1/2");
+    /// ```
+    pub fn set_number_mode(&mut self, mode: NumberMode) {
+        self.evaluator.set_number_mode(mode);
+    }
+
+    /// 获取表达式的数值模式
+    pub fn number_mode(&self) -> NumberMode {
+        self.evaluator.number_mode()
+    }
+
+    /// 设置三角函数及其反函数使用的角度单位
+    ///
+    /// ```
+    /// # use chapter02::Calculator;
+    /// use chapter02::calculator::AngleMode;
+    ///
+    /// let mut calc = Calculator::new();
+    /// calc.set_angle_mode(AngleMode::Degrees);
+    /// assert!((calc.evaluate_expression("sin(30)").unwrap() - 0.5).abs() < 1e-12);
+    /// assert!((calc.evaluate_expression("atan(1)").unwrap() - 45.0).abs() < 1e-12);
+    /// ```
+    pub fn set_angle_mode(&mut self, mode: AngleMode) {
+        self.evaluator.set_angle_mode(mode);
+    }
+
+    /// 获取角度单位
+    pub fn angle_mode(&self) -> AngleMode {
+        self.evaluator.angle_mode()
+    }
+
+    /// 设置复数结果的显示格式
+    pub fn set_complex_format(&mut self, format: ComplexFormat) {
+        self.complex_format = format;
+    }
+
+    /// 获取复数结果的显示格式
+    pub fn complex_format(&self) -> ComplexFormat {
+        self.complex_format
+    }
+
+    /// 按当前的复数显示格式显示结果
+    ///
+    /// ```
+    /// # use chapter02::Calculator;
+    /// use chapter02::calculator::NumberMode;
+    ///
+    /// let mut calc = Calculator::new();
+    /// calc.set_number_mode(NumberMode::Complex);
+    /// let value = calc.evaluate_value("(1 + 2i) * (3 - i)").unwrap();
+    /// assert_eq!(calc.format_value(&value), "5 + 5i");
+    /// ```
+    pub fn format_value(&self, value: &Value) -> String {
+        value.format(self.complex_format)
+    }
+
+    /// 设置严格模式：精确数值模式下遇到 `sqrt`、`sin` 等无法精确计算的运算时
+    /// 返回 `Error::InexactResult`，而不是退回浮点数
+    pub fn set_strict_mode(&mut self, strict: bool) {
+        self.evaluator.set_strict_mode(strict);
+    }
+
+    /// 设置是否在求值前先化简表达式（见 `simplify::simplify`）
+    ///
+    /// 化简不改变求值结果，只是让重复出现的子表达式少算几次；浮点数模式下结果可能
+    /// 相差舍入误差。
+    pub fn set_auto_simplify(&mut self, enabled: bool) {
+        self.auto_simplify = enabled;
+    }
+
+    /// 求值前是否先化简表达式
+    pub fn auto_simplify(&self) -> bool {
+        self.auto_simplify
     }
 
     // ===== 基础运算 =====
@@ -53,7 +204,7 @@ impl Calculator {
 
     /// 幂运算
     pub fn power(&self, base: f64, exponent: f64) -> Result<f64, Error> {
-        Ok(base.powf(exponent))
+        value::real_result("^", &[base, exponent], base.powf(exponent))
     }
 
     // ===== 科学运算 =====
@@ -66,19 +217,36 @@ impl Calculator {
         Ok(value.sqrt())
     }
 
-    /// 正弦函数
+    /// 正弦函数（角度按当前角度模式解释）
     pub fn sin(&self, angle: f64) -> Result<f64, Error> {
-        Ok(angle.sin())
+        Ok(self.angle_mode().to_radians(angle).sin())
     }
 
-    /// 余弦函数
+    /// 余弦函数（角度按当前角度模式解释）
     pub fn cos(&self, angle: f64) -> Result<f64, Error> {
-        Ok(angle.cos())
+        Ok(self.angle_mode().to_radians(angle).cos())
     }
 
-    /// 正切函数
+    /// 正切函数（角度按当前角度模式解释）
     pub fn tan(&self, angle: f64) -> Result<f64, Error> {
-        Ok(angle.tan())
+        Ok(self.angle_mode().to_radians(angle).tan())
+    }
+
+    /// 反正弦函数（结果按当前角度模式表示）
+    pub fn asin(&self, value: f64) -> Result<f64, Error> {
+        let radians = registry::inverse_trig("asin", value, f64::asin)?;
+        Ok(self.angle_mode().radians_to_mode(radians))
+    }
+
+    /// 反余弦函数（结果按当前角度模式表示）
+    pub fn acos(&self, value: f64) -> Result<f64, Error> {
+        let radians = registry::inverse_trig("acos", value, f64::acos)?;
+        Ok(self.angle_mode().radians_to_mode(radians))
+    }
+
+    /// 反正切函数（结果按当前角度模式表示）
+    pub fn atan(&self, value: f64) -> Result<f64, Error> {
+        Ok(self.angle_mode().radians_to_mode(value.atan()))
     }
 
     /// 自然对数
@@ -97,13 +265,55 @@ impl Calculator {
         Ok(value.log(base))
     }
 
-    /// 阶乘
+    /// 阶乘（结果超出 f64 范围时返回 `FactorialTooLarge`，即 n > 170）
     pub fn factorial(&self, n: u64) -> Result<f64, Error> {
-        if n > 20 {
-            return Err(Error::FactorialTooLarge);
+        match integer::factorial(n)?.to_f64() {
+            Some(result) if result.is_finite() => Ok(result),
+            _ => Err(Error::FactorialTooLarge),
         }
-        let result = (1..=n).product::<u64>() as f64;
-        Ok(result)
+    }
+
+    /// 精确阶乘
+    ///
+    /// ```
+    /// # use chapter02::Calculator;
+    /// let calc = Calculator::new();
+    /// assert_eq!(calc.factorial_exact(25).unwrap().to_string(), "15511210043330985984000000");
+    /// ```
+    pub fn factorial_exact(&self, n: u64) -> Result<BigInt, Error> {
+        integer::factorial(n)
+    }
+
+    // ===== 整数运算 =====
+
+    /// 组合数 nCr
+    pub fn ncr(&self, n: u64, r: u64) -> Result<BigInt, Error> {
+        integer::combinations(n, r)
+    }
+
+    /// 排列数 nPr
+    pub fn npr(&self, n: u64, r: u64) -> Result<BigInt, Error> {
+        integer::permutations(n, r)
+    }
+
+    /// 最大公约数
+    pub fn gcd(&self, a: impl Into<BigInt>, b: impl Into<BigInt>) -> BigInt {
+        integer::gcd(&a.into(), &b.into())
+    }
+
+    /// 最小公倍数
+    pub fn lcm(&self, a: impl Into<BigInt>, b: impl Into<BigInt>) -> BigInt {
+        integer::lcm(&a.into(), &b.into())
+    }
+
+    /// 取模（结果与除数同号）
+    pub fn modulo(&self, a: impl Into<BigInt>, b: impl Into<BigInt>) -> Result<BigInt, Error> {
+        integer::modulo(&a.into(), &b.into())
+    }
+
+    /// 整除（向下取整）
+    pub fn idiv(&self, a: impl Into<BigInt>, b: impl Into<BigInt>) -> Result<BigInt, Error> {
+        integer::int_divide(&a.into(), &b.into())
     }
 
     /// 绝对值
@@ -115,9 +325,12 @@ impl Calculator {
 
     /// 求值表达式字符串
     ///
+    /// 赋值表达式（如 `x = 3 * 4`）定义的变量会保留到后续调用中。
+    ///
     /// # 示例
     ///
     /// ```
+    /// # use chapter02::Calculator;
     /// let calc = Calculator::new();
     /// assert_eq!(calc.evaluate_expression("2 + 3 * 4").unwrap(), 14.0);
     /// assert_eq!(calc.evaluate_expression("(2 + 3) * 4").unwrap(), 20.0);
@@ -125,16 +338,324 @@ impl Calculator {
     /// assert_eq!(calc.evaluate_expression("sqrt(16)").unwrap(), 4.0);
     /// ```
     pub fn evaluate_expression(&self, expression: &str) -> Result<f64, Error> {
-        // 1. 词法分析：将字符串转为 token 流
-        let parser = parser::ExpressionParser::new();
-        let tokens = parser.tokenize(expression)?;
-
-        // 2. 语法分析：将 token 流转为抽象语法树 (AST)
-        let ast = parser.parse(tokens)?;
+        // 1-2. 词法分析与语法分析：字符串 → token 流 → 抽象语法树 (AST)
+        let ast = self.parse(expression)?;
 
         // 3. 求值：遍历 AST 计算结果
-        let evaluator = evaluator::ExpressionEvaluator::new();
-        evaluator.evaluate(&ast)
+        self.evaluator.evaluate(&self.prepare(ast))
+    }
+
+    /// 按当前数值模式计算表达式，返回保留精确值的结果
+    pub fn evaluate_value(&self, expression: &str) -> Result<Value, Error> {
+        let ast = self.parse(expression)?;
+        self.evaluator.evaluate_value(&self.prepare(ast))
+    }
+
+    /// 计算可以引用历史结果的表达式
+    ///
+    /// 表达式中的 `ans`、`_` 和 `$n` 在求值前交给 `recall` 查出数值，
+    /// 通常由 `HistoryManager::recall` 提供；查不到时返回 `recall` 给出的错误。
+    /// 精确值按原值代入，有理数模式下 `1/3` 的结果乘 3 仍得到 1。
+    ///
+    /// ```
+    /// use chapter02::calculator::{Calculator, ResultRef, Value};
+    /// use chapter02::utils::Error;
+    ///
+    /// let calc = Calculator::new();
+    /// let recall = |reference| match reference {
+    ///     ResultRef::Last => Ok(Value::Float(42.0)),
+    ///     ResultRef::Index(1) => Ok(Value::Float(2.0)),
+    ///     ResultRef::Index(_) => Err(Error::Other("没有这条记录".to_string())),
+    /// };
+    /// assert_eq!(calc.evaluate_with_recall("ans / $1", recall).unwrap(), 21.0);
+    /// assert!(calc.evaluate_with_recall("$7", recall).is_err());
+    /// ```
+    pub fn evaluate_with_recall<F>(&self, expression: &str, recall: F) -> Result<f64, Error>
+    where
+        F: Fn(ResultRef) -> Result<Value, Error>,
+    {
+        self.evaluate_value_with_recall(expression, recall)?
+            .to_real()
+    }
+
+    /// 与 `evaluate_with_recall` 相同，但按当前数值模式返回保留精确值的结果
+    pub fn evaluate_value_with_recall<F>(&self, expression: &str, recall: F) -> Result<Value, Error>
+    where
+        F: Fn(ResultRef) -> Result<Value, Error>,
+    {
+        let ast = self.parse(expression)?;
+        let ast = recall::resolve(&ast, &recall)?;
+        self.evaluator.evaluate_value(&self.prepare(ast))
+    }
+
+    /// 执行一条输入：函数定义（如 `f(x) = x^2`）返回 `Outcome::Defined`，不像
+    /// `evaluate_expression` 那样把定义当作结果为 0 的表达式
+    ///
+    /// ```
+    /// use chapter02::calculator::{Calculator, Outcome, Value};
+    ///
+    /// let calc = Calculator::new();
+    /// assert_eq!(calc.execute("f(x) = x^2").unwrap(), Outcome::Defined("f".to_string()));
+    /// assert_eq!(calc.execute("f(3)").unwrap(), Outcome::Value(Value::Float(9.0)));
+    /// ```
+    pub fn execute(&self, expression: &str) -> Result<Outcome, Error> {
+        let ast = self.parse(expression)?;
+        self.evaluator.execute(&self.prepare(ast))
+    }
+
+    /// 与 `execute` 相同，但表达式可以引用历史结果（见 `evaluate_with_recall`）
+    pub fn execute_with_recall<F>(&self, expression: &str, recall: F) -> Result<Outcome, Error>
+    where
+        F: Fn(ResultRef) -> Result<Value, Error>,
+    {
+        let ast = self.parse(expression)?;
+        let ast = recall::resolve(&ast, &recall)?;
+        self.evaluator.execute(&self.prepare(ast))
+    }
+
+    /// 开启自动化简时先化简语法树
+    fn prepare(&self, ast: AstNode) -> AstNode {
+        if self.auto_simplify {
+            simplify::simplify(&ast)
+        } else {
+            ast
+        }
+    }
+
+    /// 化简表达式，返回化简后的语法树
+    ///
+    /// ```
+    /// use chapter02::Calculator;
+    ///
+    /// let calc = Calculator::new();
+    /// let simplified = calc.simplify("x*1 + 2*x + (3 - 1)").unwrap();
+    /// let (expected, _) = calc.parse_expression("3 * x + 2").unwrap();
+    /// assert_eq!(simplified, expected);
+    /// ```
+    pub fn simplify(&self, expression: &str) -> Result<AstNode, Error> {
+        let ast = self.parse(expression)?;
+        Ok(simplify::simplify(&ast))
+    }
+
+    /// 对表达式关于变量 `var` 符号求导，返回导数的语法树
+    ///
+    /// 用户函数先展开为函数体，三角函数按当前角度模式求导。表达式中也可以直接写
+    /// `diff(expr, var)`（在 var 的当前值处求导数）或 `diff(expr, var, at)`：
+    ///
+    /// ```
+    /// use chapter02::Calculator;
+    ///
+    /// let calc = Calculator::new();
+    /// assert_eq!(calc.evaluate_expression("diff(x^3, x, 2)").unwrap(), 12.0);
+    ///
+    /// let derivative = calc.derivative("x^3", "x").unwrap();
+    /// let (expected, _) = calc.parse_expression("3 * x^2").unwrap();
+    /// assert_eq!(derivative, expected);
+    /// ```
+    pub fn derivative(&self, expression: &str, var: &str) -> Result<AstNode, Error> {
+        let ast = self.parse(expression)?;
+        self.evaluator.derive(&ast, var)
+    }
+
+    /// 找出方程在区间 [lower, upper] 内关于变量 var 的全部根（从小到大）
+    ///
+    /// 方程写作 `lhs = rhs`，单独的表达式视为 `expr = 0`。求单个根可以直接在表达式中写
+    /// `solve(eq, var)`（牛顿法，可以给出初始值）或 `solve(eq, var, a, b)`（Brent 方法）：
+    ///
+    /// ```
+    /// use chapter02::Calculator;
+    ///
+    /// let calc = Calculator::new();
+    /// let root = calc.evaluate_expression("solve(x^2 - 2 = 0, x, 0, 2)").unwrap();
+    /// assert!((root - 2f64.sqrt()).abs() < 1e-12);
+    ///
+    /// let roots = calc.roots("x^3 = x", "x", -2.0, 2.0).unwrap();
+    /// assert_eq!(roots.len(), 3);
+    /// ```
+    pub fn roots(
+        &self,
+        equation: &str,
+        var: &str,
+        lower: f64,
+        upper: f64,
+    ) -> Result<Vec<f64>, Error> {
+        let ast = self.parse(equation)?;
+        self.evaluator.find_roots(&ast, var, lower, upper)
+    }
+
+    /// 计算表达式关于变量 var 从 lower 到 upper 的定积分，并给出误差估计
+    ///
+    /// 在表达式中也可以直接写 `integrate(expr, var, a, b)`；求和与连乘写作
+    /// `sum(expr, k, from, to)`、`prod(expr, k, from, to)`：
+    ///
+    /// ```
+    /// use chapter02::Calculator;
+    ///
+    /// let calc = Calculator::new();
+    /// let integral = calc.integrate("x^2", "x", 0.0, 3.0).unwrap();
+    /// assert!((integral.value - 9.0).abs() <= integral.error.max(1e-12));
+    ///
+    /// assert_eq!(calc.evaluate_expression("sum(k^2, k, 1, 10)").unwrap(), 385.0);
+    /// assert_eq!(calc.evaluate_expression("prod(k, k, 1, 5)").unwrap(), 120.0);
+    /// ```
+    pub fn integrate(
+        &self,
+        expression: &str,
+        var: &str,
+        lower: f64,
+        upper: f64,
+    ) -> Result<Integral, Error> {
+        let ast = self.parse(expression)?;
+        self.evaluator.integrate(&ast, var, lower, upper)
+    }
+
+    /// 把表达式编译为字节码，之后可以对不同的变量值反复求值而不必重新解析
+    ///
+    /// variables 依次对应 `CompiledExpr::evaluate` 的参数；其它变量、用户函数和角度模式
+    /// 取编译时的值。编译后的表达式按浮点数计算：
+    ///
+    /// ```
+    /// use chapter02::Calculator;
+    ///
+    /// let calc = Calculator::new();
+    /// calc.evaluate_expression("g = 9.81").unwrap();
+    /// let height = calc.compile("v * t - g * t^2 / 2", &["v", "t"]).unwrap();
+    /// assert_eq!(height.evaluate(&[20.0, 0.0]).unwrap(), 0.0);
+    /// assert!((height.evaluate(&[20.0, 2.0]).unwrap() - 20.38).abs() < 1e-12);
+    /// ```
+    pub fn compile(&self, expression: &str, variables: &[&str]) -> Result<CompiledExpr, Error> {
+        let ast = self.parse(expression)?;
+        self.evaluator.compile(&self.prepare(ast), variables)
+    }
+
+    /// 设置批量求值最多使用的线程数（默认为 CPU 核数，1 表示只在当前线程中计算）
+    pub fn set_batch_threads(&mut self, threads: usize) {
+        self.batch_threads = threads.max(1);
+    }
+
+    /// 获取批量求值最多使用的线程数
+    pub fn batch_threads(&self) -> usize {
+        self.batch_threads
+    }
+
+    /// 对多列数据逐行求值同一个表达式，列名就是表达式中的变量名
+    ///
+    /// 表达式只解析一次，浮点数模式下能编译时按字节码执行，行数多时分给多个线程；
+    /// 其它数值模式下逐行按该模式求值（列的值是浮点数）。
+    /// 任何一行出错时返回 `Error::Row`；结果可以直接用于 `calculate_statistics`：
+    ///
+    /// ```
+    /// use chapter02::Calculator;
+    ///
+    /// let calc = Calculator::new();
+    /// let price = [10.0, 20.0, 5.0];
+    /// let qty = [3.0, 1.0, 4.0];
+    /// let discount = [0.0, 0.5, 0.25];
+    /// let totals = calc
+    ///     .evaluate_batch(
+    ///         "price * qty * (1 - discount)",
+    ///         &[("price", &price), ("qty", &qty), ("discount", &discount)],
+    ///     )
+    ///     .unwrap();
+    /// assert_eq!(totals, [30.0, 10.0, 15.0]);
+    /// assert_eq!(calc.calculate_statistics(&totals).unwrap().sum, 55.0);
+    /// ```
+    pub fn evaluate_batch(
+        &self,
+        expression: &str,
+        columns: &[(&str, &[f64])],
+    ) -> Result<Vec<f64>, Error> {
+        self.evaluate_batch_rows(expression, columns)?
+            .into_iter()
+            .enumerate()
+            .map(|(row, result)| {
+                result.map_err(|error| Error::Row {
+                    row,
+                    error: Box::new(error),
+                })
+            })
+            .collect()
+    }
+
+    /// 与 `evaluate_batch` 相同，但每行单独给出结果或错误
+    ///
+    /// 表达式本身的错误（语法错误、列的行数不同、未定义的变量等）仍作为整体的错误返回。
+    pub fn evaluate_batch_rows(
+        &self,
+        expression: &str,
+        columns: &[(&str, &[f64])],
+    ) -> Result<Vec<Result<f64, Error>>, Error> {
+        let ast = self.parse(expression)?;
+        self.evaluator
+            .evaluate_batch(&self.prepare(ast), columns, self.batch_threads)
+    }
+
+    /// 解析表达式字符串，返回语法树及每个节点的源码区间
+    ///
+    /// 词法和语法错误包装为带出错位置的 `Error::Spanned`，可用 `utils::render_error` 渲染提示。
+    /// 其他方法（如 `evaluate_expression`）返回的错误不带位置，需要位置时先调用本方法或 `diagnose`。
+    pub fn parse_expression(&self, expression: &str) -> Result<(AstNode, SpanTree), Error> {
+        let parser = self.parser();
+        let tokens = parser.tokenize_spanned(expression)?;
+        parser.parse_spanned(tokens)
+    }
+
+    /// 解析表达式，错误去掉位置信息
+    fn parse(&self, expression: &str) -> Result<AstNode, Error> {
+        self.parse_expression(expression)
+            .map(|(ast, _)| ast)
+            .map_err(Error::into_inner)
+    }
+
+    /// 检查表达式中的所有词法和语法错误（不求值）
+    ///
+    /// 与 `parse_expression` 不同，遇到错误后会继续解析，一次返回全部问题，
+    /// 结果按出现位置排序；表达式正确时返回空列表。
+    pub fn diagnose(&self, expression: &str) -> Vec<Error> {
+        self.parser().parse_recovering(expression).errors
+    }
+
+    /// 按当前数值模式配置的解析器：复数模式下 `i` 是虚数单位
+    fn parser(&self) -> parser::ExpressionParser {
+        parser::ExpressionParser::new()
+            .with_imaginary_unit(self.number_mode() == NumberMode::Complex)
+    }
+
+    // ===== 变量 =====
+
+    /// 设置变量
+    pub fn set_variable(&self, name: &str, value: f64) {
+        self.evaluator.set_variable(name, value);
+    }
+
+    /// 读取变量
+    pub fn get_variable(&self, name: &str) -> Option<f64> {
+        self.evaluator.get_variable(name)
+    }
+
+    /// 列出所有变量（按名称排序）
+    pub fn variables(&self) -> Vec<(String, f64)> {
+        self.evaluator
+            .environment()
+            .variables()
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect()
+    }
+
+    /// 清除所有变量和用户函数
+    pub fn clear_variables(&self) {
+        self.evaluator.clear_variables();
+    }
+
+    /// 列出所有用户函数的签名（按名称排序）
+    pub fn user_functions(&self) -> Vec<String> {
+        self.evaluator
+            .environment()
+            .functions()
+            .iter()
+            .map(|function| function.signature())
+            .collect()
     }
 
     // ===== 统计计算 =====
@@ -156,7 +677,7 @@ impl Calculator {
         // 计算中位数
         let mut sorted_data = data.to_vec();
         sorted_data.sort_by(|a, b| a.partial_cmp(b).unwrap());
-        let median = if sorted_data.len() % 2 == 0 {
+        let median = if sorted_data.len().is_multiple_of(2) {
             let mid = sorted_data.len() / 2;
             (sorted_data[mid - 1] + sorted_data[mid]) / 2.0
         } else {
@@ -202,6 +723,7 @@ impl Default for Calculator {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use num_complex::Complex64;
 
     #[test]
     fn test_basic_operations() {
@@ -229,7 +751,55 @@ mod tests {
     fn test_factorial() {
         let calc = Calculator::new();
         assert_eq!(calc.factorial(5).unwrap(), 120.0);
-        assert!(calc.factorial(21).is_err());
+        assert_eq!(calc.factorial(21).unwrap(), 51090942171709440000.0);
+        assert!(calc.factorial(171).is_err());
+    }
+
+    #[test]
+    fn test_integer_methods() {
+        let calc = Calculator::new();
+        assert_eq!(calc.ncr(52, 5).unwrap(), BigInt::from(2598960));
+        assert_eq!(calc.npr(5, 3).unwrap(), BigInt::from(60));
+        assert_eq!(calc.gcd(12, 18), BigInt::from(6));
+        assert_eq!(calc.lcm(4, 6), BigInt::from(12));
+        assert_eq!(calc.modulo(-7, 3).unwrap(), BigInt::from(2));
+        assert_eq!(calc.idiv(-7, 2).unwrap(), BigInt::from(-4));
+        assert_eq!(calc.idiv(1, 0), Err(Error::DivisionByZero));
+    }
+
+    #[test]
+    fn test_integer_mode_factorial() {
+        let mut calc = Calculator::new();
+        assert_eq!(calc.evaluate_expression("5! + nCr(5, 2)").unwrap(), 130.0);
+
+        calc.set_number_mode(NumberMode::Integer);
+        let value = calc.evaluate_value("100!").unwrap();
+        assert_eq!(
+            value.to_string(),
+            "93326215443944152681699238856266700490715968264381621468592963895217599993229915608941463976156518286253697920827223758251185210916864000000000000000000000000"
+        );
+        assert_eq!(
+            calc.evaluate_value("2^64 + 1").unwrap().to_string(),
+            "18446744073709551617"
+        );
+        assert_eq!(
+            calc.evaluate_value("mod(2^100, 7)").unwrap().to_string(),
+            "2"
+        );
+        assert_eq!(
+            calc.evaluate_value("idiv(7, 2) + 7/2").unwrap().to_string(),
+            "13/2"
+        );
+        assert_eq!(
+            calc.evaluate_value("gcd(12, 18) * lcm(4, 6)")
+                .unwrap()
+                .to_string(),
+            "72"
+        );
+        assert_eq!(
+            calc.evaluate_value("(-1)!"),
+            Err(Error::InvalidFactorialArgument)
+        );
     }
 
     #[test]
@@ -283,6 +853,744 @@ mod tests {
         assert_eq!(calc.evaluate_expression("2 ^ 3 ^ 2").unwrap(), 512.0);
     }
 
+    #[test]
+    fn test_evaluate_multi_argument_functions() {
+        let calc = Calculator::new();
+        assert_eq!(calc.evaluate_expression("log(8, 2)").unwrap(), 3.0);
+        assert_eq!(calc.evaluate_expression("max(1, 7, 3)").unwrap(), 7.0);
+        assert_eq!(calc.evaluate_expression("min(4, -2 * 3)").unwrap(), -6.0);
+        assert!(matches!(
+            calc.evaluate_expression("sin(1, 2)"),
+            Err(Error::InvalidArgumentCount { .. })
+        ));
+    }
+
+    #[test]
+    fn test_evaluate_variables_persist() {
+        let calc = Calculator::new();
+        assert_eq!(calc.evaluate_expression("x = 3 * 4").unwrap(), 12.0);
+        assert_eq!(calc.evaluate_expression("x ^ 2").unwrap(), 144.0);
+        assert_eq!(calc.evaluate_expression("y = x + 1").unwrap(), 13.0);
+        assert_eq!(calc.get_variable("y"), Some(13.0));
+    }
+
+    #[test]
+    fn test_custom_registry() {
+        let mut registry = FunctionRegistry::with_defaults();
+        registry.register("lerp(a, b, t)", Arity::Fixed(3), "线性插值", |args| {
+            Ok(args[0] + (args[1] - args[0]) * args[2])
+        });
+        let calc = Calculator::with_registry(registry);
+
+        assert_eq!(calc.evaluate_expression("lerp(0, 10, 0.5)").unwrap(), 5.0);
+        assert_eq!(calc.evaluate_expression("sqrt(16)").unwrap(), 4.0);
+
+        // 覆盖默认函数后，各数值模式都调用覆盖后的实现
+        let mut registry = FunctionRegistry::with_defaults();
+        registry.register("abs(x)", Arity::Fixed(1), "总是 42", |_| Ok(42.0));
+        let mut calc = Calculator::with_registry(registry);
+        for mode in [
+            NumberMode::Float,
+            NumberMode::Rational,
+            NumberMode::Integer,
+            NumberMode::Decimal,
+        ] {
+            calc.set_number_mode(mode);
+            assert_eq!(calc.evaluate_expression("abs(-3)").unwrap(), 42.0);
+        }
+
+        // 自定义函数返回 NaN 时报定义域错误
+        let mut registry = FunctionRegistry::with_defaults();
+        registry.register("bad(x)", Arity::Fixed(1), "总是 NaN", |_| Ok(f64::NAN));
+        let calc = Calculator::with_registry(registry);
+        assert!(matches!(
+            calc.evaluate_expression("bad(1)"),
+            Err(Error::DomainError { function, .. }) if function == "bad"
+        ));
+
+        // 空注册表中没有任何内置函数
+        let calc = Calculator::with_registry(FunctionRegistry::new());
+        assert_eq!(
+            calc.evaluate_expression("sqrt(16)"),
+            Err(Error::UndefinedFunction("sqrt".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_user_defined_functions() {
+        let calc = Calculator::new();
+        calc.evaluate_expression("f(x, y) = x * y + 1").unwrap();
+        calc.evaluate_expression("g(x) = f(x, x) * 2").unwrap();
+
+        assert_eq!(calc.evaluate_expression("f(3, 4)").unwrap(), 13.0);
+        assert_eq!(calc.evaluate_expression("g(2)").unwrap(), 10.0);
+        assert_eq!(calc.user_functions(), vec!["f(x, y)", "g(x)"]);
+        // 函数定义没有数值
+        assert_eq!(
+            calc.execute("h(x) = g(x) - 1").unwrap(),
+            Outcome::Defined("h".to_string())
+        );
+        assert_eq!(
+            calc.execute("h(2)").unwrap(),
+            Outcome::Value(Value::Float(9.0))
+        );
+        assert!(matches!(
+            calc.evaluate_expression("f(1)"),
+            Err(Error::InvalidArgumentCount { .. })
+        ));
+    }
+
+    #[test]
+    fn test_user_function_recursion_checks() {
+        let calc = Calculator::new();
+        assert_eq!(
+            calc.evaluate_expression("f(x) = f(x - 1) + 1"),
+            Err(Error::RecursiveFunction("f".to_string()))
+        );
+
+        // 间接递归：g 调用 h，再把 h 重新定义为调用 g
+        calc.evaluate_expression("h(x) = x").unwrap();
+        calc.evaluate_expression("g(x) = h(x) + 1").unwrap();
+        assert_eq!(
+            calc.evaluate_expression("h(x) = g(x)"),
+            Err(Error::RecursiveFunction("h".to_string()))
+        );
+
+        // 内置函数不能被覆盖
+        assert!(calc.evaluate_expression("sin(x) = x").is_err());
+    }
+
+    #[test]
+    fn test_evaluate_undefined_variable() {
+        let calc = Calculator::new();
+        assert_eq!(
+            calc.evaluate_expression("z + 1"),
+            Err(Error::UndefinedVariable("z".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors_carry_span() {
+        let calc = Calculator::new();
+        // 求值接口返回不带位置的错误，可以直接匹配
+        assert_eq!(
+            calc.evaluate_expression("2 * (3 + $)"),
+            Err(Error::InvalidCharacter('$'))
+        );
+        assert!(matches!(
+            calc.evaluate_expression("2 * (3 +"),
+            Err(Error::ParseError(_))
+        ));
+
+        // 位置由 parse_expression 给出
+        let err = calc.parse_expression("2 * (3 + $)").unwrap_err();
+        assert_eq!(err.inner(), &Error::InvalidCharacter('$'));
+        assert_eq!(
+            crate::utils::render_error("2 * (3 + $)", &err),
+            "错误: 无效字符: $\n  | 2 * (3 + $)\n  |          ^"
+        );
+    }
+
+    #[test]
+    fn test_diagnose_reports_every_error() {
+        let calc = Calculator::new();
+        assert!(calc.diagnose("sqrt(16) + 2").is_empty());
+
+        let errors = calc.diagnose("(1 + @ * 2");
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(errors[0].inner(), Error::ParseError(_)));
+        assert_eq!(errors[1].inner(), &Error::InvalidCharacter('@'));
+
+        // 严格模式下不再接受末尾多余的 token
+        assert!(calc.evaluate_expression("1 2").is_err());
+    }
+
+    #[test]
+    fn test_rational_mode() {
+        let mut calc = Calculator::new();
+        assert_ne!(calc.evaluate_expression("0.1 + 0.2").unwrap(), 0.3);
+
+        calc.set_number_mode(NumberMode::Rational);
+        assert_eq!(calc.evaluate_expression("0.1 + 0.2").unwrap(), 0.3);
+
+        let value = calc.evaluate_value("1/3*3").unwrap();
+        assert_eq!(value.to_string(), "1");
+        assert!(value.is_exact());
+
+        let value = calc
+            .evaluate_value("abs(-7/2) + floor(5/3) + 2^-2")
+            .unwrap();
+        assert_eq!(value.to_string(), "19/4");
+        assert_eq!(value.to_decimal_string(10), "4.75");
+
+        // 变量保留精确值
+        calc.evaluate_value("x = 2/3").unwrap();
+        assert_eq!(calc.evaluate_value("x * 3").unwrap().to_string(), "2");
+    }
+
+    #[test]
+    fn test_rational_mode_fallback() {
+        let mut calc = Calculator::new();
+        calc.set_number_mode(NumberMode::Rational);
+
+        let value = calc.evaluate_value("sqrt(2) * 2").unwrap();
+        assert!(!value.is_exact());
+        assert!((value.to_f64() - 2.0 * 2f64.sqrt()).abs() < 1e-12);
+
+        calc.set_strict_mode(true);
+        assert_eq!(
+            calc.evaluate_value("sqrt(2)"),
+            Err(Error::InexactResult("sqrt(x)".to_string()))
+        );
+        assert!(matches!(
+            calc.evaluate_value("4 ^ 0.5"),
+            Err(Error::InexactResult(_))
+        ));
+        assert_eq!(calc.evaluate_value("round(5/2)").unwrap().to_string(), "3");
+    }
+
+    #[test]
+    fn test_decimal_mode_honors_precision() {
+        let mut calc = Calculator::new();
+        calc.set_number_mode(NumberMode::Decimal);
+        calc.set_precision(30);
+
+        let value = calc.evaluate_value("1/3").unwrap();
+        assert_eq!(value.to_string(), format!("0.{}", "3".repeat(30)));
+        assert_eq!(
+            calc.evaluate_value("π").unwrap().to_string(),
+            "3.14159265358979323846264338328"
+        );
+        assert_eq!(
+            calc.evaluate_value("sqrt(2)").unwrap().to_string(),
+            "1.41421356237309504880168872421"
+        );
+        // 超过 f64 精度的字面量不会丢失位数
+        assert_eq!(
+            calc.evaluate_value("1.00000000000000000001 - 1")
+                .unwrap()
+                .to_string(),
+            "1e-20"
+        );
+
+        calc.set_precision(5);
+        assert_eq!(calc.evaluate_value("ln(e)").unwrap().to_string(), "1");
+        assert_eq!(calc.evaluate_value("2/3").unwrap().to_string(), "0.66667");
+    }
+
+    #[test]
+    fn test_units() {
+        let calc = Calculator::new();
+        let value = |expr| calc.evaluate_value(expr).unwrap().to_string();
+        assert_eq!(value("3 m + 20 cm"), "3.2 m");
+        assert_eq!(value("9.81 m/s^2 * 2 s"), "19.62 m/s");
+        assert_eq!(value("(10 m)^2"), "100 m^2");
+        assert_eq!(value("6 km / 2 km"), "3");
+        assert_eq!(value("1 h to min"), "60 min");
+        assert_eq!(
+            calc.evaluate_value("pi / 6 to deg")
+                .unwrap()
+                .to_decimal_string(10),
+            "30 deg"
+        );
+
+        let miles = calc.evaluate_expression("5 km to mi").unwrap();
+        assert!((miles - 3.106855961186669).abs() < 1e-12);
+        assert!((calc.evaluate_expression("sin(30 deg)").unwrap() - 0.5).abs() < 1e-12);
+
+        assert_eq!(
+            calc.evaluate_value("3 m + 2 s"),
+            Err(Error::DimensionMismatch {
+                left: "m".to_string(),
+                right: "s".to_string(),
+            })
+        );
+        assert_eq!(
+            calc.evaluate_value("5 km to kg"),
+            Err(Error::DimensionMismatch {
+                left: "km".to_string(),
+                right: "kg".to_string(),
+            })
+        );
+        assert!(matches!(
+            calc.evaluate_value("sin(3 m)"),
+            Err(Error::DimensionMismatch { .. })
+        ));
+        assert_eq!(
+            calc.evaluate_value("5 km to furlong"),
+            Err(Error::UnknownUnit("furlong".to_string()))
+        );
+
+        // 单位符号不跟在数字之后时仍可作为变量名
+        calc.set_variable("m", 2.0);
+        assert_eq!(calc.evaluate_expression("m * 3").unwrap(), 6.0);
+    }
+
+    #[test]
+    fn test_angle_modes() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-12;
+        let mut calc = Calculator::new();
+        // 度数字面量在任何角度模式下都表示度
+        assert!(close(calc.evaluate_expression("sin(30°)").unwrap(), 0.5));
+        assert!(close(
+            calc.evaluate_expression("cos((45 + 15)°)").unwrap(),
+            0.5
+        ));
+
+        calc.set_angle_mode(AngleMode::Degrees);
+        assert!(close(calc.evaluate_expression("sin(30)").unwrap(), 0.5));
+        assert!(close(calc.evaluate_expression("sin(30°)").unwrap(), 0.5));
+        assert!(close(calc.evaluate_expression("asin(1)").unwrap(), 90.0));
+        assert!(close(calc.sin(90.0).unwrap(), 1.0));
+        assert!(close(calc.atan(1.0).unwrap(), 45.0));
+        assert!(calc.asin(2.0).is_err());
+
+        calc.set_angle_mode(AngleMode::Gradians);
+        assert!(close(calc.evaluate_expression("cos(100)").unwrap(), 0.0));
+        assert!(close(calc.acos(0.0).unwrap(), 100.0));
+
+        // 十进制模式下按高精度换算
+        calc.set_angle_mode(AngleMode::Degrees);
+        calc.set_number_mode(NumberMode::Decimal);
+        calc.set_precision(20);
+        assert_eq!(calc.evaluate_value("sin(30)").unwrap().to_string(), "0.5");
+
+        // 严格模式下角度换算同样不精确
+        calc.set_number_mode(NumberMode::Rational);
+        calc.set_strict_mode(true);
+        assert_eq!(
+            calc.evaluate_value("cos(60)"),
+            Err(Error::InexactResult("cos(x)".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_scientific_functions() {
+        let mut calc = Calculator::new();
+        assert_eq!(
+            calc.evaluate_expression("log10(1000) + log2(8)").unwrap(),
+            6.0
+        );
+        assert_eq!(calc.evaluate_expression("root(-32, 5)").unwrap(), -2.0);
+        assert_eq!(calc.evaluate_expression("gamma(5) / 4!").unwrap(), 1.0);
+        assert!(matches!(
+            calc.evaluate_expression("acosh(0.5)"),
+            Err(Error::DomainError { .. })
+        ));
+        // 负数的非整数次幂在实数范围内无定义，报错而不是得到 NaN
+        let negative_root = |result: Result<f64, Error>| match result {
+            Err(Error::DomainError { function, .. }) => function == "^",
+            _ => false,
+        };
+        assert!(negative_root(calc.evaluate_expression("(-8)^(1/3)")));
+        assert!(negative_root(calc.power(-8.0, 0.5)));
+        let compiled = calc.compile("x^0.5", &["x"]).unwrap();
+        assert!(negative_root(compiled.evaluate(&[-4.0])));
+        assert_eq!(compiled.evaluate(&[4.0]), Ok(2.0));
+        calc.set_number_mode(NumberMode::Decimal);
+        assert!(negative_root(calc.evaluate_expression("(-8)^0.5")));
+        calc.set_number_mode(NumberMode::Float);
+
+        // atan2 的结果与反三角函数一样按角度模式表示
+        calc.set_angle_mode(AngleMode::Degrees);
+        assert_eq!(calc.evaluate_expression("atan2(1, -1)").unwrap(), 135.0);
+
+        // 精确模式下 trunc 和 sign 保持分数运算
+        calc.set_number_mode(NumberMode::Rational);
+        assert_eq!(
+            calc.evaluate_value("trunc(-7/2) + sign(-1/3)")
+                .unwrap()
+                .to_string(),
+            "-4"
+        );
+
+        calc.set_number_mode(NumberMode::Decimal);
+        calc.set_precision(20);
+        assert_eq!(calc.evaluate_value("log10(1000)").unwrap().to_string(), "3");
+
+        // 复数模式下实数定义域之外的调用按复数计算
+        calc.set_number_mode(NumberMode::Complex);
+        let value = calc.evaluate_value("acosh(0)").unwrap();
+        assert_eq!(
+            value.to_complex(),
+            Complex64::new(0.0, std::f64::consts::FRAC_PI_2)
+        );
+    }
+
+    #[test]
+    fn test_derivative() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        let mut calc = Calculator::new();
+        assert_eq!(
+            calc.evaluate_expression("diff(x^2 + 3*x, x, 2)").unwrap(),
+            7.0
+        );
+        assert!(close(
+            calc.evaluate_expression("diff(sin(x) * exp(x), x, 0)")
+                .unwrap(),
+            1.0
+        ));
+
+        // 在变量的当前值处求导；用户函数先展开
+        calc.evaluate_expression("f(t) = t^3 - 2*t").unwrap();
+        calc.evaluate_expression("x = 3").unwrap();
+        assert_eq!(calc.evaluate_expression("diff(f(x), x)").unwrap(), 25.0);
+        assert_eq!(
+            calc.evaluate_expression("diff(diff(f(x), x), x)").unwrap(),
+            18.0
+        );
+
+        // 与数值差分比较
+        let h = 1e-6;
+        let numeric = (calc.evaluate_expression("gamma(2.5 + 0.000001)").unwrap()
+            - calc.evaluate_expression("gamma(2.5 - 0.000001)").unwrap())
+            / (2.0 * h);
+        let symbolic = calc.evaluate_expression("diff(gamma(x), x, 2.5)").unwrap();
+        assert!((numeric - symbolic).abs() < 1e-6);
+
+        // 角度模式下 d/dx sin(x°) = π/180 · cos(x°)
+        calc.set_angle_mode(AngleMode::Degrees);
+        assert!(close(
+            calc.evaluate_expression("diff(sin(x), x, 0)").unwrap(),
+            std::f64::consts::PI / 180.0
+        ));
+
+        assert_eq!(
+            calc.evaluate_expression("diff(max(x, 1), x)"),
+            Err(Error::NotDifferentiable("max".to_string()))
+        );
+        assert!(calc.evaluate_expression("diff(x^2, 3)").is_err());
+        assert!(calc.evaluate_expression("diff(t) = t").is_err());
+    }
+
+    #[test]
+    fn test_solve() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        let mut calc = Calculator::new();
+        let sqrt2 = std::f64::consts::SQRT_2;
+        assert!(close(
+            calc.evaluate_expression("solve(x^2 - 2 = 0, x)").unwrap(),
+            sqrt2
+        ));
+        assert!(close(
+            calc.evaluate_expression("solve(x^2 = 2, x, -5)").unwrap(),
+            -sqrt2
+        ));
+        assert!(close(
+            calc.evaluate_expression("solve(x^2 - 2, x, 0, 10)")
+                .unwrap(),
+            sqrt2
+        ));
+
+        // 盈亏平衡点：单价 12、单位成本 7、固定成本 1500
+        calc.evaluate_expression("price = 12").unwrap();
+        calc.evaluate_expression("cost(q) = 1500 + 7 * q").unwrap();
+        assert!(close(
+            calc.evaluate_expression("solve(price * q = cost(q), q)")
+                .unwrap(),
+            300.0
+        ));
+        // 无法符号求导的方程用数值导数；方程中可以引用用户函数的参数
+        assert!(close(
+            calc.evaluate_expression("solve(max(x, 1) = 3, x, 2)")
+                .unwrap(),
+            3.0
+        ));
+        calc.evaluate_expression("g(a) = solve(x^2 = a, x, 0, a + 1)")
+            .unwrap();
+        assert!(close(calc.evaluate_expression("g(9)").unwrap(), 3.0));
+        calc.set_angle_mode(AngleMode::Degrees);
+        assert!(close(
+            calc.evaluate_expression("solve(sin(x) = 0.5, x, 0, 90)")
+                .unwrap(),
+            30.0
+        ));
+        calc.set_angle_mode(AngleMode::Radians);
+
+        assert!(matches!(
+            calc.evaluate_expression("solve(x^2 + 1, x)"),
+            Err(Error::NoConvergence { .. })
+        ));
+        assert_eq!(
+            calc.evaluate_expression("solve(x^2 = 2, x, 2, 3)"),
+            Err(Error::NoSignChange {
+                lower: 2.0,
+                upper: 3.0
+            })
+        );
+        assert!(calc.evaluate_expression("x^2 = 2").is_err());
+        assert!(calc.evaluate_expression("solve(x^2 = 2, 3)").is_err());
+
+        // 十进制模式的有效位数较少时仍按浮点数求解
+        calc.set_number_mode(NumberMode::Decimal);
+        calc.set_precision(10);
+        assert!(close(
+            calc.evaluate_expression("solve(x^2 = 2, x)").unwrap(),
+            sqrt2
+        ));
+        let root = calc.evaluate_expression("solve(x^2 = 2, x, 0, 2)").unwrap();
+        assert!((root - sqrt2).abs() < 1e-15);
+        assert!(close(
+            calc.evaluate_expression("solve(cos(x) = x, x)").unwrap(),
+            0.7390851332151607
+        ));
+        calc.set_number_mode(NumberMode::Float);
+
+        let roots = calc.roots("sin(x)", "x", -1.0, 10.0).unwrap();
+        assert_eq!(roots.len(), 4);
+        assert!(close(roots[3], 3.0 * std::f64::consts::PI));
+        assert!(calc.roots("x^2 + 1", "x", -10.0, 10.0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_compile() {
+        let mut calc = Calculator::new();
+        calc.evaluate_expression("f(x) = x^2 + 1").unwrap();
+        calc.set_angle_mode(AngleMode::Degrees);
+        let compiled = calc
+            .compile("f(a) + sin(a) + diff(a^3, a)", &["a"])
+            .unwrap();
+        assert_eq!(compiled.variables(), ["a"]);
+        for a in [0.0, 30.0, 90.0] {
+            calc.set_variable("a", a);
+            let expected = calc
+                .evaluate_expression("f(a) + sin(a) + diff(a^3, a)")
+                .unwrap();
+            assert_eq!(compiled.evaluate(&[a]).unwrap(), expected);
+        }
+
+        // 编译时的设置被固定下来
+        calc.set_angle_mode(AngleMode::Radians);
+        assert!((compiled.evaluate(&[90.0]).unwrap() - (8101.0 + 1.0 + 24300.0)).abs() < 1e-9);
+
+        assert!(matches!(
+            calc.compile("x to m", &["x"]),
+            Err(Error::NotCompilable(_))
+        ));
+        assert!(calc.compile("x +", &["x"]).is_err());
+    }
+
+    #[test]
+    fn test_evaluate_batch() {
+        let mut calc = Calculator::new();
+        calc.evaluate_expression("tax = 0.1").unwrap();
+        let price = [10.0, 20.0, 0.0];
+        let qty = [3.0, 1.0, 2.0];
+        let columns: [(&str, &[f64]); 2] = [("price", &price), ("qty", &qty)];
+
+        assert_eq!(
+            calc.evaluate_batch("price * qty * (1 + tax)", &columns)
+                .unwrap(),
+            [33.0, 22.0, 0.0]
+        );
+        // 逐行报告错误
+        let rows = calc.evaluate_batch_rows("qty / price", &columns).unwrap();
+        assert_eq!(rows[0], Ok(0.3));
+        assert_eq!(rows[2], Err(Error::DivisionByZero));
+        assert_eq!(
+            calc.evaluate_batch("qty / price", &columns),
+            Err(Error::Row {
+                row: 2,
+                error: Box::new(Error::DivisionByZero)
+            })
+        );
+
+        // 无法编译的表达式逐行遍历语法树，列的值不会留在全局变量中
+        calc.set_batch_threads(1);
+        assert_eq!(
+            calc.evaluate_batch("sum(price * k, k, 1, qty)", &columns)
+                .unwrap(),
+            [60.0, 20.0, 0.0]
+        );
+        assert_eq!(calc.get_variable("price"), None);
+
+        assert!(matches!(
+            calc.evaluate_batch("price * amount", &columns),
+            Err(Error::UndefinedVariable(_))
+        ));
+        assert!(matches!(
+            calc.evaluate_batch("price", &[("price", &price), ("qty", &qty[..2])]),
+            Err(Error::ColumnLength { .. })
+        ));
+        assert_eq!(
+            calc.evaluate_batch("price", &[("price", &price), ("price", &qty)]),
+            Err(Error::DuplicateColumn("price".to_string()))
+        );
+
+        // 其它数值模式下按该模式求值，与逐个求值的结果相同
+        calc.set_number_mode(NumberMode::Decimal);
+        calc.set_precision(5);
+        let results = calc.evaluate_batch("qty + 1/3", &columns).unwrap();
+        for (result, q) in results.iter().zip(qty) {
+            assert!((result - (q + 0.33333)).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_integrate_sum_prod() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        let mut calc = Calculator::new();
+        assert!(close(
+            calc.evaluate_expression("integrate(sin(x), x, 0, π)")
+                .unwrap(),
+            2.0
+        ));
+        assert!(close(
+            calc.evaluate_expression("integrate(exp(-(t^2)), t, -10, 10)^2")
+                .unwrap(),
+            std::f64::consts::PI
+        ));
+
+        // 约束变量只在函数体内有效，不影响同名的全局变量
+        calc.evaluate_expression("k = 100").unwrap();
+        assert_eq!(
+            calc.evaluate_expression("sum(k, k, 1, 100)").unwrap(),
+            5050.0
+        );
+        assert_eq!(calc.get_variable("k"), Some(100.0));
+        assert_eq!(calc.evaluate_expression("sum(k, j, 1, 3)").unwrap(), 300.0);
+        // 嵌套求和、上限引用外层变量、空范围
+        assert_eq!(
+            calc.evaluate_expression("sum(sum(i * j, j, 1, i), i, 1, 3)")
+                .unwrap(),
+            25.0
+        );
+        assert_eq!(calc.evaluate_expression("sum(k, k, 5, 1)").unwrap(), 0.0);
+        assert_eq!(calc.evaluate_expression("prod(k, k, 5, 1)").unwrap(), 1.0);
+        // 函数体内可以使用形参
+        calc.evaluate_expression("power_sum(n, p) = sum(k^p, k, 1, n)")
+            .unwrap();
+        assert_eq!(calc.evaluate_expression("power_sum(4, 3)").unwrap(), 100.0);
+
+        // 精确模式下结果保持精确
+        calc.set_number_mode(NumberMode::Rational);
+        let value = calc.evaluate_value("sum(1 / k, k, 1, 4)").unwrap();
+        assert_eq!(value.to_string(), "25/12");
+        assert!(value.is_exact());
+        calc.set_number_mode(NumberMode::Float);
+
+        assert!(matches!(
+            calc.evaluate_expression("sum(k, k, 1, 2.5)"),
+            Err(Error::DomainError { .. })
+        ));
+        assert_eq!(
+            calc.evaluate_expression("prod(10^300, k, 1, 3)"),
+            Err(Error::Divergent("prod".to_string()))
+        );
+        assert!(matches!(
+            calc.evaluate_expression("integrate(1 / x, x, 0, 1)"),
+            Err(Error::NoConvergence { .. }) | Err(Error::Divergent(_))
+        ));
+        // 求和体内的赋值只写入约束变量的作用域，不影响全局变量
+        assert_eq!(
+            calc.evaluate_expression("sum(y = k, k, 1, 3)").unwrap(),
+            6.0
+        );
+        assert_eq!(calc.get_variable("y"), None);
+        // 嵌套求和共用项数上限
+        assert!(matches!(
+            calc.evaluate_expression("sum(sum(1, j, 1, 1000), i, 1, 1001)"),
+            Err(Error::DomainError { .. })
+        ));
+        assert_eq!(
+            calc.evaluate_expression("sum(sum(1, j, 1, 9), i, 1, 100)")
+                .unwrap(),
+            900.0
+        );
+        assert!(calc.evaluate_expression("sum(k, 2, 1, 3)").is_err());
+        assert!(calc.evaluate_expression("sum(k) = k").is_err());
+    }
+
+    #[test]
+    fn test_auto_simplify() {
+        let mut calc = Calculator::new();
+        let (expected, _) = calc.parse_expression("2 * x * y + 1 / 3").unwrap();
+        assert_eq!(calc.simplify("x*y + y*x + 1/3").unwrap(), expected);
+
+        // 开启后求值结果不变，赋值和函数定义也先化简
+        calc.set_auto_simplify(true);
+        assert!(calc.auto_simplify());
+        calc.evaluate_expression("x = 2 * 3 + 0").unwrap();
+        calc.evaluate_expression("f(t) = t * t^2 + 0 * t").unwrap();
+        assert_eq!(calc.evaluate_expression("f(x) - x^3").unwrap(), 0.0);
+        calc.set_number_mode(NumberMode::Rational);
+        assert_eq!(
+            calc.evaluate_value("1/3 + 2/3 * x - x/6")
+                .unwrap()
+                .to_string(),
+            calc.evaluate_value("x/2 + 1/3").unwrap().to_string()
+        );
+        assert_eq!(
+            calc.evaluate_expression("x / (1 - 1)"),
+            Err(Error::DivisionByZero)
+        );
+        // 未定义的变量不会被消去
+        assert_eq!(
+            calc.evaluate_expression("z - z"),
+            Err(Error::UndefinedVariable("z".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_complex_mode() {
+        let mut calc = Calculator::new();
+        // 默认模式下 sqrt(-1) 仍然报错
+        assert_eq!(
+            calc.evaluate_expression("sqrt(-1)"),
+            Err(Error::NegativeSquareRoot)
+        );
+
+        calc.set_number_mode(NumberMode::Complex);
+        assert_eq!(calc.evaluate_value("sqrt(-1)").unwrap().to_string(), "i");
+        assert_eq!(
+            calc.evaluate_value("sqrt(-4) + 1").unwrap().to_string(),
+            "1 + 2i"
+        );
+        assert_eq!(calc.evaluate_value("i^2").unwrap(), Value::Float(-1.0));
+        // 复数模式下负数的分数次幂取主值
+        assert!(matches!(
+            calc.evaluate_value("(-8)^(1/3)").unwrap(),
+            Value::Complex(_)
+        ));
+        assert_eq!(calc.evaluate_expression("abs(3 + 4i)").unwrap(), 5.0);
+
+        let ln = calc.evaluate_value("ln(-2)").unwrap();
+        assert_eq!(
+            ln,
+            Value::Complex(Complex64::new(2f64.ln(), std::f64::consts::PI))
+        );
+        // 欧拉公式：e^(iπ) = -1（显示时忽略舍入误差）
+        assert_eq!(
+            calc.evaluate_value("exp(i * pi)").unwrap().to_string(),
+            "-1"
+        );
+        // 实数能计算的仍得到实数
+        assert_eq!(calc.evaluate_value("sqrt(16)").unwrap(), Value::Float(4.0));
+
+        // 复数结果无法作为 f64 返回
+        assert!(matches!(
+            calc.evaluate_expression("sqrt(-1)"),
+            Err(Error::ComplexResult(_))
+        ));
+        assert!(calc.evaluate_expression("floor(2i)").is_err());
+    }
+
+    #[test]
+    fn test_complex_format() {
+        let mut calc = Calculator::new();
+        calc.set_number_mode(NumberMode::Complex);
+        calc.set_complex_format(ComplexFormat::Polar);
+        let value = calc.evaluate_value("2i").unwrap();
+        assert_eq!(
+            calc.format_value(&value),
+            format!("2∠{}", std::f64::consts::FRAC_PI_2)
+        );
+
+        // 复数模式下的 (-8)^(1/3) 取主值
+        let root = calc.evaluate_value("(-8)^(1/3)").unwrap().to_complex();
+        assert!((root - Complex64::new(1.0, 3f64.sqrt())).norm() < 1e-12);
+    }
+
     #[test]
     fn test_evaluate_unary_minus() {
         let calc = Calculator::new();
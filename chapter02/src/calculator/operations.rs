@@ -37,6 +37,54 @@ pub enum Operation {
     Floor,
     /// 四舍五入
     Round,
+    /// 反正弦
+    Asin,
+    /// 反余弦
+    Acos,
+    /// 反正切
+    Atan,
+    /// 两参数反正切
+    Atan2,
+    /// 双曲正弦
+    Sinh,
+    /// 双曲余弦
+    Cosh,
+    /// 双曲正切
+    Tanh,
+    /// 反双曲正弦
+    Asinh,
+    /// 反双曲余弦
+    Acosh,
+    /// 反双曲正切
+    Atanh,
+    /// 自然指数
+    Exp,
+    /// 常用对数
+    Log10,
+    /// 以 2 为底的对数
+    Log2,
+    /// 立方根
+    Cbrt,
+    /// n 次方根
+    Root,
+    /// 斜边长
+    Hypot,
+    /// 符号
+    Sign,
+    /// 向零取整
+    Trunc,
+    /// 最小值
+    Min,
+    /// 最大值
+    Max,
+    /// 取模
+    Mod,
+    /// 伽马函数
+    Gamma,
+    /// 伽马函数的对数
+    Lgamma,
+    /// 误差函数
+    Erf,
 }
 
 impl Operation {
@@ -59,6 +107,30 @@ impl Operation {
             Operation::Ceil => "ceil",
             Operation::Floor => "floor",
             Operation::Round => "round",
+            Operation::Asin => "asin",
+            Operation::Acos => "acos",
+            Operation::Atan => "atan",
+            Operation::Atan2 => "atan2",
+            Operation::Sinh => "sinh",
+            Operation::Cosh => "cosh",
+            Operation::Tanh => "tanh",
+            Operation::Asinh => "asinh",
+            Operation::Acosh => "acosh",
+            Operation::Atanh => "atanh",
+            Operation::Exp => "exp",
+            Operation::Log10 => "log10",
+            Operation::Log2 => "log2",
+            Operation::Cbrt => "cbrt",
+            Operation::Root => "root",
+            Operation::Hypot => "hypot",
+            Operation::Sign => "sign",
+            Operation::Trunc => "trunc",
+            Operation::Min => "min",
+            Operation::Max => "max",
+            Operation::Mod => "mod",
+            Operation::Gamma => "gamma",
+            Operation::Lgamma => "lgamma",
+            Operation::Erf => "erf",
         }
     }
 
@@ -82,6 +154,12 @@ impl Operation {
                 | Operation::Divide
                 | Operation::Power
                 | Operation::Log
+                | Operation::Atan2
+                | Operation::Root
+                | Operation::Hypot
+                | Operation::Min
+                | Operation::Max
+                | Operation::Mod
         )
     }
 
@@ -99,6 +177,24 @@ impl Operation {
                 | Operation::Ceil
                 | Operation::Floor
                 | Operation::Round
+                | Operation::Asin
+                | Operation::Acos
+                | Operation::Atan
+                | Operation::Sinh
+                | Operation::Cosh
+                | Operation::Tanh
+                | Operation::Asinh
+                | Operation::Acosh
+                | Operation::Atanh
+                | Operation::Exp
+                | Operation::Log10
+                | Operation::Log2
+                | Operation::Cbrt
+                | Operation::Sign
+                | Operation::Trunc
+                | Operation::Gamma
+                | Operation::Lgamma
+                | Operation::Erf
         )
     }
 
@@ -121,6 +217,30 @@ impl Operation {
             "ceil" => Some(Operation::Ceil),
             "floor" => Some(Operation::Floor),
             "round" => Some(Operation::Round),
+            "asin" => Some(Operation::Asin),
+            "acos" => Some(Operation::Acos),
+            "atan" => Some(Operation::Atan),
+            "atan2" => Some(Operation::Atan2),
+            "sinh" => Some(Operation::Sinh),
+            "cosh" => Some(Operation::Cosh),
+            "tanh" => Some(Operation::Tanh),
+            "asinh" => Some(Operation::Asinh),
+            "acosh" => Some(Operation::Acosh),
+            "atanh" => Some(Operation::Atanh),
+            "exp" => Some(Operation::Exp),
+            "log10" => Some(Operation::Log10),
+            "log2" => Some(Operation::Log2),
+            "cbrt" => Some(Operation::Cbrt),
+            "root" => Some(Operation::Root),
+            "hypot" => Some(Operation::Hypot),
+            "sign" => Some(Operation::Sign),
+            "trunc" => Some(Operation::Trunc),
+            "min" => Some(Operation::Min),
+            "max" => Some(Operation::Max),
+            "mod" => Some(Operation::Mod),
+            "gamma" => Some(Operation::Gamma),
+            "lgamma" => Some(Operation::Lgamma),
+            "erf" => Some(Operation::Erf),
             _ => None,
         }
     }
@@ -155,6 +275,8 @@ mod tests {
         assert!(!Operation::Add.is_unary());
         assert!(Operation::SquareRoot.is_unary());
         assert!(!Operation::SquareRoot.is_binary());
+        assert!(Operation::Atan2.is_binary());
+        assert!(Operation::Gamma.is_unary());
     }
 
     #[test]
@@ -162,6 +284,8 @@ mod tests {
         assert_eq!(Operation::from_str("+"), Some(Operation::Add));
         assert_eq!(Operation::from_str("sqrt"), Some(Operation::SquareRoot));
         assert_eq!(Operation::from_str("×"), Some(Operation::Multiply));
+        assert_eq!(Operation::from_str("LOG10"), Some(Operation::Log10));
+        assert_eq!(Operation::from_str("atanh"), Some(Operation::Atanh));
         assert_eq!(Operation::from_str("invalid"), None);
     }
 }
@@ -1,30 +1,122 @@
 // calculator/parser.rs - 表达式解析器（简化版）
 
-use crate::utils::Error;
+use super::units::{self, UnitExpr};
+use crate::utils::{Error, Span};
+
+/// f64 能精确表示的十进制有效位数
+const F64_DIGITS: usize = 15;
 
 /// 词法单元类型
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     /// 数字
     Number(f64),
+    /// 超出 f64 精度的数字字面量（保留原文）
+    Literal(String),
+    /// 虚数字面量（如 `2i`，单独的 `i` 为 1）
+    Imaginary(f64),
     /// 操作符
     Operator(char),
     /// 左括号
     LeftParen,
     /// 右括号
     RightParen,
-    /// 函数名
+    /// 函数名（标识符后紧跟左括号）
     Function(String),
+    /// 标识符（变量名）
+    Identifier(String),
+    /// 赋值符号 =
+    Equals,
+    /// 逗号（分隔函数参数）
+    Comma,
     /// 常量（如 π, e）
     Constant(String),
     /// 前缀运算符（如 √）
     PrefixOp(String),
+    /// 后缀运算符（如 !）
+    PostfixOp(String),
+    /// 单位（紧跟在数字之后或 `to` 之后的单位符号，如 `3 m` 中的 `m`）
+    Unit(String),
+    /// 单位换算关键字 `to`
+    Convert,
+}
+
+/// 带源码位置的词法单元
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
+
+impl SpannedToken {
+    pub fn new(token: Token, span: Span) -> Self {
+        Self { token, span }
+    }
+}
+
+/// 与 `AstNode` 结构一一对应的源码区间树
+///
+/// `children` 的顺序与节点的子节点顺序一致：`BinaryOp` 为 `[left, right]`，
+/// `UnaryOp` 为 `[operand]`，`FunctionCall` 为各个参数，`Assign` 为 `[value]`，
+/// `FunctionDef` 为 `[body]`。位置信息单独存放，使 `AstNode` 的相等性只比较结构。
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpanTree {
+    /// 节点覆盖的源码区间
+    pub span: Span,
+    /// 子节点的区间
+    pub children: Vec<SpanTree>,
+}
+
+impl SpanTree {
+    /// 创建叶子节点
+    pub fn leaf(span: Span) -> Self {
+        Self {
+            span,
+            children: Vec::new(),
+        }
+    }
+
+    /// 创建带子节点的区间树
+    pub fn new(span: Span, children: Vec<SpanTree>) -> Self {
+        Self { span, children }
+    }
+
+    /// 获取第 index 个子节点的区间树
+    pub fn child(&self, index: usize) -> Option<&SpanTree> {
+        self.children.get(index)
+    }
+}
+
+/// 解析结果：语法树及其区间树
+type Parsed = (AstNode, SpanTree);
+
+/// 容错解析的结果
+///
+/// 即使输入有错误也会给出尽可能完整的语法树，出错处用 `AstNode::Invalid` 占位；
+/// `errors` 按出现位置排序，包含词法和语法错误。
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecoveredParse {
+    /// 部分语法树
+    pub ast: AstNode,
+    /// 语法树对应的区间树
+    pub spans: SpanTree,
+    /// 所有诊断信息（均带有位置）
+    pub errors: Vec<Error>,
+}
+
+impl RecoveredParse {
+    /// 判断输入是否没有任何错误
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
 }
 
 /// 表达式解析器
 pub struct ExpressionParser {
     // 预留字段供将来扩展
     _allow_functions: bool,
+    /// 是否把 `i` 识别为虚数单位（关闭时 `i` 是普通变量名）
+    imaginary_unit: bool,
 }
 
 impl ExpressionParser {
@@ -32,17 +124,50 @@ impl ExpressionParser {
     pub fn new() -> Self {
         Self {
             _allow_functions: true,
+            imaginary_unit: false,
         }
     }
 
+    /// 设置是否把 `i` 识别为虚数单位，如 `3 + 4i`、`2 * i`
+    pub fn with_imaginary_unit(mut self, enabled: bool) -> Self {
+        self.imaginary_unit = enabled;
+        self
+    }
+
     /// 词法分析：将字符串转换为 Token 序列
+    ///
+    /// 返回的错误不带位置；需要定位错误时使用 [`ExpressionParser::tokenize_spanned`]。
     pub fn tokenize(&self, expression: &str) -> Result<Vec<Token>, Error> {
+        let tokens = self
+            .tokenize_spanned(expression)
+            .map_err(Error::into_inner)?;
+        Ok(tokens.into_iter().map(|t| t.token).collect())
+    }
+
+    /// 词法分析：将字符串转换为带位置的 Token 序列
+    ///
+    /// 出错时返回的错误带有出错字符的位置。
+    pub fn tokenize_spanned(&self, expression: &str) -> Result<Vec<SpannedToken>, Error> {
+        let (tokens, errors) = self.scan(expression, false);
+        match errors.into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(tokens),
+        }
+    }
+
+    /// 扫描字符流
+    ///
+    /// `recover` 为 false 时遇到第一个错误即停止；为 true 时跳过无效字符继续扫描，
+    /// 无效数字用 NaN 占位，所有错误都收集起来一并返回。
+    fn scan(&self, expression: &str, recover: bool) -> (Vec<SpannedToken>, Vec<Error>) {
         let mut tokens = Vec::new();
+        let mut errors = Vec::new();
         let chars: Vec<char> = expression.chars().collect();
         let mut i = 0;
 
         while i < chars.len() {
             let ch = chars[i];
+            let here = Span::new(i, i + 1);
 
             // 跳过空白字符
             if ch.is_whitespace() {
@@ -51,52 +176,37 @@ impl ExpressionParser {
             }
 
             // 处理 Unicode 数学符号
-            match ch {
+            let symbol = match ch {
                 // 平方根符号 √
-                '√' => {
-                    tokens.push(Token::PrefixOp("sqrt".to_string()));
-                    i += 1;
-                    continue;
-                }
+                '√' => Some(Token::PrefixOp("sqrt".to_string())),
                 // 圆周率 π
-                'π' => {
-                    tokens.push(Token::Constant("pi".to_string()));
-                    i += 1;
-                    continue;
-                }
+                'π' => Some(Token::Constant("pi".to_string())),
                 // 自然常数 e
-                'ℯ' | 'ⅇ' => {
-                    tokens.push(Token::Constant("e".to_string()));
-                    i += 1;
-                    continue;
-                }
+                'ℯ' | 'ⅇ' => Some(Token::Constant("e".to_string())),
                 // 乘法 ×
-                '×' => {
-                    tokens.push(Token::Operator('*'));
-                    i += 1;
-                    continue;
-                }
+                '×' => Some(Token::Operator('*')),
                 // 除法 ÷
-                '÷' => {
-                    tokens.push(Token::Operator('/'));
-                    i += 1;
-                    continue;
-                }
+                '÷' => Some(Token::Operator('/')),
+                // 角度 °，等同于单位 deg
+                '°' => Some(Token::Unit("deg".to_string())),
+                // 阶乘 !
+                '!' => Some(Token::PostfixOp("factorial".to_string())),
                 // 上标平方 ²
                 '²' => {
-                    tokens.push(Token::Operator('^'));
-                    tokens.push(Token::Number(2.0));
-                    i += 1;
-                    continue;
+                    tokens.push(SpannedToken::new(Token::Operator('^'), here));
+                    Some(Token::Number(2.0))
                 }
                 // 上标立方 ³
                 '³' => {
-                    tokens.push(Token::Operator('^'));
-                    tokens.push(Token::Number(3.0));
-                    i += 1;
-                    continue;
+                    tokens.push(SpannedToken::new(Token::Operator('^'), here));
+                    Some(Token::Number(3.0))
                 }
-                _ => {}
+                _ => None,
+            };
+            if let Some(token) = symbol {
+                tokens.push(SpannedToken::new(token, here));
+                i += 1;
+                continue;
             }
 
             // 解析数字
@@ -105,90 +215,515 @@ impl ExpressionParser {
                 while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
                     i += 1;
                 }
+                let span = Span::new(start, i);
                 let num_str: String = chars[start..i].iter().collect();
-                let num = num_str
-                    .parse::<f64>()
-                    .map_err(|_| Error::InvalidNumber(num_str.clone()))?;
-                tokens.push(Token::Number(num));
+                let num = match num_str.parse::<f64>() {
+                    Ok(num) => num,
+                    Err(_) => {
+                        errors.push(Error::InvalidNumber(num_str.clone()).with_span(span));
+                        if !recover {
+                            break;
+                        }
+                        f64::NAN
+                    }
+                };
+                // 紧跟 `i` 的数字是虚数字面量，如 `4i`
+                if self.imaginary_unit && self.is_imaginary_unit(&chars, i) {
+                    i += 1;
+                    tokens.push(SpannedToken::new(
+                        Token::Imaginary(num),
+                        Span::new(start, i),
+                    ));
+                    continue;
+                }
+                // f64 只能精确保存约 15 位有效数字，更长的字面量保留原文供高精度模式使用
+                let token = if significant_digits(&num_str) > F64_DIGITS {
+                    Token::Literal(num_str)
+                } else {
+                    Token::Number(num)
+                };
+                tokens.push(SpannedToken::new(token, span));
                 continue;
             }
 
-            // 解析操作符
-            if "+-*/^".contains(ch) {
-                tokens.push(Token::Operator(ch));
+            // 解析单字符符号：操作符、括号、逗号、赋值符号
+            let simple = match ch {
+                '+' | '-' | '*' | '/' | '^' => Some(Token::Operator(ch)),
+                '(' => Some(Token::LeftParen),
+                ')' => Some(Token::RightParen),
+                ',' => Some(Token::Comma),
+                '=' => Some(Token::Equals),
+                _ => None,
+            };
+            if let Some(token) = simple {
+                tokens.push(SpannedToken::new(token, here));
                 i += 1;
                 continue;
             }
 
-            // 解析括号
-            if ch == '(' {
-                tokens.push(Token::LeftParen);
+            // 单独的 `i` 是虚数单位
+            if self.imaginary_unit && self.is_imaginary_unit(&chars, i) {
+                tokens.push(SpannedToken::new(Token::Imaginary(1.0), here));
                 i += 1;
                 continue;
             }
-            if ch == ')' {
-                tokens.push(Token::RightParen);
+
+            // 历史结果引用 `$n`，作为名为 `$n` 的变量
+            if ch == '$' && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit()) {
+                let start = i;
                 i += 1;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+                let name = chars[start..i].iter().collect();
+                tokens.push(SpannedToken::new(
+                    Token::Identifier(name),
+                    Span::new(start, i),
+                ));
                 continue;
             }
 
-            // 解析函数名（字母开头）
-            if ch.is_alphabetic() {
+            // 解析标识符（字母或下划线开头，可包含数字）
+            if ch.is_alphabetic() || ch == '_' {
                 let start = i;
-                while i < chars.len() && chars[i].is_alphabetic() {
+                while i < chars.len()
+                    && (chars[i].is_alphabetic() || chars[i].is_ascii_digit() || chars[i] == '_')
+                {
                     i += 1;
                 }
-                let func_name: String = chars[start..i].iter().collect();
-                tokens.push(Token::Function(func_name));
+                let span = Span::new(start, i);
+                let name: String = chars[start..i].iter().collect();
+
+                // 向后查看：紧跟左括号的是函数名，否则是变量名
+                let mut j = i;
+                while j < chars.len() && chars[j].is_whitespace() {
+                    j += 1;
+                }
+                let token = if j < chars.len() && chars[j] == '(' {
+                    Token::Function(name)
+                } else if name == "to" && follows_operand(&tokens) {
+                    Token::Convert
+                } else if units::is_unit(&name) && expects_unit(&tokens) {
+                    Token::Unit(name)
+                } else {
+                    Token::Identifier(name)
+                };
+                tokens.push(SpannedToken::new(token, span));
                 continue;
             }
 
             // 未知字符
-            return Err(Error::InvalidCharacter(ch));
+            errors.push(Error::InvalidCharacter(ch).with_span(here));
+            if !recover {
+                break;
+            }
+            i += 1;
         }
 
-        Ok(tokens)
+        (tokens, errors)
+    }
+
+    /// `chars[i]` 是否是独立的虚数单位 `i`（后面不再跟标识符字符或左括号）
+    fn is_imaginary_unit(&self, chars: &[char], i: usize) -> bool {
+        if chars.get(i) != Some(&'i') {
+            return false;
+        }
+        let rest = chars[i + 1..].iter().find(|c| !c.is_whitespace());
+        let continues = chars
+            .get(i + 1)
+            .is_some_and(|&c| c.is_alphanumeric() || c == '_');
+        !continues && rest != Some(&'(')
     }
 
     /// 语法分析：将 Token 序列转换为抽象语法树
+    ///
+    /// Token 不带位置信息，因此返回的错误也不带位置；
+    /// 需要定位错误时使用 [`ExpressionParser::parse_spanned`]。
     pub fn parse(&self, tokens: Vec<Token>) -> Result<AstNode, Error> {
+        let tokens = tokens
+            .into_iter()
+            .map(|token| SpannedToken::new(token, Span::default()))
+            .collect();
+        self.parse_spanned(tokens)
+            .map(|(ast, _)| ast)
+            .map_err(Error::into_inner)
+    }
+
+    /// 语法分析：将带位置的 Token 序列转换为抽象语法树及其区间树
+    pub fn parse_spanned(&self, tokens: Vec<SpannedToken>) -> Result<(AstNode, SpanTree), Error> {
         if tokens.is_empty() {
-            return Err(Error::ParseError("空表达式".to_string()));
+            return Err(Error::ParseError("空表达式".to_string()).with_span(Span::point(0)));
         }
 
+        let end = tokens.last().map_or(0, |t| t.span.end);
         let mut parser = Parser {
             tokens,
             position: 0,
+            end,
+            recover: false,
+            errors: Vec::new(),
         };
 
-        parser.parse_expression()
+        parser.parse_all()
+    }
+
+    /// 容错解析：一次性报告表达式中的所有问题
+    ///
+    /// 词法分析跳过无效字符；语法分析在缺少操作数处插入占位节点，
+    /// 在右括号和运算符处重新同步，并检查末尾多余的内容。
+    pub fn parse_recovering(&self, expression: &str) -> RecoveredParse {
+        let (tokens, mut errors) = self.scan(expression, true);
+        let end = expression.chars().count();
+
+        if tokens.is_empty() {
+            if errors.is_empty() {
+                errors.push(Error::ParseError("空表达式".to_string()).with_span(Span::point(0)));
+            }
+            return RecoveredParse {
+                ast: AstNode::Invalid,
+                spans: SpanTree::leaf(Span::point(0)),
+                errors,
+            };
+        }
+
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+            end,
+            recover: true,
+            errors: Vec::new(),
+        };
+        // 容错模式下 report 不会返回错误
+        let (ast, spans) = parser
+            .parse_all()
+            .unwrap_or((AstNode::Invalid, SpanTree::leaf(Span::point(0))));
+
+        // 紧跟在无效字符之后（中间只有空白）的语法错误是由它引起的，不再重复报告
+        let chars: Vec<char> = expression.chars().collect();
+        let caused_by_lexer = |span: Span| {
+            errors.iter().filter_map(|e| e.span()).any(|lexed| {
+                lexed.end <= span.start
+                    && chars[lexed.end..span.start]
+                        .iter()
+                        .all(|c| c.is_whitespace())
+            })
+        };
+        let parse_errors: Vec<Error> = parser
+            .errors
+            .into_iter()
+            .filter(|e| !e.span().is_some_and(caused_by_lexer))
+            .collect();
+        errors.extend(parse_errors);
+        errors.sort_by_key(|e| e.span().map_or(0, |span| span.start));
+        RecoveredParse { ast, spans, errors }
     }
 }
 
 /// 内部解析器状态
 struct Parser {
-    tokens: Vec<Token>,
+    tokens: Vec<SpannedToken>,
     position: usize,
+    /// 输入的结束位置，用于报告"意外结束"类错误
+    end: usize,
+    /// 是否处于容错模式
+    recover: bool,
+    /// 容错模式下收集到的错误
+    errors: Vec<Error>,
 }
 
 impl Parser {
     /// 获取当前 token
     fn current(&self) -> Option<&Token> {
-        self.tokens.get(self.position)
+        self.peek(0)
+    }
+
+    /// 查看之后第 offset 个 token
+    fn peek(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.position + offset).map(|t| &t.token)
+    }
+
+    /// 当前 token 的位置（已到末尾时为输入结束处）
+    fn current_span(&self) -> Span {
+        self.tokens
+            .get(self.position)
+            .map_or(Span::point(self.end), |t| t.span)
     }
 
-    /// 前进到下一个 token
-    fn advance(&mut self) {
+    /// 前进到下一个 token，返回被跳过 token 的位置
+    fn advance(&mut self) -> Span {
+        let span = self.current_span();
         self.position += 1;
+        span
+    }
+
+    /// 在指定位置构造解析错误
+    fn error(&self, message: String, span: Span) -> Error {
+        Error::ParseError(message).with_span(span)
+    }
+
+    /// 报告错误：容错模式下记录后继续解析，否则立即返回错误
+    fn report(&mut self, error: Error) -> Result<(), Error> {
+        if self.recover {
+            self.errors.push(error);
+            Ok(())
+        } else {
+            Err(error)
+        }
+    }
+
+    /// 解析完整输入，并检查多余的右括号和末尾多余的内容
+    fn parse_all(&mut self) -> Result<Parsed, Error> {
+        let mut result = self.parse_expression()?;
+        let mut trailing = false;
+
+        while self.position < self.tokens.len() {
+            let start = self.current_span();
+
+            if let Some(Token::RightParen) = self.current() {
+                let error = self.error("多余的右括号".to_string(), start);
+                self.report(error)?;
+                self.advance();
+
+                // 在运算符处重新同步：把前面的结果作为左操作数继续解析
+                if let Some(Token::Operator(op)) = self.current() {
+                    let op = *op;
+                    self.advance();
+                    let right = self.parse_expression()?;
+                    result = Self::binary(op, result, right);
+                }
+                continue;
+            }
+
+            // 多余的内容只报告一次
+            if !trailing {
+                let rest = start.to(Span::point(self.end));
+                let error = self.error("表达式末尾有多余的内容".to_string(), rest);
+                self.report(error)?;
+                trailing = true;
+            }
+
+            // 继续解析剩余部分以发现其中的错误，但丢弃其结果
+            let before = self.position;
+            self.parse_expression()?;
+            if self.position == before {
+                self.advance();
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// 组合二元运算节点
+    fn binary(op: char, left: Parsed, right: Parsed) -> Parsed {
+        let span = left.1.span.to(right.1.span);
+        (
+            AstNode::BinaryOp {
+                op,
+                left: Box::new(left.0),
+                right: Box::new(right.0),
+            },
+            SpanTree::new(span, vec![left.1, right.1]),
+        )
     }
 
     /// 解析表达式（最低优先级）
-    fn parse_expression(&mut self) -> Result<AstNode, Error> {
-        self.parse_additive()
+    fn parse_expression(&mut self) -> Result<Parsed, Error> {
+        self.parse_assignment()
+    }
+
+    /// 解析单位换算：`5 km to mi`，`to` 的优先级低于所有算术运算
+    fn parse_conversion(&mut self) -> Result<Parsed, Error> {
+        let (mut node, mut node_span) = self.parse_additive()?;
+
+        while let Some(Token::Convert) = self.current() {
+            self.advance();
+            let (unit, unit_span) = self.parse_unit()?;
+            let span = node_span.span.to(unit_span);
+            node = AstNode::Convert {
+                value: Box::new(node),
+                unit,
+            };
+            node_span = SpanTree::new(span, vec![node_span]);
+        }
+
+        Ok((node, node_span))
+    }
+
+    /// 解析单位表达式，如 `m`、`m/s^2`、`kg*m^2`、`N m`
+    fn parse_unit(&mut self) -> Result<(UnitExpr, Span), Error> {
+        let start = self.current_span();
+        let mut unit = UnitExpr::new();
+        let mut span = start;
+        let mut sign = 1;
+
+        loop {
+            let name = match self.current() {
+                Some(Token::Unit(name)) => name.clone(),
+                Some(Token::Identifier(name)) => {
+                    let error = Error::UnknownUnit(name.clone()).with_span(self.current_span());
+                    self.report(error)?;
+                    self.advance();
+                    return Ok((unit, span));
+                }
+                _ => {
+                    let error = self.error("缺少单位".to_string(), self.current_span());
+                    self.report(error)?;
+                    return Ok((unit, span));
+                }
+            };
+            span = span.to(self.advance());
+
+            // 单位的指数：可带负号的整数，如 `s^-2`
+            let mut power = 1;
+            if let Some(Token::Operator('^')) = self.current() {
+                let negative = matches!(self.peek(1), Some(Token::Operator('-')));
+                let offset = if negative { 2 } else { 1 };
+                match self.peek(offset) {
+                    Some(Token::Number(n)) if n.fract() == 0.0 && n.abs() <= i32::MAX as f64 => {
+                        power = if negative { -(*n as i32) } else { *n as i32 };
+                        for _ in 0..=offset {
+                            span = span.to(self.advance());
+                        }
+                    }
+                    _ => {
+                        let error =
+                            self.error("单位的指数必须是整数".to_string(), self.current_span());
+                        self.report(error)?;
+                    }
+                }
+            }
+            unit.push(&name, sign * power);
+
+            // 相邻的单位相乘，`*` 和 `/` 之后紧跟单位时继续解析
+            sign = match (self.current(), self.peek(1)) {
+                (Some(Token::Unit(_)), _) => 1,
+                (Some(Token::Operator('*')), Some(Token::Unit(_))) => {
+                    self.advance();
+                    1
+                }
+                (Some(Token::Operator('/')), Some(Token::Unit(_))) => {
+                    self.advance();
+                    -1
+                }
+                _ => break,
+            };
+        }
+
+        Ok((unit, span))
+    }
+
+    /// 操作数后紧跟单位时组合为带单位的数值，如 `3 m`、`9.81 m/s^2`、`(10 + 20)°`
+    fn with_unit(&mut self, parsed: Parsed) -> Result<Parsed, Error> {
+        if !matches!(self.current(), Some(Token::Unit(_))) {
+            return Ok(parsed);
+        }
+        let (value, value_span) = parsed;
+        let (unit, unit_span) = self.parse_unit()?;
+        let span = value_span.span.to(unit_span);
+        Ok((
+            AstNode::Quantity {
+                value: Box::new(value),
+                unit,
+            },
+            SpanTree::new(span, vec![value_span]),
+        ))
+    }
+
+    /// 解析赋值（优先级 0，右结合）：`x = 3 * 4` 或函数定义 `f(x, y) = x * y + 1`；
+    /// 左边不是变量名时是方程，如 `x^2 - 2 = 0`
+    fn parse_assignment(&mut self) -> Result<Parsed, Error> {
+        let start = self.current_span();
+
+        if let Some((name, params)) = self.function_definition_head()? {
+            let (body, body_span) = self.parse_assignment()?;
+            let span = start.to(body_span.span);
+            return Ok((
+                AstNode::FunctionDef {
+                    name,
+                    params,
+                    body: Box::new(body),
+                },
+                SpanTree::new(span, vec![body_span]),
+            ));
+        }
+
+        if let (Some(Token::Identifier(name)), Some(Token::Equals)) = (self.current(), self.peek(1))
+        {
+            let name = name.clone();
+            self.advance(); // 跳过变量名
+            self.advance(); // 跳过 '='
+            let (value, value_span) = self.parse_assignment()?;
+            let span = start.to(value_span.span);
+            return Ok((
+                AstNode::Assign {
+                    name,
+                    value: Box::new(value),
+                },
+                SpanTree::new(span, vec![value_span]),
+            ));
+        }
+
+        // 方程 `lhs = rhs`，只能作为 solve 的参数
+        let left = self.parse_conversion()?;
+        if let Some(Token::Equals) = self.current() {
+            self.advance();
+            let right = self.parse_conversion()?;
+            return Ok(Self::binary('=', left, right));
+        }
+        Ok(left)
+    }
+
+    /// 尝试识别函数定义的头部 `name(p1, p2, ...) =`
+    ///
+    /// 匹配成功时消耗头部（包括 `=`）并返回函数名和形参；
+    /// 否则不移动位置，返回 `None`，交给后续规则按函数调用解析。
+    /// 函数定义只能出现在表达式开头，其它位置的 `f(x) = 1` 是方程。
+    fn function_definition_head(&mut self) -> Result<Option<(String, Vec<String>)>, Error> {
+        if self.position != 0 {
+            return Ok(None);
+        }
+        let name = match (self.current(), self.peek(1)) {
+            (Some(Token::Function(name)), Some(Token::LeftParen)) => name.clone(),
+            _ => return Ok(None),
+        };
+
+        let mut params: Vec<(String, Span)> = Vec::new();
+        let mut offset = 2;
+        loop {
+            match self.peek(offset) {
+                Some(Token::Identifier(param)) => {
+                    let span = self.tokens[self.position + offset].span;
+                    params.push((param.clone(), span));
+                }
+                Some(Token::RightParen) if params.is_empty() => break,
+                _ => return Ok(None),
+            }
+            match self.peek(offset + 1) {
+                Some(Token::Comma) => offset += 2,
+                Some(Token::RightParen) => {
+                    offset += 1;
+                    break;
+                }
+                _ => return Ok(None),
+            }
+        }
+
+        if self.peek(offset + 1) != Some(&Token::Equals) {
+            return Ok(None);
+        }
+
+        for (i, (param, span)) in params.iter().enumerate() {
+            if params[..i].iter().any(|(p, _)| p == param) {
+                return Err(self.error(format!("函数 {} 的参数 {} 重复", name, param), *span));
+            }
+        }
+
+        self.position += offset + 2; // 跳过头部和 '='
+        Ok(Some((name, params.into_iter().map(|(p, _)| p).collect())))
     }
 
     /// 解析加减法（优先级 1）
-    fn parse_additive(&mut self) -> Result<AstNode, Error> {
+    fn parse_additive(&mut self) -> Result<Parsed, Error> {
         let mut left = self.parse_multiplicative()?;
 
         while let Some(Token::Operator(op)) = self.current() {
@@ -198,18 +733,14 @@ impl Parser {
             let op = *op;
             self.advance();
             let right = self.parse_multiplicative()?;
-            left = AstNode::BinaryOp {
-                op,
-                left: Box::new(left),
-                right: Box::new(right),
-            };
+            left = Self::binary(op, left, right);
         }
 
         Ok(left)
     }
 
     /// 解析乘除法（优先级 2）
-    fn parse_multiplicative(&mut self) -> Result<AstNode, Error> {
+    fn parse_multiplicative(&mut self) -> Result<Parsed, Error> {
         let mut left = self.parse_power()?;
 
         while let Some(Token::Operator(op)) = self.current() {
@@ -219,81 +750,127 @@ impl Parser {
             let op = *op;
             self.advance();
             let right = self.parse_power()?;
-            left = AstNode::BinaryOp {
-                op,
-                left: Box::new(left),
-                right: Box::new(right),
-            };
+            left = Self::binary(op, left, right);
         }
 
         Ok(left)
     }
 
     /// 解析幂运算（优先级 3，右结合）
-    fn parse_power(&mut self) -> Result<AstNode, Error> {
+    fn parse_power(&mut self) -> Result<Parsed, Error> {
         let left = self.parse_unary()?;
 
         if let Some(Token::Operator('^')) = self.current() {
             self.advance();
             let right = self.parse_power()?; // 右结合
-            return Ok(AstNode::BinaryOp {
-                op: '^',
-                left: Box::new(left),
-                right: Box::new(right),
-            });
+            return Ok(Self::binary('^', left, right));
         }
 
         Ok(left)
     }
 
     /// 解析一元运算符（优先级 4）
-    fn parse_unary(&mut self) -> Result<AstNode, Error> {
+    fn parse_unary(&mut self) -> Result<Parsed, Error> {
         // 处理 +/- 一元运算符
         if let Some(Token::Operator(op)) = self.current() {
             if *op == '+' || *op == '-' {
                 let op = *op;
-                self.advance();
-                let operand = self.parse_unary()?;
-                return Ok(AstNode::UnaryOp {
-                    op,
-                    operand: Box::new(operand),
-                });
+                let start = self.advance();
+                let (operand, operand_span) = self.parse_unary()?;
+                let span = start.to(operand_span.span);
+                return Ok((
+                    AstNode::UnaryOp {
+                        op,
+                        operand: Box::new(operand),
+                    },
+                    SpanTree::new(span, vec![operand_span]),
+                ));
             }
         }
 
         // 处理前缀运算符（如 √）
         if let Some(Token::PrefixOp(name)) = self.current() {
             let name = name.clone();
-            self.advance();
-            let operand = self.parse_unary()?;
-            return Ok(AstNode::FunctionCall {
+            let start = self.advance();
+            let (operand, operand_span) = self.parse_unary()?;
+            let span = start.to(operand_span.span);
+            return Ok((
+                AstNode::FunctionCall {
+                    name,
+                    args: vec![operand],
+                },
+                SpanTree::new(span, vec![operand_span]),
+            ));
+        }
+
+        self.parse_postfix()
+    }
+
+    /// 解析后缀运算符（如 `5!`），结合得比一元运算符和幂运算更紧
+    fn parse_postfix(&mut self) -> Result<Parsed, Error> {
+        let primary = self.parse_primary()?;
+        let (mut node, mut node_span) = self.with_unit(primary)?;
+
+        while let Some(Token::PostfixOp(name)) = self.current() {
+            let name = name.clone();
+            let end = self.advance();
+            let span = node_span.span.to(end);
+            node = AstNode::FunctionCall {
                 name,
-                args: vec![operand],
-            });
+                args: vec![node],
+            };
+            node_span = SpanTree::new(span, vec![node_span]);
         }
 
-        self.parse_primary()
+        Ok((node, node_span))
     }
 
-    /// 解析基本元素（数字、括号、函数调用）
-    fn parse_primary(&mut self) -> Result<AstNode, Error> {
+    /// 解析基本元素（数字、变量、括号、函数调用）
+    fn parse_primary(&mut self) -> Result<Parsed, Error> {
+        let start = self.current_span();
+
         match self.current() {
             Some(Token::Number(n)) => {
                 let n = *n;
                 self.advance();
-                Ok(AstNode::Number(n))
+                Ok((AstNode::Number(n), SpanTree::leaf(start)))
+            }
+
+            Some(Token::Literal(text)) => {
+                let text = text.clone();
+                self.advance();
+                Ok((AstNode::Literal(text), SpanTree::leaf(start)))
+            }
+
+            Some(Token::Imaginary(n)) => {
+                let n = *n;
+                self.advance();
+                Ok((AstNode::Imaginary(n), SpanTree::leaf(start)))
+            }
+
+            Some(Token::Identifier(name)) => {
+                let name = name.clone();
+                self.advance();
+                Ok((AstNode::Variable(name), SpanTree::leaf(start)))
             }
 
             Some(Token::LeftParen) => {
                 self.advance(); // 跳过 '('
-                let expr = self.parse_expression()?;
+                let (expr, expr_span) = self.parse_expression()?;
 
                 match self.current() {
                     Some(Token::RightParen) => {
-                        self.advance(); // 跳过 ')'
-                        Ok(expr)
+                        // 跳过 ')'；括号本身不产生节点，但计入内部表达式的区间
+                        let end = self.advance();
+                        Ok((expr, SpanTree::new(start.to(end), expr_span.children)))
+                    }
+                    _ => {
+                        // 未闭合的 '(' 视为在此处闭合
+                        let error = self.error("缺少右括号".to_string(), start);
+                        self.report(error)?;
+                        let span = start.to(expr_span.span);
+                        Ok((expr, SpanTree::new(span, expr_span.children)))
                     }
-                    _ => Err(Error::ParseError("缺少右括号".to_string())),
                 }
             }
 
@@ -307,35 +884,56 @@ impl Parser {
                         self.advance();
                     }
                     _ => {
-                        return Err(Error::ParseError(format!("函数 {} 后缺少左括号", name)));
+                        let error =
+                            self.error(format!("函数 {} 后缺少左括号", name), self.current_span());
+                        self.report(error)?;
+                        return Ok((AstNode::Invalid, SpanTree::leaf(start)));
                     }
                 }
 
                 // 解析参数列表
                 let mut args = Vec::new();
+                let mut arg_spans = Vec::new();
 
                 // 检查是否为空参数列表
                 if let Some(Token::RightParen) = self.current() {
-                    self.advance();
-                    return Ok(AstNode::FunctionCall { name, args });
+                    let end = self.advance();
+                    return Ok((
+                        AstNode::FunctionCall { name, args },
+                        SpanTree::leaf(start.to(end)),
+                    ));
                 }
 
-                // 解析第一个参数
-                args.push(self.parse_expression()?);
-
-                // 解析剩余参数（逗号分隔）
-                // 注意：这个简化版本暂不支持逗号，只支持单参数函数
-
-                // 期望右括号
-                match self.current() {
-                    Some(Token::RightParen) => {
-                        self.advance();
-                        Ok(AstNode::FunctionCall { name, args })
+                // 解析参数列表（逗号分隔）
+                loop {
+                    let (arg, arg_span) = self.parse_expression()?;
+                    args.push(arg);
+                    arg_spans.push(arg_span);
+
+                    match self.current() {
+                        Some(Token::Comma) => {
+                            self.advance();
+                        }
+                        Some(Token::RightParen) => {
+                            let end = self.advance();
+                            return Ok((
+                                AstNode::FunctionCall { name, args },
+                                SpanTree::new(start.to(end), arg_spans),
+                            ));
+                        }
+                        _ => {
+                            let error = self.error(
+                                format!("函数 {} 参数列表缺少右括号", name),
+                                self.current_span(),
+                            );
+                            self.report(error)?;
+                            let span = arg_spans.iter().fold(start, |acc, s| acc.to(s.span));
+                            return Ok((
+                                AstNode::FunctionCall { name, args },
+                                SpanTree::new(span, arg_spans),
+                            ));
+                        }
                     }
-                    _ => Err(Error::ParseError(format!(
-                        "函数 {} 参数列表缺少右括号",
-                        name
-                    ))),
                 }
             }
 
@@ -343,20 +941,28 @@ impl Parser {
                 let name = name.clone();
                 self.advance();
 
-                // 返回常量对应的数值
-                let value = match name.as_str() {
-                    "pi" => std::f64::consts::PI,
-                    "e" => std::f64::consts::E,
-                    _ => return Err(Error::ParseError(format!("未知常量: {}", name))),
-                };
+                // 常量的数值由求值器按数值模式决定
+                if !matches!(name.as_str(), "pi" | "e") {
+                    let error = self.error(format!("未知常量: {}", name), start);
+                    self.report(error)?;
+                    return Ok((AstNode::Invalid, SpanTree::leaf(start)));
+                }
 
-                Ok(AstNode::Number(value))
+                Ok((AstNode::Constant(name), SpanTree::leaf(start)))
             }
 
-            _ => Err(Error::ParseError(format!(
-                "意外的 token: {:?}",
-                self.current()
-            ))),
+            // 缺少操作数：不消耗当前 token，让上层在运算符或括号处继续
+            Some(token) => {
+                let error = self.error(format!("缺少操作数，遇到 {:?}", token), start);
+                self.report(error)?;
+                Ok((AstNode::Invalid, SpanTree::leaf(Span::point(start.start))))
+            }
+
+            None => {
+                let error = self.error("表达式意外结束，缺少操作数".to_string(), start);
+                self.report(error)?;
+                Ok((AstNode::Invalid, SpanTree::leaf(start)))
+            }
         }
     }
 }
@@ -372,6 +978,12 @@ impl Default for ExpressionParser {
 pub enum AstNode {
     /// 数字节点
     Number(f64),
+    /// 超出 f64 精度的数字字面量（保留原文）
+    Literal(String),
+    /// 虚数节点（复数模式下的 `2i`、`i`）
+    Imaginary(f64),
+    /// 常量节点（`pi` 或 `e`）
+    Constant(String),
     /// 二元操作节点
     BinaryOp {
         op: char,
@@ -382,6 +994,108 @@ pub enum AstNode {
     UnaryOp { op: char, operand: Box<AstNode> },
     /// 函数调用节点
     FunctionCall { name: String, args: Vec<AstNode> },
+    /// 变量引用节点
+    Variable(String),
+    /// 赋值节点
+    Assign { name: String, value: Box<AstNode> },
+    /// 函数定义节点
+    FunctionDef {
+        name: String,
+        params: Vec<String>,
+        body: Box<AstNode>,
+    },
+    /// 带单位的数值，如 `3 m`
+    Quantity { value: Box<AstNode>, unit: UnitExpr },
+    /// 单位换算，如 `5 km to mi`
+    Convert { value: Box<AstNode>, unit: UnitExpr },
+    /// 解析失败处的占位节点（只出现在容错解析的结果中）
+    Invalid,
+}
+
+impl AstNode {
+    /// 对每个直接子节点应用 `f`，重建结构相同的节点
+    pub(crate) fn map_children<E, F>(&self, mut f: F) -> Result<AstNode, E>
+    where
+        F: FnMut(&AstNode) -> Result<AstNode, E>,
+    {
+        Ok(match self {
+            AstNode::BinaryOp { op, left, right } => AstNode::BinaryOp {
+                op: *op,
+                left: Box::new(f(left)?),
+                right: Box::new(f(right)?),
+            },
+            AstNode::UnaryOp { op, operand } => AstNode::UnaryOp {
+                op: *op,
+                operand: Box::new(f(operand)?),
+            },
+            AstNode::FunctionCall { name, args } => AstNode::FunctionCall {
+                name: name.clone(),
+                args: args.iter().map(f).collect::<Result<_, E>>()?,
+            },
+            AstNode::Quantity { value, unit } => AstNode::Quantity {
+                value: Box::new(f(value)?),
+                unit: unit.clone(),
+            },
+            AstNode::Convert { value, unit } => AstNode::Convert {
+                value: Box::new(f(value)?),
+                unit: unit.clone(),
+            },
+            AstNode::Assign { name, value } => AstNode::Assign {
+                name: name.clone(),
+                value: Box::new(f(value)?),
+            },
+            _ => self.clone(),
+        })
+    }
+}
+
+/// 当前位置是否紧跟在一个操作数之后（此时 `to` 是换算关键字而不是变量名）
+fn follows_operand(tokens: &[SpannedToken]) -> bool {
+    matches!(
+        tokens.last().map(|t| &t.token),
+        Some(
+            Token::Number(_)
+                | Token::Literal(_)
+                | Token::Imaginary(_)
+                | Token::Unit(_)
+                | Token::Identifier(_)
+                | Token::Constant(_)
+                | Token::RightParen
+                | Token::PostfixOp(_)
+        )
+    )
+}
+
+/// 当前位置的单位符号是否应识别为单位：紧跟在数字、`to` 或另一个单位之后，
+/// 或者是单位表达式中 `*`、`/` 之后的部分（如 `m/s` 中的 `s`）
+fn expects_unit(tokens: &[SpannedToken]) -> bool {
+    let tokens: Vec<&Token> = tokens.iter().map(|t| &t.token).collect();
+    match tokens.as_slice() {
+        [.., Token::Number(_) | Token::Literal(_) | Token::Unit(_) | Token::Convert] => true,
+        [rest @ .., Token::Operator('*' | '/')] => {
+            // 跳过前一个单位的指数，如 `s^2`、`s^-2`
+            let rest = match rest {
+                [rest @ .., Token::Operator('^'), Token::Number(_)]
+                | [rest @ .., Token::Operator('^'), Token::Operator('-'), Token::Number(_)] => rest,
+                rest => rest,
+            };
+            matches!(rest.last(), Some(Token::Unit(_)))
+        }
+        _ => false,
+    }
+}
+
+/// 数字字面量的有效位数（不计前导 0 和小数点后末尾的 0）
+fn significant_digits(text: &str) -> usize {
+    let text = if text.contains('.') {
+        text.trim_end_matches('0')
+    } else {
+        text
+    };
+    text.chars()
+        .filter(char::is_ascii_digit)
+        .skip_while(|&c| c == '0')
+        .count()
 }
 
 #[cfg(test)]
@@ -561,6 +1275,360 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_multiple_arguments() {
+        let parser = ExpressionParser::new();
+        let tokens = parser.tokenize("max(a, 2 + 3, sqrt(4))").unwrap();
+        assert_eq!(tokens[3], Token::Comma);
+        let ast = parser.parse(tokens).unwrap();
+
+        match ast {
+            AstNode::FunctionCall { name, args } => {
+                assert_eq!(name, "max");
+                assert_eq!(args.len(), 3);
+                assert!(matches!(args[1], AstNode::BinaryOp { op: '+', .. }));
+            }
+            _ => panic!("Expected FunctionCall"),
+        }
+    }
+
+    #[test]
+    fn test_parse_trailing_comma() {
+        let parser = ExpressionParser::new();
+        let tokens = parser.tokenize("log(8,)").unwrap();
+        assert!(parser.parse(tokens).is_err());
+    }
+
+    #[test]
+    fn test_tokenize_identifier_and_function() {
+        let parser = ExpressionParser::new();
+        let tokens = parser.tokenize("x1 = sin (y_2)").unwrap();
+
+        assert_eq!(tokens[0], Token::Identifier("x1".to_string()));
+        assert_eq!(tokens[1], Token::Equals);
+        assert_eq!(tokens[2], Token::Function("sin".to_string()));
+        assert_eq!(tokens[4], Token::Identifier("y_2".to_string()));
+    }
+
+    #[test]
+    fn test_tokenize_history_reference() {
+        let parser = ExpressionParser::new();
+        let tokens = parser.tokenize("$12 * _").unwrap();
+        assert_eq!(tokens[0], Token::Identifier("$12".to_string()));
+        assert_eq!(tokens[2], Token::Identifier("_".to_string()));
+
+        // 不跟数字的 `$` 是无效字符
+        assert_eq!(parser.tokenize("$x"), Err(Error::InvalidCharacter('$')));
+    }
+
+    #[test]
+    fn test_parse_variable() {
+        let parser = ExpressionParser::new();
+        let tokens = parser.tokenize("x ^ 2").unwrap();
+        let ast = parser.parse(tokens).unwrap();
+
+        match ast {
+            AstNode::BinaryOp { op, left, .. } => {
+                assert_eq!(op, '^');
+                assert_eq!(*left, AstNode::Variable("x".to_string()));
+            }
+            _ => panic!("Expected BinaryOp"),
+        }
+    }
+
+    #[test]
+    fn test_parse_assignment() {
+        let parser = ExpressionParser::new();
+        let tokens = parser.tokenize("x = 3 * 4").unwrap();
+        let ast = parser.parse(tokens).unwrap();
+
+        match ast {
+            AstNode::Assign { name, value } => {
+                assert_eq!(name, "x");
+                assert!(matches!(*value, AstNode::BinaryOp { op: '*', .. }));
+            }
+            _ => panic!("Expected Assign"),
+        }
+    }
+
+    #[test]
+    fn test_parse_function_definition() {
+        let parser = ExpressionParser::new();
+        let tokens = parser.tokenize("f(x, y) = x * y + 1").unwrap();
+        let ast = parser.parse(tokens).unwrap();
+
+        match ast {
+            AstNode::FunctionDef { name, params, body } => {
+                assert_eq!(name, "f");
+                assert_eq!(params, vec!["x".to_string(), "y".to_string()]);
+                assert!(matches!(*body, AstNode::BinaryOp { op: '+', .. }));
+            }
+            _ => panic!("Expected FunctionDef"),
+        }
+    }
+
+    #[test]
+    fn test_parse_call_is_not_definition() {
+        let parser = ExpressionParser::new();
+        let tokens = parser.tokenize("f(2, y)").unwrap();
+        let ast = parser.parse(tokens).unwrap();
+        assert!(matches!(ast, AstNode::FunctionCall { .. }));
+
+        let tokens = parser.tokenize("f(x, x) = x").unwrap();
+        assert!(parser.parse(tokens).is_err());
+    }
+
+    #[test]
+    fn test_parse_equation() {
+        let parser = ExpressionParser::new();
+        let tokens = parser.tokenize("x^2 - 2 = 0").unwrap();
+        let ast = parser.parse(tokens).unwrap();
+        assert!(matches!(ast, AstNode::BinaryOp { op: '=', .. }));
+
+        // 参数中的 `sin(x) = 0.5` 是方程而不是函数定义
+        let tokens = parser.tokenize("solve(sin(x) = 0.5, x)").unwrap();
+        match parser.parse(tokens).unwrap() {
+            AstNode::FunctionCall { args, .. } => {
+                assert!(matches!(args[0], AstNode::BinaryOp { op: '=', .. }));
+            }
+            ast => panic!("{:?}", ast),
+        }
+
+        let tokens = parser.tokenize("1 = 2 = 3").unwrap();
+        assert!(parser.parse(tokens).is_err());
+    }
+
+    #[test]
+    fn test_tokenize_spans() {
+        let parser = ExpressionParser::new();
+        let tokens = parser.tokenize_spanned("√16 + sin(x)").unwrap();
+
+        assert_eq!(tokens[0].span, Span::new(0, 1));
+        assert_eq!(tokens[1].span, Span::new(1, 3));
+        assert_eq!(tokens[3].token, Token::Function("sin".to_string()));
+        assert_eq!(tokens[3].span, Span::new(6, 9));
+    }
+
+    #[test]
+    fn test_tokenize_error_span() {
+        let parser = ExpressionParser::new();
+        let err = parser.tokenize_spanned("1 + π @ 2").unwrap_err();
+
+        assert_eq!(err.inner(), &Error::InvalidCharacter('@'));
+        assert_eq!(err.span(), Some(Span::new(6, 7)));
+    }
+
+    #[test]
+    fn test_parse_span_tree() {
+        let parser = ExpressionParser::new();
+        let tokens = parser.tokenize_spanned("(1 + 2) * max(3, 4)").unwrap();
+        let (_, spans) = parser.parse_spanned(tokens).unwrap();
+
+        assert_eq!(spans.span, Span::new(0, 19));
+        let left = spans.child(0).unwrap();
+        assert_eq!(left.span, Span::new(0, 7));
+        assert_eq!(left.child(1).unwrap().span, Span::new(5, 6));
+        let call = spans.child(1).unwrap();
+        assert_eq!(call.span, Span::new(10, 19));
+        assert_eq!(call.child(1).unwrap().span, Span::new(17, 18));
+    }
+
+    #[test]
+    fn test_parse_error_span() {
+        let parser = ExpressionParser::new();
+
+        let tokens = parser.tokenize_spanned("2 * (3 + 4").unwrap();
+        let err = parser.parse_spanned(tokens).unwrap_err();
+        assert_eq!(err.span(), Some(Span::new(4, 5)));
+
+        let tokens = parser.tokenize_spanned("2 * * 3").unwrap();
+        let err = parser.parse_spanned(tokens).unwrap_err();
+        assert_eq!(err.span(), Some(Span::new(4, 5)));
+
+        let tokens = parser.tokenize_spanned("2 +").unwrap();
+        let err = parser.parse_spanned(tokens).unwrap_err();
+        assert_eq!(err.span(), Some(Span::point(3)));
+    }
+
+    #[test]
+    fn test_tokenize_units() {
+        let parser = ExpressionParser::new();
+        let tokens = parser.tokenize("9.81 m/s^2 * 2 s").unwrap();
+        assert_eq!(tokens[1], Token::Unit("m".to_string()));
+        assert_eq!(tokens[3], Token::Unit("s".to_string()));
+        assert_eq!(tokens[8], Token::Unit("s".to_string()));
+
+        // 不跟在数字之后的单位符号仍是变量名
+        let tokens = parser.tokenize("m * s").unwrap();
+        assert_eq!(tokens[0], Token::Identifier("m".to_string()));
+        assert_eq!(tokens[2], Token::Identifier("s".to_string()));
+
+        let tokens = parser.tokenize("x to km").unwrap();
+        assert_eq!(tokens[1], Token::Convert);
+        assert_eq!(tokens[2], Token::Unit("km".to_string()));
+    }
+
+    #[test]
+    fn test_parse_units() {
+        let parser = ExpressionParser::new();
+        let tokens = parser.tokenize("5 km to mi").unwrap();
+        let ast = parser.parse(tokens).unwrap();
+        assert_eq!(
+            ast,
+            AstNode::Convert {
+                value: Box::new(AstNode::Quantity {
+                    value: Box::new(AstNode::Number(5.0)),
+                    unit: UnitExpr::parse("km").unwrap(),
+                }),
+                unit: UnitExpr::parse("mi").unwrap(),
+            }
+        );
+
+        let tokens = parser.tokenize("2 kg*m/s^2").unwrap();
+        match parser.parse(tokens).unwrap() {
+            AstNode::Quantity { unit, .. } => assert_eq!(unit.to_string(), "kg*m/s^2"),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let errors = parser.parse_recovering("3 m to furlong").errors;
+        assert_eq!(
+            errors,
+            vec![Error::UnknownUnit("furlong".to_string()).with_span(Span::new(7, 14))]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_degree_sign() {
+        let parser = ExpressionParser::new();
+        let tokens = parser.tokenize("30°").unwrap();
+        assert_eq!(
+            tokens,
+            vec![Token::Number(30.0), Token::Unit("deg".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_imaginary_unit() {
+        let parser = ExpressionParser::new().with_imaginary_unit(true);
+        let tokens = parser.tokenize("3 + 4i - i").unwrap();
+        assert_eq!(tokens[2], Token::Imaginary(4.0));
+        assert_eq!(tokens[4], Token::Imaginary(1.0));
+
+        // 以 i 开头的标识符和函数名不受影响
+        let tokens = parser.tokenize("in + i2").unwrap();
+        assert_eq!(tokens[0], Token::Identifier("in".to_string()));
+        assert_eq!(tokens[2], Token::Identifier("i2".to_string()));
+
+        // 未开启时 i 是普通变量
+        let tokens = ExpressionParser::new().tokenize("2 * i").unwrap();
+        assert_eq!(tokens[2], Token::Identifier("i".to_string()));
+    }
+
+    #[test]
+    fn test_parse_postfix_factorial() {
+        let parser = ExpressionParser::new();
+        let factorial = |n: f64| AstNode::FunctionCall {
+            name: "factorial".to_string(),
+            args: vec![AstNode::Number(n)],
+        };
+
+        // 后缀运算符先于幂运算和一元负号
+        let ast = parser.parse(parser.tokenize("2^3!").unwrap()).unwrap();
+        assert_eq!(
+            ast,
+            AstNode::BinaryOp {
+                op: '^',
+                left: Box::new(AstNode::Number(2.0)),
+                right: Box::new(factorial(3.0)),
+            }
+        );
+
+        let ast = parser.parse(parser.tokenize("-3!").unwrap()).unwrap();
+        assert_eq!(
+            ast,
+            AstNode::UnaryOp {
+                op: '-',
+                operand: Box::new(factorial(3.0)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_long_literal_keeps_text() {
+        let parser = ExpressionParser::new();
+        let tokens = parser.tokenize("0.1 + 1.00000000000000000001").unwrap();
+        assert_eq!(tokens[0], Token::Number(0.1));
+        assert_eq!(
+            tokens[2],
+            Token::Literal("1.00000000000000000001".to_string())
+        );
+
+        let tokens = parser.tokenize("π").unwrap();
+        let ast = parser.parse(tokens).unwrap();
+        assert_eq!(ast, AstNode::Constant("pi".to_string()));
+    }
+
+    #[test]
+    fn test_parse_rejects_trailing_tokens() {
+        let parser = ExpressionParser::new();
+        let tokens = parser.tokenize_spanned("1 2").unwrap();
+        let err = parser.parse_spanned(tokens).unwrap_err();
+        assert_eq!(err.span(), Some(Span::new(2, 3)));
+
+        let tokens = parser.tokenize("(1 + 2))").unwrap();
+        assert!(parser.parse(tokens).is_err());
+    }
+
+    #[test]
+    fn test_parse_recovering_reports_all_errors() {
+        let parser = ExpressionParser::new();
+        let result = parser.parse_recovering("(1 + @) * (2 - ");
+
+        let spans: Vec<Span> = result.errors.iter().filter_map(|e| e.span()).collect();
+        assert_eq!(
+            spans,
+            vec![Span::new(5, 6), Span::new(10, 11), Span::point(15)]
+        );
+        assert_eq!(result.errors[0].inner(), &Error::InvalidCharacter('@'));
+        assert!(!result.is_ok());
+    }
+
+    #[test]
+    fn test_parse_recovering_partial_ast() {
+        let parser = ExpressionParser::new();
+        let result = parser.parse_recovering("2 * * 3) + 1");
+
+        // 缺少的操作数用占位节点代替，多余的右括号之后继续解析
+        assert_eq!(result.errors.len(), 2);
+        let expected = AstNode::BinaryOp {
+            op: '+',
+            left: Box::new(AstNode::BinaryOp {
+                op: '*',
+                left: Box::new(AstNode::BinaryOp {
+                    op: '*',
+                    left: Box::new(AstNode::Number(2.0)),
+                    right: Box::new(AstNode::Invalid),
+                }),
+                right: Box::new(AstNode::Number(3.0)),
+            }),
+            right: Box::new(AstNode::Number(1.0)),
+        };
+        assert_eq!(result.ast, expected);
+    }
+
+    #[test]
+    fn test_parse_recovering_trailing_garbage() {
+        let parser = ExpressionParser::new();
+        let result = parser.parse_recovering("1 + 2 3 4");
+
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].span(), Some(Span::new(6, 9)));
+        assert!(matches!(result.ast, AstNode::BinaryOp { op: '+', .. }));
+
+        let result = parser.parse_recovering("sqrt(4) + 1");
+        assert!(result.is_ok());
+    }
+
     #[test]
     fn test_invalid_character() {
         let parser = ExpressionParser::new();
@@ -0,0 +1,543 @@
+// calculator/printer.rs - 把语法树打印回表达式文本
+
+use std::fmt;
+
+use super::parser::AstNode;
+use super::units::{self, UnitExpr};
+
+/// 语法树的打印风格
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PrintStyle {
+    /// 纯 ASCII，如 `sqrt(x) * y^2`
+    #[default]
+    Ascii,
+    /// Unicode 数学符号，如 `√x × y²`
+    Unicode,
+    /// LaTeX 公式，如 `\sqrt{x} \cdot y^{2}`
+    Latex,
+}
+
+/// 运算的优先级，从低到高与 `ExpressionParser` 的各层解析规则一一对应
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    /// 赋值与函数定义
+    Assignment,
+    /// 单位换算 `to`
+    Conversion,
+    /// 加减法
+    Additive,
+    /// 乘除法
+    Multiplicative,
+    /// 幂运算
+    Power,
+    /// 一元运算符与前缀 `√`
+    Unary,
+    /// 后缀 `!` 与带单位的数值
+    Postfix,
+    /// 数字、变量、函数调用、括号
+    Primary,
+}
+
+/// 打印结果
+struct Printed {
+    text: String,
+    /// 最外层运算的优先级
+    precedence: Precedence,
+    /// 文本以单位结尾：后面紧跟 `*`、`/`、`^` 时会被解析为单位表达式的一部分
+    ends_with_unit: bool,
+}
+
+impl Printed {
+    fn new(text: String, precedence: Precedence) -> Self {
+        Self {
+            text,
+            precedence,
+            ends_with_unit: false,
+        }
+    }
+
+    /// 以负号开头的数字与一元负号的优先级相同
+    fn number(text: String) -> Self {
+        let precedence = if text.starts_with('-') {
+            Precedence::Unary
+        } else {
+            Precedence::Primary
+        };
+        Self::new(text, precedence)
+    }
+}
+
+impl AstNode {
+    /// 按指定风格打印为表达式文本，只在必要处加括号
+    ///
+    /// ASCII 和 Unicode 风格的结果可以重新解析为相同的语法树（Unicode 风格下常量写作 `π`、
+    /// `ℯ`；ASCII 风格下写作 `pi`、`e`，重新解析后是同名变量）。
+    ///
+    /// ```
+    /// use chapter02::calculator::parser::ExpressionParser;
+    /// use chapter02::calculator::PrintStyle;
+    ///
+    /// let parser = ExpressionParser::new();
+    /// let ast = parser.parse(parser.tokenize("((x + 1)) * (y ^ 2) / sqrt(2)").unwrap()).unwrap();
+    /// assert_eq!(ast.to_string(), "(x + 1) * y^2 / sqrt(2)");
+    /// assert_eq!(ast.format(PrintStyle::Unicode), "(x + 1) × y² ÷ √2");
+    /// assert_eq!(ast.format(PrintStyle::Latex), r"\frac{\left(x + 1\right) \cdot y^{2}}{\sqrt{2}}");
+    /// ```
+    pub fn format(&self, style: PrintStyle) -> String {
+        Printer { style }.print(self).text
+    }
+}
+
+impl fmt::Display for AstNode {
+    /// ASCII 风格，与 `format(PrintStyle::Ascii)` 相同
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format(PrintStyle::Ascii))
+    }
+}
+
+struct Printer {
+    style: PrintStyle,
+}
+
+impl Printer {
+    fn print(&self, node: &AstNode) -> Printed {
+        match node {
+            AstNode::Number(n) => Printed::number(n.to_string()),
+            AstNode::Literal(text) => Printed::number(text.clone()),
+            AstNode::Imaginary(n) if *n == 1.0 => {
+                Printed::new("i".to_string(), Precedence::Primary)
+            }
+            AstNode::Imaginary(n) => Printed::number(format!("{}i", n)),
+            AstNode::Constant(name) => {
+                let text = match (self.style, name.as_str()) {
+                    (PrintStyle::Unicode, "pi") => "π",
+                    (PrintStyle::Unicode, "e") => "ℯ",
+                    (PrintStyle::Latex, "pi") => r"\pi",
+                    (_, name) => name,
+                };
+                Printed::new(text.to_string(), Precedence::Primary)
+            }
+            AstNode::Variable(name) => Printed::new(self.identifier(name), Precedence::Primary),
+            AstNode::BinaryOp { op, left, right } => self.binary(*op, left, right),
+            AstNode::UnaryOp { op, operand } => {
+                // LaTeX 按数学习惯，`-x^{2}` 表示 `-(x^2)`
+                let min = match self.style {
+                    PrintStyle::Latex => Precedence::Power,
+                    _ => Precedence::Unary,
+                };
+                let operand = self.operand(operand, min);
+                Printed {
+                    text: format!("{}{}", op, operand.text),
+                    precedence: Precedence::Unary,
+                    ends_with_unit: operand.ends_with_unit,
+                }
+            }
+            AstNode::FunctionCall { name, args } => self.call(name, args),
+            AstNode::Assign { name, value } => {
+                let value = self.operand(value, Precedence::Assignment);
+                let text = format!("{} = {}", self.identifier(name), value.text);
+                Printed::new(text, Precedence::Assignment)
+            }
+            AstNode::FunctionDef { name, params, body } => {
+                let params: Vec<String> = params.iter().map(|p| self.identifier(p)).collect();
+                let body = self.operand(body, Precedence::Assignment);
+                let head = self.call_text(&self.function_name(name), &params.join(", "));
+                Printed::new(format!("{} = {}", head, body.text), Precedence::Assignment)
+            }
+            AstNode::Quantity { value, unit } => {
+                let value = self.operand(value, Precedence::Primary);
+                let degrees = unit.to_string() == "deg";
+                let text = match self.style {
+                    PrintStyle::Unicode if degrees => format!("{}°", value.text),
+                    PrintStyle::Latex if degrees => format!(r"{}^{{\circ}}", value.text),
+                    PrintStyle::Latex => format!(r"{}\,{}", value.text, self.unit(unit)),
+                    _ => format!("{} {}", value.text, unit),
+                };
+                Printed {
+                    text,
+                    precedence: Precedence::Postfix,
+                    ends_with_unit: true,
+                }
+            }
+            AstNode::Convert { value, unit } => {
+                let value = self.operand(value, Precedence::Conversion);
+                let keyword = match self.style {
+                    PrintStyle::Latex => r"\to",
+                    _ => "to",
+                };
+                Printed {
+                    text: format!("{} {} {}", value.text, keyword, self.unit(unit)),
+                    precedence: Precedence::Conversion,
+                    ends_with_unit: true,
+                }
+            }
+            AstNode::Invalid => Printed::new("?".to_string(), Precedence::Primary),
+        }
+    }
+
+    fn binary(&self, op: char, left: &AstNode, right: &AstNode) -> Printed {
+        let latex = self.style == PrintStyle::Latex;
+
+        if latex && op == '/' {
+            let numerator = self.operand(left, Precedence::Assignment);
+            let denominator = self.operand(right, Precedence::Assignment);
+            let text = format!(r"\frac{{{}}}{{{}}}", numerator.text, denominator.text);
+            return Printed::new(text, Precedence::Primary);
+        }
+
+        // 左结合的运算右操作数需要更高的优先级，右结合的 `^` 则是左操作数
+        let (precedence, left_min, right_min) = match op {
+            '+' | '-' => (
+                Precedence::Additive,
+                Precedence::Additive,
+                Precedence::Multiplicative,
+            ),
+            '*' | '/' => (
+                Precedence::Multiplicative,
+                Precedence::Multiplicative,
+                Precedence::Power,
+            ),
+            // LaTeX 的底数是分式或一元运算时也加括号
+            '^' if latex => (
+                Precedence::Power,
+                Precedence::Primary,
+                Precedence::Assignment,
+            ),
+            '^' => (Precedence::Power, Precedence::Unary, Precedence::Power),
+            // 方程 `lhs = rhs` 与赋值同级，两边都是换算表达式
+            _ => (
+                Precedence::Assignment,
+                Precedence::Conversion,
+                Precedence::Conversion,
+            ),
+        };
+
+        let mut left = self.operand(left, left_min);
+        let right_node = right;
+        let right = self.operand(right, right_min);
+
+        // 单位之后的 `^` 和紧跟单位名的 `*`、`/` 会被解析成单位表达式的一部分
+        let joins_unit = match op {
+            '^' => true,
+            '*' | '/' => starts_with_unit(&right.text),
+            _ => false,
+        };
+        if left.ends_with_unit && joins_unit && !latex {
+            left = self.parenthesize(left);
+        }
+        if latex && op == '^' && left.text.starts_with(r"\frac") {
+            left = self.parenthesize(left);
+        }
+
+        let text = match (self.style, op) {
+            (PrintStyle::Unicode, '^') => match right_node {
+                AstNode::Number(n) if *n == 2.0 => format!("{}²", left.text),
+                AstNode::Number(n) if *n == 3.0 => format!("{}³", left.text),
+                _ => format!("{}^{}", left.text, right.text),
+            },
+            (PrintStyle::Latex, '^') => format!("{}^{{{}}}", left.text, right.text),
+            (_, '^') => format!("{}^{}", left.text, right.text),
+            (PrintStyle::Unicode, '*') => format!("{} × {}", left.text, right.text),
+            (PrintStyle::Unicode, '/') => format!("{} ÷ {}", left.text, right.text),
+            (PrintStyle::Latex, '*') => format!(r"{} \cdot {}", left.text, right.text),
+            (_, op) => format!("{} {} {}", left.text, op, right.text),
+        };
+        let ends_with_unit = right.ends_with_unit && !(latex && op == '^');
+        Printed {
+            text,
+            precedence,
+            ends_with_unit,
+        }
+    }
+
+    fn call(&self, name: &str, args: &[AstNode]) -> Printed {
+        match (self.style, name, args) {
+            // 后缀阶乘
+            (style, "factorial", [arg]) => {
+                let min = match style {
+                    PrintStyle::Latex => Precedence::Primary,
+                    _ => Precedence::Postfix,
+                };
+                let arg = self.operand(arg, min);
+                Printed::new(format!("{}!", arg.text), Precedence::Postfix)
+            }
+            (PrintStyle::Unicode, "sqrt", [arg]) => {
+                let arg = self.operand(arg, Precedence::Unary);
+                Printed {
+                    text: format!("√{}", arg.text),
+                    precedence: Precedence::Unary,
+                    ends_with_unit: arg.ends_with_unit,
+                }
+            }
+            (PrintStyle::Latex, "sqrt", [arg]) => {
+                let arg = self.operand(arg, Precedence::Assignment);
+                Printed::new(format!(r"\sqrt{{{}}}", arg.text), Precedence::Primary)
+            }
+            (PrintStyle::Latex, "abs", [arg]) => {
+                let arg = self.operand(arg, Precedence::Assignment);
+                Printed::new(format!(r"\left|{}\right|", arg.text), Precedence::Primary)
+            }
+            _ => {
+                let args: Vec<String> = args
+                    .iter()
+                    .map(|arg| self.operand(arg, Precedence::Assignment).text)
+                    .collect();
+                let text = self.call_text(&self.function_name(name), &args.join(", "));
+                Printed::new(text, Precedence::Primary)
+            }
+        }
+    }
+
+    /// 打印操作数，优先级低于 `min` 时加括号
+    fn operand(&self, node: &AstNode, min: Precedence) -> Printed {
+        let printed = self.print(node);
+        if printed.precedence < min {
+            self.parenthesize(printed)
+        } else {
+            printed
+        }
+    }
+
+    fn parenthesize(&self, printed: Printed) -> Printed {
+        let text = match self.style {
+            PrintStyle::Latex => format!(r"\left({}\right)", printed.text),
+            _ => format!("({})", printed.text),
+        };
+        Printed::new(text, Precedence::Primary)
+    }
+
+    fn call_text(&self, name: &str, args: &str) -> String {
+        match self.style {
+            PrintStyle::Latex => format!(r"{}\left({}\right)", name, args),
+            _ => format!("{}({})", name, args),
+        }
+    }
+
+    /// LaTeX 中常见函数使用对应的命令，其余函数名用 `\operatorname`
+    fn function_name(&self, name: &str) -> String {
+        if self.style != PrintStyle::Latex {
+            return name.to_string();
+        }
+        match name {
+            "sin" | "cos" | "tan" | "sinh" | "cosh" | "tanh" | "ln" | "log" | "exp" | "max"
+            | "min" | "gcd" => format!(r"\{}", name),
+            "asin" | "acos" | "atan" => format!(r"\arc{}", &name[1..]),
+            _ => format!(r"\operatorname{{{}}}", name),
+        }
+    }
+
+    /// LaTeX 中多字母的变量名用正体，避免显示成几个变量的乘积
+    fn identifier(&self, name: &str) -> String {
+        if self.style == PrintStyle::Latex && name.chars().count() > 1 {
+            format!(r"\mathrm{{{}}}", name)
+        } else {
+            name.to_string()
+        }
+    }
+
+    fn unit(&self, unit: &UnitExpr) -> String {
+        match self.style {
+            PrintStyle::Latex => {
+                format!(r"\mathrm{{{}}}", unit.to_string().replace('*', r" \cdot "))
+            }
+            _ => unit.to_string(),
+        }
+    }
+}
+
+/// 文本是否以单位名开头（后面没有紧跟左括号，即不是函数调用）
+fn starts_with_unit(text: &str) -> bool {
+    let end = text
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(text.len());
+    let (name, rest) = text.split_at(end);
+    name.starts_with(|c: char| c.is_alphabetic())
+        && units::is_unit(name)
+        && !rest.trim_start().starts_with('(')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculator::parser::ExpressionParser;
+    use proptest::prelude::*;
+
+    fn parse(expression: &str) -> AstNode {
+        let parser = ExpressionParser::new();
+        parser.parse(parser.tokenize(expression).unwrap()).unwrap()
+    }
+
+    fn assert_prints(expression: &str, style: PrintStyle, expected: &str) {
+        assert_eq!(parse(expression).format(style), expected, "{}", expression);
+    }
+
+    #[test]
+    fn test_minimal_parentheses() {
+        let ascii = PrintStyle::Ascii;
+        assert_prints("((1 + 2)) + 3", ascii, "1 + 2 + 3");
+        assert_prints("1 + (2 + 3)", ascii, "1 + (2 + 3)");
+        assert_prints("1 - (2 - 3)", ascii, "1 - (2 - 3)");
+        assert_prints("(1 * 2) / (3 * 4)", ascii, "1 * 2 / (3 * 4)");
+        assert_prints("2 ^ (3 ^ 2)", ascii, "2^3^2");
+        assert_prints("(2 ^ 3) ^ 2", ascii, "(2^3)^2");
+        // 与解析器一致：一元负号比 `^` 结合得更紧
+        assert_prints("-x ^ 2", ascii, "-x^2");
+        assert_prints("-(x ^ 2)", ascii, "-(x^2)");
+        assert_prints("2 ^ -x", ascii, "2^-x");
+        assert_prints("(x + 1)! * 3!", ascii, "(x + 1)! * 3!");
+        assert_prints(
+            "max(1, (2 + 3)) - (x = 4)",
+            ascii,
+            "max(1, 2 + 3) - (x = 4)",
+        );
+        assert_prints("f(x, y) = x * (y + 1)", ascii, "f(x, y) = x * (y + 1)");
+        assert_prints("(5 km + 2 m) to mi", ascii, "5 km + 2 m to mi");
+        // 单位后紧跟 `*` 或 `^` 会被当作单位表达式的一部分，因此需要括号
+        assert_eq!(
+            AstNode::BinaryOp {
+                op: '^',
+                left: Box::new(parse("3 m")),
+                right: Box::new(AstNode::Number(2.0)),
+            }
+            .to_string(),
+            "(3 m)^2"
+        );
+        assert_prints("9.81 m/s^2 * (2 s)", ascii, "9.81 m/s^2 * 2 s");
+        assert_prints("(2 m) * s", ascii, "(2 m) * s");
+    }
+
+    #[test]
+    fn test_unicode_style() {
+        let unicode = PrintStyle::Unicode;
+        assert_prints("x^2 * y^3 / 2", unicode, "x² × y³ ÷ 2");
+        // 与解析器一致：`√` 比 `^` 结合得更紧
+        assert_prints("sqrt(x + 1) * sqrt(2)^2", unicode, "√(x + 1) × √2²");
+        assert_prints("π * r^2", unicode, "π × r²");
+        assert_prints("sin((10 + 20)°)", unicode, "sin((10 + 20)°)");
+        assert_prints("x^4", unicode, "x^4");
+    }
+
+    #[test]
+    fn test_latex_style() {
+        let latex = PrintStyle::Latex;
+        assert_prints("(1 + x) / 2", latex, r"\frac{1 + x}{2}");
+        assert_prints("(1 / 2) ^ 2", latex, r"\left(\frac{1}{2}\right)^{2}");
+        assert_prints("x ^ (y + 1)", latex, r"x^{y + 1}");
+        assert_prints("-x ^ 2", latex, r"\left(-x\right)^{2}");
+        assert_prints(
+            "sin(π * x) + asin(1)",
+            latex,
+            r"\sin\left(\pi \cdot x\right) + \arcsin\left(1\right)",
+        );
+        assert_prints(
+            "abs(x) + gamma(n)!",
+            latex,
+            r"\left|x\right| + \operatorname{gamma}\left(n\right)!",
+        );
+        assert_prints("rate = 3 m/s", latex, r"\mathrm{rate} = 3\,\mathrm{m/s}");
+        assert_prints("90°", latex, r"90^{\circ}");
+    }
+
+    #[test]
+    fn test_display_derived_trees() {
+        use crate::calculator::derivative::derive;
+        use crate::calculator::simplify::simplify;
+
+        let derivative = derive(&parse("x^3 + sin(2 * x)"), "x").unwrap();
+        assert_eq!(derivative.to_string(), "3 * x^2 + 2 * cos(2 * x)");
+        let simplified = simplify(&parse("x * 2 * x - (1 - x)^2 / 4"));
+        assert_eq!(simplified.to_string(), "2 * x^2 - 0.25 * (-x + 1)^2");
+    }
+
+    /// 解析器能够产生的语法树；`constants` 为 false 时不生成常量
+    fn parsed_tree(constants: bool) -> impl Strategy<Value = AstNode> {
+        let number = prop_oneof![
+            (0u32..1000).prop_map(|n| AstNode::Number(n as f64)),
+            (0u32..400).prop_map(|n| AstNode::Number(n as f64 / 8.0)),
+        ];
+        let variable = prop::sample::select(vec!["x", "y", "rate"])
+            .prop_map(|name| AstNode::Variable(name.to_string()));
+        let quantity = (
+            0u32..100,
+            prop::sample::select(vec![("m", 1), ("s", -2), ("deg", 1), ("km", 2)]),
+        )
+            .prop_map(|(n, (name, power))| {
+                let mut unit = UnitExpr::new();
+                unit.push(name, power);
+                AstNode::Quantity {
+                    value: Box::new(AstNode::Number(n as f64)),
+                    unit,
+                }
+            });
+        let constant = prop::sample::select(vec!["pi", "e"])
+            .prop_map(|name| AstNode::Constant(name.to_string()));
+        let leaf = if constants {
+            prop_oneof![number, variable, quantity, constant].boxed()
+        } else {
+            prop_oneof![number, variable, quantity].boxed()
+        };
+
+        leaf.prop_recursive(4, 48, 3, |inner| {
+            prop_oneof![
+                (
+                    inner.clone(),
+                    inner.clone(),
+                    prop::sample::select(vec!['+', '-', '*', '/', '^'])
+                )
+                    .prop_map(|(left, right, op)| AstNode::BinaryOp {
+                        op,
+                        left: Box::new(left),
+                        right: Box::new(right),
+                    }),
+                (inner.clone(), prop::sample::select(vec!['-', '+'])).prop_map(|(operand, op)| {
+                    AstNode::UnaryOp {
+                        op,
+                        operand: Box::new(operand),
+                    }
+                }),
+                (
+                    prop::collection::vec(inner.clone(), 0..3),
+                    prop::sample::select(vec!["max", "sin", "f"])
+                )
+                    .prop_map(|(args, name)| AstNode::FunctionCall {
+                        name: name.to_string(),
+                        args,
+                    }),
+                (
+                    inner.clone(),
+                    prop::sample::select(vec!["sqrt", "factorial"])
+                )
+                    .prop_map(|(arg, name)| AstNode::FunctionCall {
+                        name: name.to_string(),
+                        args: vec![arg],
+                    }),
+                inner.clone().prop_map(|value| {
+                    let mut unit = UnitExpr::new();
+                    unit.push("m", 1);
+                    AstNode::Convert {
+                        value: Box::new(value),
+                        unit,
+                    }
+                }),
+                inner.prop_map(|value| AstNode::Assign {
+                    name: "z".to_string(),
+                    value: Box::new(value),
+                }),
+            ]
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_round_trip_ascii(ast in parsed_tree(false)) {
+            let text = ast.to_string();
+            prop_assert_eq!(parse(&text), ast, "{}", text);
+        }
+
+        #[test]
+        fn prop_round_trip_unicode(ast in parsed_tree(true)) {
+            let text = ast.format(PrintStyle::Unicode);
+            prop_assert_eq!(parse(&text), ast, "{}", text);
+        }
+    }
+}
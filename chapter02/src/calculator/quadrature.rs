@@ -0,0 +1,274 @@
+// calculator/quadrature.rs - 数值积分：自适应 Gauss–Kronrod 与自适应 Simpson 方法
+
+use std::collections::BinaryHeap;
+
+use crate::utils::Error;
+
+/// 积分的相对误差目标
+pub const RELATIVE_TOLERANCE: f64 = 1e-10;
+
+/// 积分的绝对误差目标（积分值接近 0 时使用）
+pub const ABSOLUTE_TOLERANCE: f64 = 1e-12;
+
+/// 最多把积分区间细分的次数
+pub const MAX_SUBDIVISIONS: usize = 1000;
+
+/// 15 点 Kronrod 节点（正半轴，最后一个是区间中点）
+const KRONROD_NODES: [f64; 8] = [
+    0.991_455_371_120_812_6,
+    0.949_107_912_342_758_5,
+    0.864_864_423_359_769_1,
+    0.741_531_185_599_394_4,
+    0.586_087_235_467_691_1,
+    0.405_845_151_377_397_2,
+    0.207_784_955_007_898_5,
+    0.0,
+];
+
+/// 15 点 Kronrod 权重
+const KRONROD_WEIGHTS: [f64; 8] = [
+    0.022_935_322_010_529_225,
+    0.063_092_092_629_978_55,
+    0.104_790_010_322_250_18,
+    0.140_653_259_715_525_92,
+    0.169_004_726_639_267_9,
+    0.190_350_578_064_785_4,
+    0.204_432_940_075_298_9,
+    0.209_482_141_084_727_83,
+];
+
+/// 7 点 Gauss 权重，对应 Kronrod 节点中下标为奇数的节点
+const GAUSS_WEIGHTS: [f64; 4] = [
+    0.129_484_966_168_869_7,
+    0.279_705_391_489_276_7,
+    0.381_830_050_505_118_9,
+    0.417_959_183_673_469_4,
+];
+
+/// 积分结果及其误差估计
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Integral {
+    pub value: f64,
+    /// 绝对误差的估计值
+    pub error: f64,
+}
+
+fn divergent() -> Error {
+    Error::Divergent("integrate".to_string())
+}
+
+/// 被积函数的值必须是有限的
+fn finite<F>(f: &F, x: f64) -> Result<f64, Error>
+where
+    F: Fn(f64) -> Result<f64, Error>,
+{
+    let y = f(x)?;
+    if y.is_finite() {
+        Ok(y)
+    } else {
+        Err(divergent())
+    }
+}
+
+fn accurate_enough(value: f64, error: f64) -> bool {
+    error <= ABSOLUTE_TOLERANCE.max(RELATIVE_TOLERANCE * value.abs())
+}
+
+/// 一段子区间上的 Gauss–Kronrod 结果，按误差排序
+struct Segment {
+    a: f64,
+    b: f64,
+    value: f64,
+    error: f64,
+}
+
+impl PartialEq for Segment {
+    fn eq(&self, other: &Self) -> bool {
+        self.error == other.error
+    }
+}
+
+impl Eq for Segment {}
+
+impl PartialOrd for Segment {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Segment {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.error.total_cmp(&other.error)
+    }
+}
+
+/// 在 [a, b] 上应用 G7–K15 规则，误差估计为两者之差
+fn kronrod_segment<F>(f: &F, a: f64, b: f64) -> Result<Segment, Error>
+where
+    F: Fn(f64) -> Result<f64, Error>,
+{
+    let center = 0.5 * (a + b);
+    let half = 0.5 * (b - a);
+    let (mut kronrod, mut gauss) = (0.0, 0.0);
+    for (i, (&node, &weight)) in KRONROD_NODES.iter().zip(&KRONROD_WEIGHTS).enumerate() {
+        let sum = if node == 0.0 {
+            finite(f, center)?
+        } else {
+            finite(f, center - half * node)? + finite(f, center + half * node)?
+        };
+        kronrod += weight * sum;
+        if i % 2 == 1 {
+            gauss += GAUSS_WEIGHTS[i / 2] * sum;
+        }
+    }
+    Ok(Segment {
+        a,
+        b,
+        value: kronrod * half,
+        error: ((kronrod - gauss) * half).abs(),
+    })
+}
+
+/// 自适应 Gauss–Kronrod 积分
+///
+/// 每次把误差估计最大的子区间一分为二，直到总误差满足 `RELATIVE_TOLERANCE`。
+/// 节点不包含区间端点，因此端点处的可积奇点（如 `1/sqrt(x)` 在 0 处）也能处理。
+/// 细分超过 `MAX_SUBDIVISIONS` 次（通常是积分发散）时返回 `Error::NoConvergence`。
+pub fn gauss_kronrod<F>(f: F, a: f64, b: f64) -> Result<Integral, Error>
+where
+    F: Fn(f64) -> Result<f64, Error>,
+{
+    if a == b {
+        return Ok(Integral {
+            value: 0.0,
+            error: 0.0,
+        });
+    }
+
+    let mut segments = BinaryHeap::from([kronrod_segment(&f, a, b)?]);
+    for subdivisions in 0..=MAX_SUBDIVISIONS {
+        let value: f64 = segments.iter().map(|s| s.value).sum();
+        let error: f64 = segments.iter().map(|s| s.error).sum();
+        if !value.is_finite() {
+            return Err(divergent());
+        }
+        if accurate_enough(value, error) {
+            return Ok(Integral { value, error });
+        }
+        if subdivisions == MAX_SUBDIVISIONS {
+            break;
+        }
+
+        let worst = segments.pop().expect("至少有一个子区间");
+        let mid = 0.5 * (worst.a + worst.b);
+        segments.push(kronrod_segment(&f, worst.a, mid)?);
+        segments.push(kronrod_segment(&f, mid, worst.b)?);
+    }
+    Err(Error::NoConvergence {
+        method: "Gauss–Kronrod 积分".to_string(),
+        iterations: MAX_SUBDIVISIONS,
+    })
+}
+
+/// 自适应 Simpson 积分
+///
+/// 子区间上 Simpson 公式与两半之和的差用于估计误差，并做 Richardson 外推。
+/// 需要在区间端点求值，端点处有奇点时请使用 [`gauss_kronrod`]。
+pub fn adaptive_simpson<F>(f: F, a: f64, b: f64) -> Result<Integral, Error>
+where
+    F: Fn(f64) -> Result<f64, Error>,
+{
+    let simpson = |a: f64, b: f64, fa: f64, fm: f64, fb: f64| (b - a) / 6.0 * (fa + 4.0 * fm + fb);
+
+    let (fa, fm, fb) = (finite(&f, a)?, finite(&f, 0.5 * (a + b))?, finite(&f, b)?);
+    let whole = simpson(a, b, fa, fm, fb);
+    let tolerance = ABSOLUTE_TOLERANCE.max(RELATIVE_TOLERANCE * whole.abs());
+    // 待处理的子区间：(a, b, f(a), f(中点), f(b), 该段的 Simpson 值, 该段的误差目标)
+    let mut stack = vec![(a, b, fa, fm, fb, whole, tolerance)];
+    let (mut value, mut error) = (0.0, 0.0);
+    let mut subdivisions = 0;
+
+    while let Some((a, b, fa, fm, fb, whole, tolerance)) = stack.pop() {
+        let m = 0.5 * (a + b);
+        let (lm, rm) = (0.5 * (a + m), 0.5 * (m + b));
+        let (flm, frm) = (finite(&f, lm)?, finite(&f, rm)?);
+        let left = simpson(a, m, fa, flm, fm);
+        let right = simpson(m, b, fm, frm, fb);
+        let delta = left + right - whole;
+
+        if delta.abs() <= 15.0 * tolerance {
+            value += left + right + delta / 15.0;
+            error += delta.abs() / 15.0;
+            continue;
+        }
+        subdivisions += 1;
+        if subdivisions > MAX_SUBDIVISIONS {
+            return Err(Error::NoConvergence {
+                method: "自适应 Simpson 积分".to_string(),
+                iterations: MAX_SUBDIVISIONS,
+            });
+        }
+        stack.push((a, m, fa, flm, fm, left, tolerance / 2.0));
+        stack.push((m, b, fm, frm, fb, right, tolerance / 2.0));
+    }
+    Ok(Integral { value, error })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() <= 1e-9 * b.abs().max(1.0)
+    }
+
+    #[test]
+    fn test_gauss_kronrod() {
+        let integral = gauss_kronrod(|x: f64| Ok(x.sin()), 0.0, PI).unwrap();
+        assert!(close(integral.value, 2.0));
+        assert!(integral.error < 1e-9);
+
+        // 多项式由单个 15 点规则精确积出
+        assert!(close(
+            gauss_kronrod(|x: f64| Ok(x.powi(5)), -1.0, 2.0)
+                .unwrap()
+                .value,
+            10.5
+        ));
+        // 端点处的可积奇点，以及交换上下限
+        assert!(close(
+            gauss_kronrod(|x: f64| Ok(1.0 / x.sqrt()), 0.0, 1.0)
+                .unwrap()
+                .value,
+            2.0
+        ));
+        assert!(close(
+            gauss_kronrod(|x: f64| Ok(x), 1.0, 0.0).unwrap().value,
+            -0.5
+        ));
+        assert_eq!(gauss_kronrod(|x: f64| Ok(x), 3.0, 3.0).unwrap().value, 0.0);
+
+        // ∫₀¹ 1/x dx 发散
+        assert!(matches!(
+            gauss_kronrod(|x: f64| Ok(1.0 / x), 0.0, 1.0),
+            Err(Error::NoConvergence { .. }) | Err(Error::Divergent(_))
+        ));
+    }
+
+    #[test]
+    fn test_adaptive_simpson() {
+        let integral = adaptive_simpson(|x: f64| Ok((-x * x).exp()), -3.0, 3.0).unwrap();
+        assert!((integral.value - PI.sqrt()).abs() < 1e-4);
+        assert!(close(
+            adaptive_simpson(|x: f64| Ok(x.cos()), 0.0, PI / 2.0)
+                .unwrap()
+                .value,
+            1.0
+        ));
+        assert_eq!(
+            adaptive_simpson(|x: f64| Ok(1.0 / x), 0.0, 1.0),
+            Err(Error::Divergent("integrate".to_string()))
+        );
+    }
+}
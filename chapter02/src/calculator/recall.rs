@@ -0,0 +1,151 @@
+// calculator/recall.rs - 引用历史结果：表达式中的 `ans`、`_` 和 `$n`
+
+use std::fmt;
+
+use num_bigint::BigInt;
+
+use super::parser::AstNode;
+use super::value::Value;
+use crate::utils::Error;
+
+/// 最近一次结果的名称
+pub const LAST_RESULT_NAMES: [&str; 2] = ["ans", "_"];
+
+/// 对历史结果的引用
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultRef {
+    /// `ans` 或 `_`：最近一次成功的结果
+    Last,
+    /// `$n`：编号为 n 的历史记录
+    Index(usize),
+}
+
+impl ResultRef {
+    /// 识别变量名 `ans`、`_` 和 `$n`，其他名称返回 None
+    pub fn parse(name: &str) -> Option<Self> {
+        if LAST_RESULT_NAMES.contains(&name) {
+            return Some(ResultRef::Last);
+        }
+        let digits = name.strip_prefix('$')?;
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        // 位数过多的编号不可能存在，按 usize::MAX 处理以便报告“记录不存在”
+        Some(ResultRef::Index(digits.parse().unwrap_or(usize::MAX)))
+    }
+}
+
+impl fmt::Display for ResultRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResultRef::Last => write!(f, "ans"),
+            ResultRef::Index(n) => write!(f, "${}", n),
+        }
+    }
+}
+
+/// 把语法树中的历史引用替换为 `recall` 给出的数值
+///
+/// 精确值替换为整数字面量或两个整数字面量的商，因此在有理数和十进制模式下不会损失精度。
+/// 函数定义的函数体不做替换；给历史引用赋值（如 `ans = 1`）是错误。
+pub fn resolve<F>(ast: &AstNode, recall: &F) -> Result<AstNode, Error>
+where
+    F: Fn(ResultRef) -> Result<Value, Error>,
+{
+    match ast {
+        AstNode::Variable(name) => match ResultRef::parse(name) {
+            Some(reference) => recall(reference).map(|value| value_node(&value)),
+            None => Ok(ast.clone()),
+        },
+        AstNode::Assign { name, .. } if ResultRef::parse(name).is_some() => {
+            Err(Error::Other(format!("不能给历史结果 {} 赋值", name)))
+        }
+        _ => ast.map_children(|child| resolve(child, recall)),
+    }
+}
+
+/// 数值对应的语法树节点：有理数（包括十进制数的精确值）写成整数字面量的商，复数写成实部加虚部
+fn value_node(value: &Value) -> AstNode {
+    let rational = match value {
+        Value::Decimal(d) => d.to_rational(),
+        Value::Complex(z) => {
+            return AstNode::BinaryOp {
+                op: '+',
+                left: Box::new(AstNode::Number(z.re)),
+                right: Box::new(AstNode::Imaginary(z.im)),
+            }
+        }
+        value => value.to_rational(),
+    };
+    match rational {
+        Some(r) if r.is_integer() => integer_node(r.numer()),
+        Some(r) => AstNode::BinaryOp {
+            op: '/',
+            left: Box::new(integer_node(r.numer())),
+            right: Box::new(integer_node(r.denom())),
+        },
+        None => AstNode::Number(value.to_f64()),
+    }
+}
+
+fn integer_node(n: &BigInt) -> AstNode {
+    AstNode::Literal(n.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculator::parser::ExpressionParser;
+    use num_rational::BigRational;
+
+    fn parse(expression: &str) -> AstNode {
+        let parser = ExpressionParser::new();
+        parser.parse(parser.tokenize(expression).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_reference() {
+        assert_eq!(ResultRef::parse("ans"), Some(ResultRef::Last));
+        assert_eq!(ResultRef::parse("_"), Some(ResultRef::Last));
+        assert_eq!(ResultRef::parse("$12"), Some(ResultRef::Index(12)));
+        assert_eq!(ResultRef::parse("$"), None);
+        assert_eq!(ResultRef::parse("answer"), None);
+        assert_eq!(ResultRef::Index(3).to_string(), "$3");
+    }
+
+    #[test]
+    fn test_resolve() {
+        let recall = |reference| match reference {
+            ResultRef::Last => Ok(Value::Float(10.0)),
+            ResultRef::Index(n) => Ok(Value::Float(n as f64)),
+        };
+        assert_eq!(
+            resolve(&parse("ans + $2 * _"), &recall).unwrap(),
+            parse("10 + 2 * 10")
+        );
+        assert_eq!(resolve(&parse("x + 1"), &recall).unwrap(), parse("x + 1"));
+        assert!(resolve(&parse("ans = 1"), &recall).is_err());
+    }
+
+    #[test]
+    fn test_resolve_exact() {
+        let third = BigRational::new(BigInt::from(-1), BigInt::from(3));
+        let recall = |_| Ok(Value::Rational(third.clone()));
+        assert_eq!(
+            resolve(&parse("ans * 3"), &recall).unwrap(),
+            AstNode::BinaryOp {
+                op: '*',
+                left: Box::new(AstNode::BinaryOp {
+                    op: '/',
+                    left: Box::new(AstNode::Literal("-1".to_string())),
+                    right: Box::new(AstNode::Literal("3".to_string())),
+                }),
+                right: Box::new(AstNode::Number(3.0)),
+            }
+        );
+        assert_eq!(
+            value_node(&Value::Integer(BigInt::from(7))),
+            AstNode::Literal("7".to_string())
+        );
+    }
+}
@@ -0,0 +1,642 @@
+// calculator/registry.rs - 内置函数注册表
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+
+use super::integer::{self, integer_arg, natural_arg};
+use super::special;
+use crate::utils::Error;
+
+/// 内置函数的实现：接收已求值的参数，返回结果
+pub type NativeFn = Arc<dyn Fn(&[f64]) -> Result<f64, Error> + Send + Sync>;
+
+/// 函数的参数个数约束
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    /// 固定个数
+    Fixed(usize),
+    /// 闭区间 [min, max]
+    Range(usize, usize),
+    /// 变参：至少 min 个
+    Variadic(usize),
+}
+
+impl Arity {
+    /// 判断参数个数是否满足约束
+    pub fn accepts(&self, count: usize) -> bool {
+        match *self {
+            Arity::Fixed(n) => count == n,
+            Arity::Range(min, max) => (min..=max).contains(&count),
+            Arity::Variadic(min) => count >= min,
+        }
+    }
+
+    /// 检查参数个数，错误信息中包含函数签名
+    pub fn check(&self, signature: &str, actual: usize) -> Result<(), Error> {
+        if self.accepts(actual) {
+            return Ok(());
+        }
+
+        Err(match *self {
+            Arity::Fixed(expected) => Error::InvalidArgumentCount {
+                function: signature.to_string(),
+                expected,
+                actual,
+            },
+            Arity::Range(min, max) => Error::InvalidArgumentRange {
+                function: signature.to_string(),
+                min,
+                max: Some(max),
+                actual,
+            },
+            Arity::Variadic(min) => Error::InvalidArgumentRange {
+                function: signature.to_string(),
+                min,
+                max: None,
+                actual,
+            },
+        })
+    }
+}
+
+/// 注册表中的一个函数
+#[derive(Clone)]
+pub struct BuiltinFunction {
+    /// 函数名（小写）
+    pub name: String,
+    /// 签名，如 `log(value, base)`
+    pub signature: String,
+    /// 参数个数约束
+    pub arity: Arity,
+    /// 说明文字
+    pub doc: String,
+    func: NativeFn,
+    /// 是否是 `with_defaults` 注册的默认实现
+    default: bool,
+}
+
+impl BuiltinFunction {
+    /// 是否是默认实现；只有默认实现会在精确、十进制和复数模式下换用对应的高精度实现，
+    /// 用户注册的同名函数总是按浮点数调用
+    pub fn is_default(&self) -> bool {
+        self.default
+    }
+
+    /// 检查参数个数并调用函数
+    pub fn call(&self, args: &[f64]) -> Result<f64, Error> {
+        self.arity.check(&self.signature, args.len())?;
+        (self.func)(args)
+    }
+}
+
+impl fmt::Debug for BuiltinFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BuiltinFunction")
+            .field("signature", &self.signature)
+            .field("arity", &self.arity)
+            .field("doc", &self.doc)
+            .finish()
+    }
+}
+
+/// 函数注册表
+///
+/// 保存表达式中可调用的内置函数。`FunctionRegistry::with_defaults()` 提供默认的
+/// 函数集合，也可以在此基础上注册领域函数：
+///
+/// ```
+/// use chapter02::calculator::registry::{Arity, FunctionRegistry};
+/// use chapter02::Calculator;
+///
+/// let mut registry = FunctionRegistry::with_defaults();
+/// registry.register("clamp(x, lo, hi)", Arity::Fixed(3), "将 x 限制在 [lo, hi] 内", |args| {
+///     Ok(args[0].max(args[1]).min(args[2]))
+/// });
+///
+/// let calc = Calculator::with_registry(registry);
+/// assert_eq!(calc.evaluate_expression("clamp(15, 0, 10)").unwrap(), 10.0);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FunctionRegistry {
+    functions: HashMap<String, BuiltinFunction>,
+}
+
+impl FunctionRegistry {
+    /// 创建空的注册表
+    pub fn new() -> Self {
+        Self {
+            functions: HashMap::new(),
+        }
+    }
+
+    /// 创建包含默认内置函数的注册表
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+        register_defaults(&mut registry);
+        for function in registry.functions.values_mut() {
+            function.default = true;
+        }
+        registry
+    }
+
+    /// 注册函数（同名函数会被替换）
+    ///
+    /// `signature` 形如 `clamp(x, lo, hi)`，括号前的部分作为函数名；
+    /// 函数名不区分大小写。
+    pub fn register<F>(&mut self, signature: &str, arity: Arity, doc: &str, func: F)
+    where
+        F: Fn(&[f64]) -> Result<f64, Error> + Send + Sync + 'static,
+    {
+        let name = signature
+            .split('(')
+            .next()
+            .unwrap_or(signature)
+            .trim()
+            .to_lowercase();
+
+        self.functions.insert(
+            name.clone(),
+            BuiltinFunction {
+                name,
+                signature: signature.to_string(),
+                arity,
+                doc: doc.to_string(),
+                func: Arc::new(func),
+                default: false,
+            },
+        );
+    }
+
+    /// 删除函数
+    pub fn unregister(&mut self, name: &str) -> Option<BuiltinFunction> {
+        self.functions.remove(&name.to_lowercase())
+    }
+
+    /// 查找函数
+    pub fn get(&self, name: &str) -> Option<&BuiltinFunction> {
+        self.functions.get(&name.to_lowercase())
+    }
+
+    /// 判断函数是否已注册
+    pub fn contains(&self, name: &str) -> bool {
+        self.functions.contains_key(&name.to_lowercase())
+    }
+
+    /// 调用函数
+    pub fn call(&self, name: &str, args: &[f64]) -> Result<f64, Error> {
+        match self.get(name) {
+            Some(function) => function.call(args),
+            None => Err(Error::UndefinedFunction(name.to_string())),
+        }
+    }
+
+    /// 按名称排序列出所有函数
+    pub fn functions(&self) -> Vec<&BuiltinFunction> {
+        let mut functions: Vec<&BuiltinFunction> = self.functions.values().collect();
+        functions.sort_by(|a, b| a.name.cmp(&b.name));
+        functions
+    }
+
+    /// 已注册函数的数量
+    pub fn len(&self) -> usize {
+        self.functions.len()
+    }
+
+    /// 判断注册表是否为空
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+}
+
+/// 注册默认内置函数
+fn register_defaults(registry: &mut FunctionRegistry) {
+    registry.register("sin(x)", Arity::Fixed(1), "正弦（弧度）", |args| {
+        Ok(args[0].sin())
+    });
+    registry.register("cos(x)", Arity::Fixed(1), "余弦（弧度）", |args| {
+        Ok(args[0].cos())
+    });
+    registry.register("tan(x)", Arity::Fixed(1), "正切（弧度）", |args| {
+        Ok(args[0].tan())
+    });
+    registry.register("asin(x)", Arity::Fixed(1), "反正弦", |args| {
+        inverse_trig("asin", args[0], f64::asin)
+    });
+    registry.register("acos(x)", Arity::Fixed(1), "反余弦", |args| {
+        inverse_trig("acos", args[0], f64::acos)
+    });
+    registry.register("atan(x)", Arity::Fixed(1), "反正切", |args| {
+        Ok(args[0].atan())
+    });
+    registry.register(
+        "atan2(y, x)",
+        Arity::Fixed(2),
+        "点 (x, y) 的辐角，范围 (-π, π]",
+        |args| {
+            let (y, x) = (args[0], args[1]);
+            if x == 0.0 && y == 0.0 {
+                return Err(domain_error("atan2", "原点的辐角无定义"));
+            }
+            Ok(y.atan2(x))
+        },
+    );
+    registry.register("sqrt(x)", Arity::Fixed(1), "平方根", |args| {
+        if args[0] < 0.0 {
+            return Err(Error::NegativeSquareRoot);
+        }
+        Ok(args[0].sqrt())
+    });
+    registry.register("ln(x)", Arity::Fixed(1), "自然对数", |args| {
+        if args[0] <= 0.0 {
+            return Err(Error::InvalidLogarithm);
+        }
+        Ok(args[0].ln())
+    });
+    registry.register("exp(x)", Arity::Fixed(1), "自然指数 e^x", |args| {
+        Ok(args[0].exp())
+    });
+    registry.register(
+        "log10(x)",
+        Arity::Fixed(1),
+        "常用对数（以 10 为底）",
+        |args| {
+            if args[0] <= 0.0 {
+                return Err(Error::InvalidLogarithm);
+            }
+            Ok(args[0].log10())
+        },
+    );
+    registry.register("log2(x)", Arity::Fixed(1), "以 2 为底的对数", |args| {
+        if args[0] <= 0.0 {
+            return Err(Error::InvalidLogarithm);
+        }
+        Ok(args[0].log2())
+    });
+    registry.register(
+        "log(value, base)",
+        Arity::Fixed(2),
+        "以 base 为底的对数",
+        |args| {
+            let (value, base) = (args[0], args[1]);
+            if value <= 0.0 || base <= 0.0 || base == 1.0 {
+                return Err(Error::InvalidLogarithm);
+            }
+            Ok(value.log(base))
+        },
+    );
+    registry.register("cbrt(x)", Arity::Fixed(1), "立方根", |args| {
+        Ok(args[0].cbrt())
+    });
+    registry.register("root(x, n)", Arity::Fixed(2), "n 次方根", |args| {
+        nth_root(args[0], args[1])
+    });
+    registry.register(
+        "hypot(x, y)",
+        Arity::Fixed(2),
+        "直角三角形的斜边 √(x² + y²)",
+        |args| Ok(args[0].hypot(args[1])),
+    );
+    registry.register("abs(x)", Arity::Fixed(1), "绝对值", |args| {
+        Ok(args[0].abs())
+    });
+    registry.register("sign(x)", Arity::Fixed(1), "符号：-1、0 或 1", |args| {
+        Ok(if args[0] == 0.0 {
+            0.0
+        } else {
+            args[0].signum()
+        })
+    });
+    registry.register("ceil(x)", Arity::Fixed(1), "向上取整", |args| {
+        Ok(args[0].ceil())
+    });
+    registry.register("floor(x)", Arity::Fixed(1), "向下取整", |args| {
+        Ok(args[0].floor())
+    });
+    registry.register("round(x)", Arity::Fixed(1), "四舍五入", |args| {
+        Ok(args[0].round())
+    });
+    registry.register("trunc(x)", Arity::Fixed(1), "向零取整", |args| {
+        Ok(args[0].trunc())
+    });
+    registry.register("max(x, ...)", Arity::Variadic(1), "最大值", |args| {
+        Ok(args.iter().copied().fold(f64::NEG_INFINITY, f64::max))
+    });
+    registry.register("min(x, ...)", Arity::Variadic(1), "最小值", |args| {
+        Ok(args.iter().copied().fold(f64::INFINITY, f64::min))
+    });
+    register_hyperbolic_functions(registry);
+    register_special_functions(registry);
+    register_integer_functions(registry);
+}
+
+/// 定义域错误
+fn domain_error(function: &str, reason: &str) -> Error {
+    Error::DomainError {
+        function: function.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+/// 反正弦、反余弦：参数必须在 [-1, 1] 内
+pub(crate) fn inverse_trig(name: &str, x: f64, f: fn(f64) -> f64) -> Result<f64, Error> {
+    if !(-1.0..=1.0).contains(&x) {
+        return Err(domain_error(name, "参数必须在 [-1, 1] 内"));
+    }
+    Ok(f(x))
+}
+
+/// n 次方根：负数只能开奇数次方根
+fn nth_root(x: f64, n: f64) -> Result<f64, Error> {
+    if n == 0.0 {
+        return Err(domain_error("root", "根指数不能为 0"));
+    }
+    let odd = n.fract() == 0.0 && n % 2.0 != 0.0;
+    if x < 0.0 && !odd {
+        return Err(domain_error("root", "负数只能开奇数次方根"));
+    }
+
+    let root = x.abs().powf(n.recip()).copysign(x);
+    // 结果是整数时消除 powf 的舍入误差，如 root(27, 3) = 3
+    let rounded = root.round();
+    Ok(if rounded.powf(n) == x { rounded } else { root })
+}
+
+/// 注册双曲函数及其反函数
+fn register_hyperbolic_functions(registry: &mut FunctionRegistry) {
+    registry.register("sinh(x)", Arity::Fixed(1), "双曲正弦", |args| {
+        Ok(args[0].sinh())
+    });
+    registry.register("cosh(x)", Arity::Fixed(1), "双曲余弦", |args| {
+        Ok(args[0].cosh())
+    });
+    registry.register("tanh(x)", Arity::Fixed(1), "双曲正切", |args| {
+        Ok(args[0].tanh())
+    });
+    registry.register("asinh(x)", Arity::Fixed(1), "反双曲正弦", |args| {
+        Ok(args[0].asinh())
+    });
+    registry.register("acosh(x)", Arity::Fixed(1), "反双曲余弦", |args| {
+        if args[0] < 1.0 {
+            return Err(domain_error("acosh", "参数必须 ≥ 1"));
+        }
+        Ok(args[0].acosh())
+    });
+    registry.register("atanh(x)", Arity::Fixed(1), "反双曲正切", |args| {
+        if !(args[0] > -1.0 && args[0] < 1.0) {
+            return Err(domain_error("atanh", "参数必须在 (-1, 1) 内"));
+        }
+        Ok(args[0].atanh())
+    });
+}
+
+/// 注册伽马函数、双伽马函数和误差函数
+fn register_special_functions(registry: &mut FunctionRegistry) {
+    registry.register("gamma(x)", Arity::Fixed(1), "伽马函数 Γ(x)", |args| {
+        let x = args[0];
+        if x <= 0.0 && x.fract() == 0.0 {
+            return Err(domain_error("gamma", "非正整数是伽马函数的极点"));
+        }
+        match special::gamma(x) {
+            y if y.is_infinite() => Err(Error::ResultOverflow("gamma".to_string())),
+            y => Ok(y),
+        }
+    });
+    registry.register("lgamma(x)", Arity::Fixed(1), "ln|Γ(x)|", |args| {
+        let x = args[0];
+        if x <= 0.0 && x.fract() == 0.0 {
+            return Err(domain_error("lgamma", "非正整数是伽马函数的极点"));
+        }
+        Ok(special::lgamma(x))
+    });
+    registry.register(
+        "digamma(x)",
+        Arity::Fixed(1),
+        "双伽马函数 ψ(x) = Γ'(x)/Γ(x)",
+        |args| {
+            let x = args[0];
+            if x <= 0.0 && x.fract() == 0.0 {
+                return Err(domain_error("digamma", "非正整数是双伽马函数的极点"));
+            }
+            Ok(special::digamma(x))
+        },
+    );
+    registry.register("erf(x)", Arity::Fixed(1), "误差函数", |args| {
+        Ok(special::erf(args[0]))
+    });
+}
+
+/// 注册整数函数；浮点数模式下先按整数精确计算，再转换为 f64
+fn register_integer_functions(registry: &mut FunctionRegistry) {
+    registry.register("factorial(n)", Arity::Fixed(1), "阶乘 n!", |args| {
+        to_finite(
+            "factorial",
+            integer::factorial(natural_arg("factorial", args[0])?),
+        )
+    });
+    registry.register("ncr(n, r)", Arity::Fixed(2), "组合数", |args| {
+        let (n, r) = (natural_arg("ncr", args[0])?, natural_arg("ncr", args[1])?);
+        to_finite("ncr", integer::combinations(n, r))
+    });
+    registry.register("npr(n, r)", Arity::Fixed(2), "排列数", |args| {
+        let (n, r) = (natural_arg("npr", args[0])?, natural_arg("npr", args[1])?);
+        to_finite("npr", integer::permutations(n, r))
+    });
+    registry.register("gcd(a, b)", Arity::Fixed(2), "最大公约数", |args| {
+        let (a, b) = (integer_arg("gcd", args[0])?, integer_arg("gcd", args[1])?);
+        to_finite("gcd", Ok(integer::gcd(&a, &b)))
+    });
+    registry.register("lcm(a, b)", Arity::Fixed(2), "最小公倍数", |args| {
+        let (a, b) = (integer_arg("lcm", args[0])?, integer_arg("lcm", args[1])?);
+        to_finite("lcm", Ok(integer::lcm(&a, &b)))
+    });
+    // 取模和整除对非整数也有意义，按向下取整定义
+    registry.register(
+        "mod(a, b)",
+        Arity::Fixed(2),
+        "取模（结果与 b 同号）",
+        |args| {
+            let (a, b) = (args[0], args[1]);
+            if b == 0.0 {
+                return Err(Error::DivisionByZero);
+            }
+            Ok(a - b * (a / b).floor())
+        },
+    );
+    registry.register(
+        "idiv(a, b)",
+        Arity::Fixed(2),
+        "整除（向下取整）",
+        |args| {
+            let (a, b) = (args[0], args[1]);
+            if b == 0.0 {
+                return Err(Error::DivisionByZero);
+            }
+            Ok((a / b).floor())
+        },
+    );
+}
+
+/// 整数结果转换为 f64，超出 f64 范围时报告是哪个函数的结果过大
+fn to_finite(function: &str, result: Result<BigInt, Error>) -> Result<f64, Error> {
+    match result?.to_f64() {
+        Some(n) if n.is_finite() => Ok(n),
+        _ => Err(integer::too_large(function)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arity_accepts() {
+        assert!(Arity::Fixed(2).accepts(2));
+        assert!(!Arity::Fixed(2).accepts(3));
+        assert!(Arity::Range(1, 2).accepts(1));
+        assert!(!Arity::Range(1, 2).accepts(0));
+        assert!(Arity::Variadic(1).accepts(10));
+        assert!(!Arity::Variadic(1).accepts(0));
+    }
+
+    #[test]
+    fn test_defaults() {
+        let registry = FunctionRegistry::with_defaults();
+        assert_eq!(registry.call("sqrt", &[16.0]).unwrap(), 4.0);
+        assert_eq!(registry.call("LOG", &[8.0, 2.0]).unwrap(), 3.0);
+        assert_eq!(registry.call("max", &[1.0, 5.0, 3.0]).unwrap(), 5.0);
+        assert_eq!(
+            registry.call("sqrt", &[-1.0]),
+            Err(Error::NegativeSquareRoot)
+        );
+    }
+
+    #[test]
+    fn test_integer_functions() {
+        let registry = FunctionRegistry::with_defaults();
+        assert_eq!(registry.call("factorial", &[5.0]).unwrap(), 120.0);
+        assert_eq!(registry.call("nCr", &[5.0, 2.0]).unwrap(), 10.0);
+        assert_eq!(registry.call("mod", &[-7.0, 3.0]).unwrap(), 2.0);
+        assert_eq!(registry.call("idiv", &[7.0, 2.0]).unwrap(), 3.0);
+        assert_eq!(
+            registry.call("gcd", &[4.5, 3.0]),
+            Err(Error::InvalidIntegerArgument("gcd".to_string()))
+        );
+        assert_eq!(
+            registry.call("factorial", &[-1.0]),
+            Err(Error::InvalidFactorialArgument)
+        );
+        assert_eq!(
+            registry.call("factorial", &[171.0]),
+            Err(Error::FactorialTooLarge)
+        );
+        // 其他整数函数的结果过大时报告函数名
+        let overflow = |function: &str| Err(Error::ResultOverflow(function.to_string()));
+        assert_eq!(registry.call("ncr", &[2000.0, 1000.0]), overflow("ncr"));
+        assert_eq!(registry.call("npr", &[200.0, 200.0]), overflow("npr"));
+        assert_eq!(
+            registry.call("lcm", &[2f64.powi(1000), 3f64.powi(20)]),
+            overflow("lcm")
+        );
+        assert_eq!(
+            registry
+                .call("ncr", &[2000.0, 1000.0])
+                .unwrap_err()
+                .to_string(),
+            "ncr 的结果过大: 超出可表示的范围"
+        );
+    }
+
+    #[test]
+    fn test_scientific_functions() {
+        let registry = FunctionRegistry::with_defaults();
+        assert_eq!(registry.call("log10", &[1000.0]).unwrap(), 3.0);
+        assert_eq!(registry.call("log2", &[8.0]).unwrap(), 3.0);
+        assert_eq!(registry.call("cbrt", &[-27.0]).unwrap(), -3.0);
+        assert_eq!(registry.call("root", &[27.0, 3.0]).unwrap(), 3.0);
+        assert_eq!(registry.call("root", &[-32.0, 5.0]).unwrap(), -2.0);
+        assert_eq!(registry.call("hypot", &[3.0, 4.0]).unwrap(), 5.0);
+        assert_eq!(registry.call("sign", &[-2.5]).unwrap(), -1.0);
+        assert_eq!(registry.call("sign", &[0.0]).unwrap(), 0.0);
+        assert_eq!(registry.call("trunc", &[-2.7]).unwrap(), -2.0);
+        assert_eq!(registry.call("gamma", &[5.0]).unwrap(), 24.0);
+        assert_eq!(registry.call("tanh", &[0.0]).unwrap(), 0.0);
+        assert_eq!(
+            registry.call("atan2", &[1.0, -1.0]).unwrap(),
+            3.0 * std::f64::consts::FRAC_PI_4
+        );
+    }
+
+    #[test]
+    fn test_domain_errors() {
+        let registry = FunctionRegistry::with_defaults();
+        let domain = |function: &str, reason: &str| {
+            Err(Error::DomainError {
+                function: function.to_string(),
+                reason: reason.to_string(),
+            })
+        };
+        assert_eq!(
+            registry.call("acosh", &[0.5]),
+            domain("acosh", "参数必须 ≥ 1")
+        );
+        assert_eq!(
+            registry.call("atanh", &[1.0]),
+            domain("atanh", "参数必须在 (-1, 1) 内")
+        );
+        assert_eq!(
+            registry.call("asin", &[2.0]),
+            domain("asin", "参数必须在 [-1, 1] 内")
+        );
+        assert_eq!(
+            registry.call("root", &[-16.0, 4.0]),
+            domain("root", "负数只能开奇数次方根")
+        );
+        assert_eq!(
+            registry.call("gamma", &[-2.0]),
+            domain("gamma", "非正整数是伽马函数的极点")
+        );
+        assert_eq!(
+            registry.call("atan2", &[0.0, 0.0]),
+            domain("atan2", "原点的辐角无定义")
+        );
+        assert_eq!(registry.call("log2", &[0.0]), Err(Error::InvalidLogarithm));
+        assert_eq!(
+            registry.call("gamma", &[200.0]),
+            Err(Error::ResultOverflow("gamma".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_register_custom_function() {
+        let mut registry = FunctionRegistry::new();
+        registry.register("lerp(a, b, t)", Arity::Fixed(3), "线性插值", |args| {
+            Ok(args[0] + (args[1] - args[0]) * args[2])
+        });
+
+        let lerp = registry.get("lerp").unwrap();
+        assert_eq!(lerp.signature, "lerp(a, b, t)");
+        assert_eq!(lerp.doc, "线性插值");
+        assert_eq!(registry.call("lerp", &[0.0, 10.0, 0.25]).unwrap(), 2.5);
+        assert_eq!(
+            registry.call("lerp", &[0.0]),
+            Err(Error::InvalidArgumentCount {
+                function: "lerp(a, b, t)".to_string(),
+                expected: 3,
+                actual: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn test_unknown_function() {
+        let registry = FunctionRegistry::new();
+        assert_eq!(
+            registry.call("foo", &[]),
+            Err(Error::UndefinedFunction("foo".to_string()))
+        );
+    }
+}
@@ -0,0 +1,847 @@
+// calculator/simplify.rs - 语法树的代数化简与常量折叠
+
+use std::cmp::Ordering;
+use std::convert::Infallible;
+
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+use super::parser::AstNode;
+use super::value::{self, exact_power_fits, NumberMode, Value};
+
+/// 折叠整数次幂时允许的最大指数，避免 `10^100000` 这类常量生成巨大的分数
+const MAX_FOLD_EXPONENT: i64 = 1024;
+
+/// 因子指数绝对值的上限，超出后不再合并幂，避免指数溢出
+const MAX_FACTOR_EXPONENT: i64 = i32::MAX as i64;
+
+/// 化简表达式
+///
+/// - 折叠常量子树（按分数精确计算，结果无法精确写成字面量时保留为 `p / q`）
+/// - 消去 `x*1`、`x+0`、`x^1` 等恒等式
+/// - 合并同类项（`2*x + 3*x` → `5 * x`）与同底、同号的整数次幂（`x * x^2` → `x^3`），
+///   常数乘以和式时展开（`2 * (x + 1)` → `2 * x + 2`）
+/// - 展平嵌套的 `+` 与 `*`，并把可交换的操作数排成规范顺序（常数项在最后，系数在最前）
+///
+/// `x / x`、`x - x`、`0 * x`、`x^0` 只在 x 是常数时化简：x 可能未定义或为 0，
+/// 消去它会改变结果。原表达式能够求值时，化简后的结果与原结果相同（浮点数模式下至多
+/// 相差舍入误差）；原表达式求值出错时，化简后仍然出错。浮点数模式下 `0^-1` 得到无穷大，
+/// 化简为 `1 / 0` 后改为报除零错误。
+///
+/// ```
+/// use chapter02::calculator::parser::ExpressionParser;
+/// use chapter02::calculator::simplify::simplify;
+///
+/// let parser = ExpressionParser::new();
+/// let parse = |text: &str| parser.parse(parser.tokenize(text).unwrap()).unwrap();
+/// assert_eq!(simplify(&parse("x*1 + 0 + 2*x + y*x")), parse("3 * x + x * y"));
+/// assert_eq!(simplify(&parse("(2 + 3) * x^1 / 10")), parse("0.5 * x"));
+/// ```
+pub fn simplify(ast: &AstNode) -> AstNode {
+    if let AstNode::FunctionDef { name, params, body } = ast {
+        return AstNode::FunctionDef {
+            name: name.clone(),
+            params: params.clone(),
+            body: Box::new(simplify(body)),
+        };
+    }
+
+    let node = ast
+        .map_children(|child| Ok::<_, Infallible>(simplify(child)))
+        .unwrap_or_else(|never| match never {});
+    match &node {
+        AstNode::BinaryOp { .. } | AstNode::UnaryOp { .. } => rebuild_sum(collect_terms(&node)),
+        AstNode::FunctionCall { name, args } => fold_function(name, args).unwrap_or(node),
+        _ => node,
+    }
+}
+
+/// 单项式：有理系数乘以若干因子的整数次幂
+///
+/// 因子可能为 0 或未定义，因此同一底数的正、负次幂分开记录，不互相抵消；
+/// 系数为 0 的项也保留因子。含有除以 0 的项记为 `±1 / 0`。
+#[derive(Debug, Clone, PartialEq)]
+struct Term {
+    coefficient: BigRational,
+    /// 按规范顺序排列的因子及其指数（指数不为 0；同一底数至多出现一正一负两次）
+    factors: Vec<(AstNode, i64)>,
+}
+
+impl Term {
+    fn constant(coefficient: BigRational) -> Self {
+        Term {
+            coefficient,
+            factors: Vec::new(),
+        }
+    }
+
+    /// 不再分解的因子
+    fn opaque(node: AstNode) -> Self {
+        Term {
+            coefficient: BigRational::one(),
+            factors: vec![(node, 1)],
+        }
+    }
+
+    fn neg(mut self) -> Self {
+        self.coefficient = -self.coefficient;
+        self
+    }
+
+    /// 除以 0 的项
+    fn undefined() -> Self {
+        Term {
+            coefficient: BigRational::one(),
+            factors: vec![(AstNode::Number(0.0), -1)],
+        }
+    }
+
+    /// 同一底数、同号的指数相加
+    fn mul(mut self, other: Term) -> Self {
+        self.coefficient *= other.coefficient;
+        for (base, exponent) in other.factors {
+            let same = self.factors.iter_mut().find(|(b, e)| {
+                *b == base && e.signum() == exponent.signum() && e.abs() < MAX_FACTOR_EXPONENT
+            });
+            match same {
+                Some((_, e)) => *e += exponent,
+                None => self.factors.push((base, exponent)),
+            }
+        }
+        self.normalize()
+    }
+
+    /// 整数次幂；结果过大或指数为 0 而项不是常数时无法折叠，返回 None
+    ///
+    /// 系数为 0 的项（其值为 0 或出错）的负数次幂记为除以 0。指数为负时，原来在分母中的
+    /// 因子仍然留一份在分母中：`1 / (1 / x)` 化简为 `x^2 / x`，x 为 0 时照样出错。
+    fn pow(self, n: i64) -> Option<Self> {
+        if self.is_undefined() || (n < 0 && self.coefficient.is_zero()) {
+            return Some(Term::undefined());
+        }
+        if n == 0 && !self.is_constant() {
+            return None;
+        }
+
+        let mut factors = Vec::with_capacity(self.factors.len());
+        for (base, e) in self.factors {
+            let power = e
+                .checked_mul(n)
+                .filter(|e| e.abs() <= MAX_FACTOR_EXPONENT)?;
+            if n < 0 && e < 0 {
+                factors.push((base.clone(), power.checked_sub(e)?));
+                factors.push((base, e));
+            } else {
+                factors.push((base, power));
+            }
+        }
+        let power = Term {
+            coefficient: rational_pow(&self.coefficient, n)?,
+            factors: Vec::new(),
+        };
+        // 逐个乘入，合并同底同号的因子
+        Some(factors.into_iter().fold(power, |term, factor| {
+            term.mul(Term {
+                coefficient: BigRational::one(),
+                factors: vec![factor],
+            })
+        }))
+    }
+
+    /// 排序；除以 0 的项只保留系数的符号和一个 `0^-1` 因子（其余部分已无意义）
+    fn normalize(mut self) -> Self {
+        if self.is_undefined() {
+            let mut term = Term::undefined();
+            if self.coefficient.is_negative() {
+                term.coefficient = -term.coefficient;
+            }
+            return term;
+        }
+        self.factors.retain(|(_, e)| *e != 0);
+        self.factors
+            .sort_by(|a, b| canonical_order(&a.0, &b.0).then(b.1.cmp(&a.1)));
+        self
+    }
+
+    fn is_constant(&self) -> bool {
+        self.factors.is_empty()
+    }
+
+    fn is_undefined(&self) -> bool {
+        let zero = AstNode::Number(0.0);
+        self.factors.iter().any(|(b, _)| *b == zero)
+    }
+}
+
+/// 有理数的整数次幂（底数为 0 时指数必须非负）；结果的位数过多时返回 None
+fn rational_pow(r: &BigRational, n: i64) -> Option<BigRational> {
+    let magnitude = n.unsigned_abs();
+    if !exact_power_fits(r.numer(), magnitude) || !exact_power_fits(r.denom(), magnitude) {
+        return None;
+    }
+    let power = num_traits::pow(r.clone(), magnitude as usize);
+    Some(if n < 0 { power.recip() } else { power })
+}
+
+/// 把和式展开为各项（尚未合并同类项）
+fn collect_terms(node: &AstNode) -> Vec<Term> {
+    match node {
+        AstNode::BinaryOp {
+            op: '+',
+            left,
+            right,
+        } => {
+            let mut terms = collect_terms(left);
+            terms.extend(collect_terms(right));
+            terms
+        }
+        AstNode::BinaryOp {
+            op: '-',
+            left,
+            right,
+        } => {
+            let mut terms = collect_terms(left);
+            terms.extend(collect_terms(right).into_iter().map(Term::neg));
+            terms
+        }
+        AstNode::UnaryOp { op: '-', operand } => {
+            collect_terms(operand).into_iter().map(Term::neg).collect()
+        }
+        AstNode::UnaryOp { op: '+', operand } => collect_terms(operand),
+        node => {
+            // 常数乘以和式时展开：2 * (x + 1) → 2 * x + 2
+            let term = term_of(node);
+            match term.factors.as_slice() {
+                [(sum, 1)] if is_sum(sum) => collect_terms(sum)
+                    .into_iter()
+                    .map(|t| t.mul(Term::constant(term.coefficient.clone())))
+                    .collect(),
+                _ => vec![term],
+            }
+        }
+    }
+}
+
+/// 把乘积分解为系数与因子
+fn term_of(node: &AstNode) -> Term {
+    match node {
+        AstNode::BinaryOp {
+            op: '*',
+            left,
+            right,
+        } => term_of(left).mul(term_of(right)),
+        AstNode::BinaryOp {
+            op: '/',
+            left,
+            right,
+        } => {
+            // 除数为 0 时记为除以 0，求值时仍然报错；指数溢出时保留除数
+            let inverse = term_of(right).pow(-1).unwrap_or_else(|| Term {
+                coefficient: BigRational::one(),
+                factors: vec![((**right).clone(), -1)],
+            });
+            term_of(left).mul(inverse)
+        }
+        AstNode::BinaryOp {
+            op: '^',
+            left,
+            right,
+        } => {
+            if let Some(n) = integer_exponent(right) {
+                if let Some(term) = term_of(left).pow(n) {
+                    return term;
+                }
+            }
+            simplify_power(left, right)
+        }
+        AstNode::UnaryOp { op: '-', operand } => term_of(operand).neg(),
+        AstNode::UnaryOp { op: '+', operand } => term_of(operand),
+        // 子节点已经化简过，其中的和式作为一个整体参与乘积
+        node => match rational_of(node) {
+            Some(r) => Term::constant(r),
+            None => Term::opaque(node.clone()),
+        },
+    }
+}
+
+/// term_of 无法折叠的幂：只处理常数，其余保持原样
+fn simplify_power(base: &AstNode, exponent: &AstNode) -> Term {
+    if let (Some(b), Some(e)) = (rational_of(base), rational_of(exponent)) {
+        if b.is_one() {
+            return Term::constant(b);
+        }
+        // 指数不是整数且结果可以精确表示时折叠（整数次幂已在 term_of 中处理，
+        // 剩下的是结果过大的整数次幂）
+        if !e.is_integer() {
+            let result = Value::binary('^', &Value::Rational(b), &Value::Rational(e));
+            if let Ok(Value::Rational(r)) = result {
+                return Term::constant(r);
+            }
+        }
+    }
+
+    Term::opaque(AstNode::BinaryOp {
+        op: '^',
+        left: Box::new(base.clone()),
+        right: Box::new(exponent.clone()),
+    })
+}
+
+/// 指数是（不太大的）整数常数时返回它
+fn integer_exponent(node: &AstNode) -> Option<i64> {
+    let r = rational_of(node)?;
+    if !r.is_integer() {
+        return None;
+    }
+    r.to_integer()
+        .to_i64()
+        .filter(|n| n.abs() <= MAX_FOLD_EXPONENT)
+}
+
+/// 常数节点（含 `-3`、`1 / 3` 这样的常数表达式）的精确值
+fn rational_of(node: &AstNode) -> Option<BigRational> {
+    match node {
+        AstNode::Number(n) if n.is_finite() => literal_rational(&n.to_string()),
+        AstNode::Literal(text) => literal_rational(text),
+        AstNode::UnaryOp { op: '-', operand } => rational_of(operand).map(|r| -r),
+        AstNode::BinaryOp {
+            op: '/',
+            left,
+            right,
+        } => {
+            let (a, b) = (rational_of(left)?, rational_of(right)?);
+            (!b.is_zero()).then(|| a / b)
+        }
+        _ => None,
+    }
+}
+
+fn literal_rational(text: &str) -> Option<BigRational> {
+    match Value::from_literal(text, NumberMode::Rational, 0) {
+        Ok(Value::Rational(r)) => Some(r),
+        _ => None,
+    }
+}
+
+/// 参数都是常数时，按分数精确计算 `abs`、`floor`、`max` 等函数
+fn fold_function(name: &str, args: &[AstNode]) -> Option<AstNode> {
+    let values = args.iter().map(rational_of).collect::<Option<Vec<_>>>()?;
+    let result = value::rational_function(&name.to_lowercase(), &values)?;
+    Some(rebuild_sum(vec![Term::constant(result)]))
+}
+
+/// 合并同类项并按规范顺序重建和式
+fn rebuild_sum(terms: Vec<Term>) -> AstNode {
+    // 系数乘进和式因子、展开系数为 0 的项后可能出现新的同类项，合并到不再变化为止
+    let mut combined = combine_terms(terms);
+    loop {
+        let next = combine_terms(combined.clone());
+        if next == combined {
+            break;
+        }
+        combined = next;
+    }
+    // 常数项放在最后
+    combined.sort_by(|a, b| match (a.is_constant(), b.is_constant()) {
+        (false, true) => Ordering::Less,
+        (true, false) => Ordering::Greater,
+        _ => compare_factors(&a.factors, &b.factors),
+    });
+
+    let mut result: Option<AstNode> = None;
+    for term in combined {
+        let negative = term.coefficient.is_negative();
+        let magnitude = rebuild_term(&Term {
+            coefficient: term.coefficient.abs(),
+            factors: term.factors,
+        });
+        result = Some(match (result, negative) {
+            (None, false) => magnitude,
+            (None, true) => negate_leading(magnitude),
+            (Some(sum), negative) => binary(if negative { '-' } else { '+' }, sum, magnitude),
+        });
+    }
+    result.unwrap_or(AstNode::Number(0.0))
+}
+
+/// 取负：负号加在乘积最左边的因子上，如 `-2 * x` 而不是 `-(2 * x)`；
+/// 最左边的因子是和式时仍然对整个乘积取负，否则再次化简时会展开成不同的形式
+fn negate_leading(node: AstNode) -> AstNode {
+    fn leftmost(node: &AstNode) -> &AstNode {
+        match node {
+            AstNode::BinaryOp {
+                op: '*' | '/',
+                left,
+                ..
+            } => leftmost(left),
+            node => node,
+        }
+    }
+
+    match node {
+        AstNode::BinaryOp {
+            op: op @ ('*' | '/'),
+            left,
+            right,
+        } if !is_sum(leftmost(&left)) => AstNode::BinaryOp {
+            op,
+            left: Box::new(negate_leading(*left)),
+            right,
+        },
+        node => AstNode::UnaryOp {
+            op: '-',
+            operand: Box::new(node),
+        },
+    }
+}
+
+/// 合并因子相同的项，去掉系数为 0 的项，并把系数乘进和式因子
+fn combine_terms(terms: Vec<Term>) -> Vec<Term> {
+    let mut combined: Vec<Term> = Vec::new();
+    for term in terms.into_iter().flat_map(expand_zero) {
+        match combined.iter_mut().find(|t| t.factors == term.factors) {
+            Some(existing) => existing.coefficient += term.coefficient,
+            None => combined.push(term),
+        }
+    }
+    // 系数为 0 的项保留非常数的因子，因子出错时结果仍然出错
+    combined
+        .into_iter()
+        .filter(|t| !(t.is_constant() && t.coefficient.is_zero()))
+        .map(|t| {
+            // 符号留在系数上，由 rebuild_sum 写成减号
+            let t = t.normalize();
+            if t.coefficient.is_negative() {
+                absorb_coefficient(&t.neg()).neg()
+            } else {
+                absorb_coefficient(&t)
+            }
+        })
+        .collect()
+}
+
+/// 系数为 0 的项展开其中一次的和式因子：`0 * (x + 1) * y` → `0 * x * y`。
+/// 展开前后都只在某个因子出错时出错；否则 `0 * (x + 1)` 会作为子树留在结果中，
+/// 再次化简时被展开，化简就不是幂等的
+fn expand_zero(term: Term) -> Vec<Term> {
+    let position = term
+        .factors
+        .iter()
+        .position(|(base, exponent)| *exponent == 1 && is_sum(base));
+    let Some(index) = position.filter(|_| term.coefficient.is_zero()) else {
+        return vec![term];
+    };
+
+    let mut rest = term;
+    let (sum, _) = rest.factors.remove(index);
+    collect_terms(&sum)
+        .into_iter()
+        .flat_map(|t| expand_zero(t.mul(rest.clone())))
+        .collect()
+}
+
+/// 系数不为 1 且含有一次的和式因子时，把系数乘进和式：`2 * (x + 1) * y` → `(2 * x + 2) * y`。
+/// 否则 `2 * (x + 1)` 会作为子树留在结果中，再次化简时被展开，化简就不是幂等的
+///
+/// 和式在分母中时只有系数写不成一个字面量（要拆出分母，如 `1 / (3 * (x + 1))`）才需要乘进去，
+/// 而且只乘系数的分母：`5 / (x + 3)` 保持不变，而不是写成 `1 / (0.2 * x + 0.6)`，
+/// 否则浮点数求值时分母的舍入误差会让原本除以 0 的点不再报错
+fn absorb_coefficient(term: &Term) -> Term {
+    // 优先乘进分子中的和式
+    let position = |sign: i64| {
+        term.factors
+            .iter()
+            .position(|(base, exponent)| *exponent == sign && is_sum(base))
+    };
+    let coefficient = &term.coefficient;
+    let position = position(1).or_else(|| position(-1));
+    let Some(index) = position.filter(|_| !coefficient.is_one() && !coefficient.is_zero()) else {
+        return term.clone();
+    };
+
+    let (scale, rest) = if term.factors[index].1 == 1 {
+        (coefficient.clone(), BigRational::one())
+    } else if number_node(coefficient).is_some() {
+        return term.clone();
+    } else {
+        let denom = BigRational::from_integer(coefficient.denom().clone());
+        (
+            denom,
+            BigRational::from_integer(coefficient.numer().clone()),
+        )
+    };
+    let scaled = collect_terms(&term.factors[index].0)
+        .into_iter()
+        .map(|t| t.mul(Term::constant(scale.clone())))
+        .collect();
+    let mut factors = term.factors.clone();
+    factors[index].0 = rebuild_sum(scaled);
+    // 乘进系数后可能与其它因子同底，重新合并
+    Term::constant(rest).mul(Term {
+        coefficient: BigRational::one(),
+        factors,
+    })
+}
+
+fn is_sum(node: &AstNode) -> bool {
+    matches!(node, AstNode::BinaryOp { op: '+' | '-', .. })
+}
+
+/// 重建单项式：正指数的因子在分子，负指数的在分母
+fn rebuild_term(term: &Term) -> AstNode {
+    let coefficient = &term.coefficient;
+    let (mut numerator, mut denominator) = (Vec::new(), Vec::new());
+
+    // 系数能写成一个字面量时放在最前面，否则拆成分子和分母
+    match number_node(coefficient) {
+        Some(_) if coefficient.is_one() => {}
+        Some(node) => numerator.push(node),
+        None => {
+            let numer = BigRational::from_integer(coefficient.numer().clone());
+            let denom = BigRational::from_integer(coefficient.denom().clone());
+            if !numer.is_one() {
+                numerator.extend(number_node(&numer));
+            }
+            denominator.extend(number_node(&denom));
+        }
+    }
+    for (base, exponent) in &term.factors {
+        let side = if *exponent > 0 {
+            &mut numerator
+        } else {
+            &mut denominator
+        };
+        side.push(match exponent.abs() {
+            1 => base.clone(),
+            n => binary('^', base.clone(), AstNode::Number(n as f64)),
+        });
+    }
+
+    let product = |factors: Vec<AstNode>| factors.into_iter().reduce(|a, b| binary('*', a, b));
+    let numerator = product(numerator).unwrap_or(AstNode::Number(1.0));
+    match product(denominator) {
+        Some(denominator) => binary('/', numerator, denominator),
+        None => numerator,
+    }
+}
+
+/// 非负有理数对应的字面量：可以由 f64 精确表示时用 `Number`，过大的整数用 `Literal`
+fn number_node(value: &BigRational) -> Option<AstNode> {
+    if let Some(n) = value.to_f64() {
+        if n.is_finite() && literal_rational(&n.to_string()).as_ref() == Some(value) {
+            return Some(AstNode::Number(n));
+        }
+    }
+    value
+        .is_integer()
+        .then(|| AstNode::Literal(value.to_integer().to_string()))
+}
+
+fn binary(op: char, left: AstNode, right: AstNode) -> AstNode {
+    AstNode::BinaryOp {
+        op,
+        left: Box::new(left),
+        right: Box::new(right),
+    }
+}
+
+/// 可交换操作数的规范顺序：先按节点种类，再依次比较各字段
+fn canonical_order(a: &AstNode, b: &AstNode) -> Ordering {
+    fn rank(node: &AstNode) -> u8 {
+        match node {
+            AstNode::Number(_) => 0,
+            AstNode::Literal(_) => 1,
+            AstNode::Imaginary(_) => 2,
+            AstNode::Constant(_) => 3,
+            AstNode::Variable(_) => 4,
+            AstNode::FunctionCall { .. } => 5,
+            AstNode::UnaryOp { .. } => 6,
+            AstNode::BinaryOp { .. } => 7,
+            AstNode::Quantity { .. } => 8,
+            AstNode::Convert { .. } => 9,
+            AstNode::Assign { .. } => 10,
+            AstNode::FunctionDef { .. } => 11,
+            AstNode::Invalid => 12,
+        }
+    }
+
+    fn all(a: &[AstNode], b: &[AstNode]) -> Ordering {
+        a.iter()
+            .zip(b)
+            .map(|(a, b)| canonical_order(a, b))
+            .find(|order| order.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len()))
+    }
+
+    match (a, b) {
+        (AstNode::Number(x), AstNode::Number(y))
+        | (AstNode::Imaginary(x), AstNode::Imaginary(y)) => x.total_cmp(y),
+        (AstNode::Literal(x), AstNode::Literal(y)) => x.len().cmp(&y.len()).then(x.cmp(y)),
+        (AstNode::Constant(x), AstNode::Constant(y))
+        | (AstNode::Variable(x), AstNode::Variable(y)) => x.cmp(y),
+        (
+            AstNode::FunctionCall { name, args },
+            AstNode::FunctionCall {
+                name: other_name,
+                args: other_args,
+            },
+        ) => name.cmp(other_name).then_with(|| all(args, other_args)),
+        (
+            AstNode::UnaryOp { op, operand },
+            AstNode::UnaryOp {
+                op: other_op,
+                operand: other_operand,
+            },
+        ) => op
+            .cmp(other_op)
+            .then_with(|| canonical_order(operand, other_operand)),
+        (
+            AstNode::BinaryOp { op, left, right },
+            AstNode::BinaryOp {
+                op: other_op,
+                left: other_left,
+                right: other_right,
+            },
+        ) => op
+            .cmp(other_op)
+            .then_with(|| canonical_order(left, other_left))
+            .then_with(|| canonical_order(right, other_right)),
+        (AstNode::Quantity { value, unit }, AstNode::Quantity { value: v, unit: u })
+        | (AstNode::Convert { value, unit }, AstNode::Convert { value: v, unit: u }) => {
+            canonical_order(value, v).then_with(|| unit.to_string().cmp(&u.to_string()))
+        }
+        (AstNode::Assign { name, value }, AstNode::Assign { name: n, value: v }) => {
+            name.cmp(n).then_with(|| canonical_order(value, v))
+        }
+        (
+            AstNode::FunctionDef { name, params, body },
+            AstNode::FunctionDef {
+                name: n,
+                params: p,
+                body: b,
+            },
+        ) => name
+            .cmp(n)
+            .then_with(|| params.cmp(p))
+            .then_with(|| canonical_order(body, b)),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+fn compare_factors(a: &[(AstNode, i64)], b: &[(AstNode, i64)]) -> Ordering {
+    for ((base_a, exp_a), (base_b, exp_b)) in a.iter().zip(b) {
+        let order = canonical_order(base_a, base_b).then(exp_b.cmp(exp_a));
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculator::evaluator::ExpressionEvaluator;
+    use crate::calculator::parser::ExpressionParser;
+    use crate::utils::Error;
+    use proptest::prelude::*;
+
+    fn parse(expression: &str) -> AstNode {
+        let parser = ExpressionParser::new();
+        parser.parse(parser.tokenize(expression).unwrap()).unwrap()
+    }
+
+    fn assert_simplifies(expression: &str, expected: &str) {
+        assert_eq!(
+            simplify(&parse(expression)),
+            parse(expected),
+            "{}",
+            expression
+        );
+    }
+
+    #[test]
+    fn test_identities() {
+        assert_simplifies("x * 1", "x");
+        assert_simplifies("0 + x", "x");
+        assert_simplifies("x^1", "x");
+        assert_simplifies("--x", "x");
+        assert_simplifies("3 - 3", "0");
+        assert_simplifies("3 / 3", "1");
+        assert_simplifies("2^0 * 1^0.5", "1");
+    }
+
+    #[test]
+    fn test_identities_keep_errors() {
+        // x 可能未定义或为 0，不能消去
+        assert_simplifies("x^0", "x^0");
+        assert_simplifies("1^x", "1^x");
+        assert_simplifies("x - x", "0 * x");
+        assert_simplifies("x / x", "x / x");
+        assert_simplifies("x^2 / x", "x^2 / x");
+        assert_simplifies("1 / (1 / x)", "x^2 / x");
+        assert_simplifies("0 * (1 / 0)", "1 / 0");
+        assert_simplifies("z * 0", "0 * z");
+        // 分母中的和式不乘以小数系数，x = -3 时仍然除以 0
+        assert_simplifies("5 / (x + 3)", "5 / (x + 3)");
+        assert_simplifies("1 / 3 / (x + 1)", "1 / (3 * x + 3)");
+        assert_simplifies("2 / 3 / (x + 1)", "2 / (3 * x + 3)");
+    }
+
+    #[test]
+    fn test_constant_folding() {
+        assert_simplifies("2 + 3 * 4", "14");
+        assert_simplifies("0.1 + 0.2", "0.3");
+        assert_simplifies("1 / 3 + 1 / 3", "2 / 3");
+        assert_simplifies("2^-2", "0.25");
+        assert_simplifies("2^0.5", "2^0.5");
+        assert_simplifies("abs(-3) + floor(2.5)", "5");
+        assert_simplifies("2^64", "18446744073709551616");
+        assert_simplifies("sin(2 * 3)", "sin(6)");
+        // 除以 0 保留下来，求值时仍然报错
+        assert_simplifies("x / (1 - 1)", "1 / 0");
+        assert_simplifies("-3 / (x - x)", "-1 / 0");
+        // 结果过大时不折叠
+        assert_simplifies("(10^1000)^1000", &format!("1{}^1000", "0".repeat(1000)));
+    }
+
+    #[test]
+    fn test_like_terms() {
+        assert_simplifies("2*x + 3*x", "5 * x");
+        assert_simplifies("x * x^2 * 3", "3 * x^3");
+        assert_simplifies("x + 1 + x", "2 * x + 1");
+        assert_simplifies("y*x - x*y + sin(x) + sin(x)", "0 * x * y + 2 * sin(x)");
+        assert_simplifies("(x + 1)^2 * 2 + (1 + x)^2", "3 * (x + 1)^2");
+        assert_simplifies("x / 2 + x / 3", "5 * x / 6");
+        assert_simplifies("x * y / (2 * x)", "0.5 * x * y / x");
+    }
+
+    #[test]
+    fn test_canonical_order() {
+        assert_simplifies("3 + y + x", "x + y + 3");
+        assert_simplifies("x + x^2", "x^2 + x");
+        assert_simplifies("y * 2 * x", "2 * x * y");
+        assert_simplifies("1 - x", "-x + 1");
+        assert_eq!(simplify(&parse("b + a")), simplify(&parse("a + b")));
+    }
+
+    #[test]
+    fn test_nested_nodes() {
+        assert_simplifies("f(t) = t * 1 + 0", "f(t) = t");
+        assert_simplifies("y = 2 * (x + x)", "y = 4 * x");
+        assert_simplifies("-(1 - x) * 3", "3 * x - 3");
+        assert_simplifies("sqrt(x * x)", "sqrt(x^2)");
+    }
+
+    /// 由整数、0.5、x、y 和四则运算、指数不小于 min_exponent 的整数次幂组成的表达式
+    fn arithmetic(min_exponent: i32) -> impl Strategy<Value = AstNode> {
+        let leaf = prop_oneof![
+            (-5i32..=5).prop_map(|n| AstNode::Number(n as f64)),
+            Just(AstNode::Number(0.5)),
+            Just(AstNode::Variable("x".to_string())),
+            Just(AstNode::Variable("y".to_string())),
+        ];
+        leaf.prop_recursive(4, 32, 2, move |inner| {
+            prop_oneof![
+                (
+                    inner.clone(),
+                    inner.clone(),
+                    prop::sample::select(vec!['+', '-', '*', '/'])
+                )
+                    .prop_map(|(l, r, op)| binary(op, l, r)),
+                (inner.clone(), min_exponent..=3).prop_map(|(b, n)| binary(
+                    '^',
+                    b,
+                    AstNode::Number(n as f64)
+                )),
+                inner.prop_map(|e| AstNode::UnaryOp {
+                    op: '-',
+                    operand: Box::new(e),
+                }),
+            ]
+        })
+    }
+
+    /// 在 arithmetic 的基础上加入函数调用。只选导数有界或按相对误差传播的函数：floor 在整数
+    /// 附近、sin 在参数很大时都会把舍入误差放大成完全不同的结果；
+    /// 浮点数模式下 0 的负数次幂是无穷大而不是错误，因此不生成负指数
+    fn with_functions() -> impl Strategy<Value = AstNode> {
+        arithmetic(0).prop_recursive(2, 32, 2, |inner| {
+            prop_oneof![
+                (
+                    inner.clone(),
+                    prop::sample::select(vec!["atan", "tanh", "abs", "exp"])
+                )
+                    .prop_map(|(arg, name)| AstNode::FunctionCall {
+                        name: name.to_string(),
+                        args: vec![arg],
+                    }),
+                (
+                    inner.clone(),
+                    inner.clone(),
+                    prop::sample::select(vec!['+', '*'])
+                )
+                    .prop_map(|(l, r, op)| binary(op, l, r)),
+            ]
+        })
+    }
+
+    fn evaluate(ast: &AstNode, mode: NumberMode, x: f64, y: f64) -> Result<Value, Error> {
+        let mut evaluator = ExpressionEvaluator::new();
+        evaluator.set_number_mode(mode);
+        for (name, value) in [("x", x), ("y", y)] {
+            evaluator.evaluate_value(&AstNode::Assign {
+                name: name.to_string(),
+                value: Box::new(AstNode::Number(value)),
+            })?;
+        }
+        evaluator.evaluate_value(ast)
+    }
+
+    proptest! {
+        /// 有理数模式下化简前后的结果完全相同
+        #[test]
+        fn prop_preserves_exact_value(ast in arithmetic(-3), x in -8i32..=8, y in -8i32..=8) {
+            let (x, y) = (x as f64 / 2.0, y as f64 / 2.0);
+            let simplified = simplify(&ast);
+            let actual = evaluate(&simplified, NumberMode::Rational, x, y);
+            match evaluate(&ast, NumberMode::Rational, x, y) {
+                Ok(expected) => prop_assert_eq!(actual, Ok(expected), "{:?} => {:?}", ast, simplified),
+                Err(_) => prop_assert!(actual.is_err(), "{:?} => {:?}: {:?}", ast, simplified, actual),
+            }
+        }
+
+        /// 浮点数模式下化简前后的结果至多相差舍入误差
+        #[test]
+        fn prop_preserves_float_value(ast in with_functions(), x in -20i32..=20, y in -20i32..=20) {
+            let (x, y) = (x as f64 / 4.0, y as f64 / 4.0);
+            let simplified = simplify(&ast);
+            let actual = evaluate(&simplified, NumberMode::Float, x, y);
+            let expected = match evaluate(&ast, NumberMode::Float, x, y) {
+                Ok(value) if value.to_f64().is_finite() => value.to_f64(),
+                Ok(_) => return Ok(()),
+                Err(_) => {
+                    prop_assert!(actual.is_err(), "{:?} => {:?}: {:?}", ast, simplified, actual);
+                    return Ok(());
+                }
+            };
+            prop_assert!(actual.is_ok(), "{:?} => {:?}: {:?}", ast, simplified, actual);
+            let actual = actual.unwrap().to_f64();
+            prop_assert!(
+                (actual - expected).abs() <= 1e-6 * (1.0 + expected.abs()),
+                "{:?} => {:?}: {} != {}",
+                ast,
+                simplified,
+                actual,
+                expected
+            );
+        }
+
+        /// 化简是幂等的
+        #[test]
+        fn prop_idempotent(ast in with_functions()) {
+            let once = simplify(&ast);
+            prop_assert_eq!(simplify(&once), once);
+        }
+    }
+}
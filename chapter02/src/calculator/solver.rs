@@ -0,0 +1,322 @@
+// calculator/solver.rs - 数值求根：二分法、Brent 方法与牛顿法
+
+use crate::utils::Error;
+
+/// 收敛判据：相邻两次迭代的差小于 `TOLERANCE · (1 + |x|)`，即几个 ulp
+pub const TOLERANCE: f64 = 4.0 * f64::EPSILON;
+
+/// 最大迭代次数
+pub const MAX_ITERATIONS: usize = 200;
+
+/// 在区间内找全部根时的采样段数
+pub const SCAN_STEPS: usize = 1000;
+
+fn no_convergence(method: &str, iterations: usize) -> Error {
+    Error::NoConvergence {
+        method: method.to_string(),
+        iterations,
+    }
+}
+
+/// 检查区间两端异号；某一端恰好是根时直接返回它
+fn check_bracket(a: f64, b: f64, fa: f64, fb: f64) -> Result<Option<f64>, Error> {
+    if fa == 0.0 {
+        return Ok(Some(a));
+    }
+    if fb == 0.0 {
+        return Ok(Some(b));
+    }
+    if fa.is_nan() || fb.is_nan() || (fa > 0.0) == (fb > 0.0) {
+        return Err(Error::NoSignChange { lower: a, upper: b });
+    }
+    Ok(None)
+}
+
+/// 二分法：要求 f(a) 与 f(b) 异号，每次迭代把区间减半
+pub fn bisection<F>(mut f: F, a: f64, b: f64) -> Result<f64, Error>
+where
+    F: FnMut(f64) -> Result<f64, Error>,
+{
+    let (mut a, mut b) = (a, b);
+    let mut fa = f(a)?;
+    if let Some(root) = check_bracket(a, b, fa, f(b)?)? {
+        return Ok(root);
+    }
+
+    for _ in 0..MAX_ITERATIONS {
+        let mid = 0.5 * (a + b);
+        let fm = f(mid)?;
+        if fm == 0.0 || (b - a).abs() * 0.5 <= TOLERANCE * (1.0 + mid.abs()) {
+            return Ok(mid);
+        }
+        if (fa > 0.0) == (fm > 0.0) {
+            a = mid;
+            fa = fm;
+        } else {
+            b = mid;
+        }
+    }
+    Err(no_convergence("二分法", MAX_ITERATIONS))
+}
+
+/// Brent 方法：在二分法的保证下尽量使用逆二次插值和割线法，通常只需十几次迭代
+///
+/// 要求 f(a) 与 f(b) 异号。
+pub fn brent<F>(mut f: F, a: f64, b: f64) -> Result<f64, Error>
+where
+    F: FnMut(f64) -> Result<f64, Error>,
+{
+    let (mut a, mut b) = (a, b);
+    let (mut fa, mut fb) = (f(a)?, f(b)?);
+    if let Some(root) = check_bracket(a, b, fa, fb)? {
+        return Ok(root);
+    }
+
+    // b 是当前最好的近似，c 是与 b 异号的另一端，d、e 是最近两次的步长
+    let (mut c, mut fc) = (b, fb);
+    let (mut d, mut e) = (b - a, b - a);
+    for _ in 0..MAX_ITERATIONS {
+        if (fb > 0.0) == (fc > 0.0) {
+            c = a;
+            fc = fa;
+            d = b - a;
+            e = d;
+        }
+        if fc.abs() < fb.abs() {
+            (a, b, c) = (b, c, b);
+            (fa, fb, fc) = (fb, fc, fb);
+        }
+
+        let tol = 2.0 * f64::EPSILON * b.abs() + 0.5 * TOLERANCE * (1.0 + b.abs());
+        let m = 0.5 * (c - b);
+        if m.abs() <= tol || fb == 0.0 {
+            return Ok(b);
+        }
+
+        if e.abs() >= tol && fa.abs() > fb.abs() {
+            let s = fb / fa;
+            let (mut p, mut q) = if a == c {
+                // 割线法
+                (2.0 * m * s, 1.0 - s)
+            } else {
+                // 逆二次插值
+                let (q, r) = (fa / fc, fb / fc);
+                (
+                    s * (2.0 * m * q * (q - r) - (b - a) * (r - 1.0)),
+                    (q - 1.0) * (r - 1.0) * (s - 1.0),
+                )
+            };
+            if p > 0.0 {
+                q = -q;
+            } else {
+                p = -p;
+            }
+            // 插值点落在区间内且收敛得足够快时采用，否则退回二分
+            if 2.0 * p < (3.0 * m * q - (tol * q).abs()).min((e * q).abs()) {
+                e = d;
+                d = p / q;
+            } else {
+                d = m;
+                e = m;
+            }
+        } else {
+            d = m;
+            e = m;
+        }
+
+        a = b;
+        fa = fb;
+        b += if d.abs() > tol { d } else { tol.copysign(m) };
+        fb = f(b)?;
+    }
+    Err(no_convergence("Brent 方法", MAX_ITERATIONS))
+}
+
+/// 牛顿法：从 x0 出发迭代 x ← x − f(x) / f'(x)
+///
+/// 在单根附近二次收敛；导数为 0 或迭代发散时返回 `Error::NoConvergence`。
+pub fn newton<F, D>(mut f: F, mut df: D, x0: f64) -> Result<f64, Error>
+where
+    F: FnMut(f64) -> Result<f64, Error>,
+    D: FnMut(f64) -> Result<f64, Error>,
+{
+    let mut x = x0;
+    for iteration in 1..=MAX_ITERATIONS {
+        let fx = f(x)?;
+        if fx == 0.0 {
+            return Ok(x);
+        }
+        let slope = df(x)?;
+        let step = fx / slope;
+        if !step.is_finite() {
+            return Err(no_convergence("牛顿法", iteration));
+        }
+        x -= step;
+        if step.abs() <= TOLERANCE * (1.0 + x.abs()) {
+            return Ok(x);
+        }
+    }
+    Err(no_convergence("牛顿法", MAX_ITERATIONS))
+}
+
+/// 中心差分近似的导数，用于无法符号求导的函数
+pub fn numeric_derivative<F>(f: &F, x: f64) -> Result<f64, Error>
+where
+    F: Fn(f64) -> Result<f64, Error>,
+{
+    let h = 1e-6 * (1.0 + x.abs());
+    Ok((f(x + h)? - f(x - h)?) / (2.0 * h))
+}
+
+/// 找出区间 [a, b] 内的全部根（从小到大）
+///
+/// 把区间等分为 `SCAN_STEPS` 段：两端异号的段用 Brent 方法求根（排除 `1/x` 这类
+/// 在极点处变号的情况）；|f| 取得局部极小但不变号的点（如 `x^2` 的二重根）用牛顿法验证。
+/// 函数在某些点无定义（如 `ln(x)` 在 x ≤ 0 处）时跳过这些点。
+pub fn find_roots<F>(f: F, a: f64, b: f64) -> Result<Vec<f64>, Error>
+where
+    F: Fn(f64) -> Result<f64, Error>,
+{
+    let (a, b) = if a <= b { (a, b) } else { (b, a) };
+    let width = (b - a) / SCAN_STEPS as f64;
+    let samples: Vec<(f64, Option<f64>)> = (0..=SCAN_STEPS)
+        .map(|i| {
+            let x = if i == SCAN_STEPS {
+                b
+            } else {
+                a + width * i as f64
+            };
+            (x, f(x).ok().filter(|y| y.is_finite()))
+        })
+        .collect();
+
+    let mut roots = Vec::new();
+    for (i, window) in samples.windows(2).enumerate() {
+        let ((x0, y0), (x1, y1)) = (window[0], window[1]);
+        let (Some(y0), Some(y1)) = (y0, y1) else {
+            continue;
+        };
+        if y0 == 0.0 {
+            roots.push(x0);
+        } else if y1 != 0.0 && (y0 > 0.0) != (y1 > 0.0) {
+            let root = brent(&f, x0, x1)?;
+            // 极点两侧也会变号，但那里的函数值不会趋于 0
+            if f(root).is_ok_and(|y| y.abs() <= y0.abs().min(y1.abs())) {
+                roots.push(root);
+            }
+        } else if i > 0 {
+            // 不变号的局部极小值可能是偶数重根
+            if let Some(y_prev) = samples[i - 1].1 {
+                let touches = y0.abs() < y_prev.abs() && y0.abs() < y1.abs();
+                if touches {
+                    let lower = samples[i - 1].0;
+                    let root = newton(&f, |x| numeric_derivative(&f, x), x0);
+                    if let Some(root) = root.ok().filter(|r| (lower..=x1).contains(r)) {
+                        roots.push(root);
+                    }
+                }
+            }
+        }
+    }
+    if samples.last().is_some_and(|(_, y)| *y == Some(0.0)) {
+        roots.push(b);
+    }
+
+    // 相邻段可能找到同一个根
+    roots.sort_by(f64::total_cmp);
+    roots.dedup_by(|x, y| (*x - *y).abs() <= 1e-9 * (1.0 + y.abs()));
+    Ok(roots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() <= 1e-9 * (1.0 + b.abs())
+    }
+
+    #[test]
+    fn test_bracketed_methods() {
+        let f = |x: f64| Ok(x * x - 2.0);
+        let sqrt2 = std::f64::consts::SQRT_2;
+        assert!(close(bisection(f, 0.0, 2.0).unwrap(), sqrt2));
+        assert!(close(brent(f, 0.0, 2.0).unwrap(), sqrt2));
+        assert!(close(
+            brent(|x: f64| Ok(x.cos() - x), 0.0, 1.0).unwrap(),
+            0.7390851332151607
+        ));
+        // 端点恰好是根
+        assert_eq!(brent(|x: f64| Ok(x - 1.0), 1.0, 3.0).unwrap(), 1.0);
+        assert_eq!(
+            brent(f, 2.0, 3.0),
+            Err(Error::NoSignChange {
+                lower: 2.0,
+                upper: 3.0
+            })
+        );
+
+        // Brent 方法比二分法少得多的函数求值次数
+        let mut calls = 0;
+        brent(
+            |x: f64| {
+                calls += 1;
+                Ok(x.powi(3) - x - 1.0)
+            },
+            1.0,
+            2.0,
+        )
+        .unwrap();
+        assert!(calls < 15, "{}", calls);
+    }
+
+    #[test]
+    fn test_newton() {
+        let root = newton(|x| Ok(x * x - 2.0), |x| Ok(2.0 * x), 1.0).unwrap();
+        assert!(close(root, std::f64::consts::SQRT_2));
+        // x² + 1 没有实根
+        assert!(matches!(
+            newton(|x| Ok(x * x + 1.0), |x| Ok(2.0 * x), 0.5),
+            Err(Error::NoConvergence {
+                iterations: MAX_ITERATIONS,
+                ..
+            })
+        ));
+        // 导数为 0
+        assert_eq!(
+            newton(|x| Ok(x * x + 1.0), |x| Ok(2.0 * x), 0.0),
+            Err(Error::NoConvergence {
+                method: "牛顿法".to_string(),
+                iterations: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_find_roots() {
+        // sin 在 [-1, 10] 内的根 0, π, 2π, 3π
+        let roots = find_roots(|x: f64| Ok(x.sin()), -1.0, 10.0).unwrap();
+        assert_eq!(roots.len(), 4);
+        for (root, k) in roots.iter().zip(0..) {
+            assert!(close(*root, k as f64 * std::f64::consts::PI));
+        }
+
+        // 二重根、极点和无定义的区间
+        let roots = find_roots(|x: f64| Ok((x - 1.0).powi(2) * (x + 2.0)), -5.0, 5.0).unwrap();
+        assert_eq!(roots.len(), 2);
+        assert!(close(roots[0], -2.0) && close(roots[1], 1.0));
+        assert!(find_roots(|x: f64| Ok(1.0 / x), -1.0, 1.3)
+            .unwrap()
+            .is_empty());
+        let ln = |x: f64| {
+            if x > 0.0 {
+                Ok(x.ln())
+            } else {
+                Err(Error::InvalidLogarithm)
+            }
+        };
+        let roots = find_roots(ln, -3.0, 3.0).unwrap();
+        assert_eq!(roots.len(), 1);
+        assert!(close(roots[0], 1.0));
+    }
+}
@@ -0,0 +1,165 @@
+// calculator/special.rs - 特殊函数：伽马函数、双伽马函数与误差函数
+
+use std::f64::consts::PI;
+
+/// Lanczos 近似的参数 g
+const LANCZOS_G: f64 = 7.0;
+
+/// Lanczos 近似的系数（g = 7，n = 9），相对误差约 1e-15
+const LANCZOS_COEFFICIENTS: [f64; 9] = [
+    0.999_999_999_999_809_9,
+    676.520_368_121_885_1,
+    -1_259.139_216_722_402_8,
+    771.323_428_777_653_1,
+    -176.615_029_162_140_6,
+    12.507_343_278_686_905,
+    -0.138_571_095_265_720_12,
+    9.984_369_578_019_572e-6,
+    1.505_632_735_149_311_6e-7,
+];
+
+/// 伽马函数可以精确计算的最大正整数参数（Γ(171) = 170! 仍在 f64 范围内）
+const MAX_EXACT_GAMMA: f64 = 171.0;
+
+/// 伽马函数 Γ(x)
+///
+/// 正整数参数按阶乘精确计算；非正整数是极点，返回 NaN，由调用方检查定义域。
+pub fn gamma(x: f64) -> f64 {
+    if x.fract() == 0.0 {
+        if x <= 0.0 {
+            return f64::NAN;
+        }
+        if x <= MAX_EXACT_GAMMA {
+            return (1..x as u64).fold(1.0, |acc, k| acc * k as f64);
+        }
+        return f64::INFINITY;
+    }
+    if x < 0.5 {
+        // 反射公式 Γ(x)Γ(1 − x) = π / sin(πx)
+        return PI / ((PI * x).sin() * gamma(1.0 - x));
+    }
+    let (t, series) = lanczos(x);
+    (2.0 * PI).sqrt() * t.powf(x - 0.5) * (-t).exp() * series
+}
+
+/// 伽马函数绝对值的自然对数 ln|Γ(x)|，参数很大时不会溢出
+pub fn lgamma(x: f64) -> f64 {
+    if x.fract() == 0.0 && x <= 0.0 {
+        return f64::NAN;
+    }
+    if x < 0.5 {
+        return (PI / (PI * x).sin().abs()).ln() - lgamma(1.0 - x);
+    }
+    let (t, series) = lanczos(x);
+    0.5 * (2.0 * PI).ln() + (x - 0.5) * t.ln() - t + series.ln()
+}
+
+/// 双伽马函数 ψ(x) = Γ'(x) / Γ(x)，即 ln Γ(x) 的导数
+///
+/// 先用递推 ψ(x) = ψ(x + 1) − 1/x 把参数移到 10 以上，再用渐近展开计算。
+pub fn digamma(x: f64) -> f64 {
+    if x.fract() == 0.0 && x <= 0.0 {
+        return f64::NAN;
+    }
+    if x < 0.5 {
+        // 反射公式 ψ(1 − x) − ψ(x) = π cot(πx)
+        return digamma(1.0 - x) - PI / (PI * x).tan();
+    }
+
+    let mut x = x;
+    let mut result = 0.0;
+    while x < 10.0 {
+        result -= 1.0 / x;
+        x += 1.0;
+    }
+    let inv2 = 1.0 / (x * x);
+    let series = inv2
+        * (1.0 / 12.0
+            - inv2 * (1.0 / 120.0 - inv2 * (1.0 / 252.0 - inv2 * (1.0 / 240.0 - inv2 / 132.0))));
+    result + x.ln() - 0.5 / x - series
+}
+
+/// Lanczos 近似的公共部分：返回 t = x + g − 0.5 与级数之和
+fn lanczos(x: f64) -> (f64, f64) {
+    let x = x - 1.0;
+    let series = LANCZOS_COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(LANCZOS_COEFFICIENTS[0], |acc, (i, c)| {
+            acc + c / (x + i as f64 + 1.0)
+        });
+    (x + LANCZOS_G + 0.5, series)
+}
+
+/// 误差函数 erf(x)
+///
+/// 使用各项均为正的级数 erf(x) = 2/√π · e^(−x²) · Σ (2x²)ⁿ x / (2n + 1)!!，
+/// 没有正负项相消带来的精度损失；|x| > 6 时 erf(x) 在 f64 精度下已等于 ±1。
+pub fn erf(x: f64) -> f64 {
+    if x.is_nan() {
+        return f64::NAN;
+    }
+    if x.abs() > 6.0 {
+        return x.signum();
+    }
+
+    let x2 = 2.0 * x * x;
+    let mut term = x;
+    let mut sum = x;
+    let mut n = 0.0;
+    while term.abs() > f64::EPSILON * sum.abs() * 1e-2 {
+        n += 1.0;
+        term *= x2 / (2.0 * n + 1.0);
+        sum += term;
+    }
+    2.0 / PI.sqrt() * (-x * x).exp() * sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() <= 1e-13 * b.abs().max(1.0)
+    }
+
+    #[test]
+    fn test_gamma() {
+        assert_eq!(gamma(5.0), 24.0);
+        assert_eq!(gamma(1.0), 1.0);
+        assert!(close(gamma(0.5), PI.sqrt()));
+        assert!(close(gamma(-0.5), -2.0 * PI.sqrt()));
+        assert!(close(gamma(4.5), 11.631728396567448));
+        assert!(gamma(0.0).is_nan());
+        assert!(gamma(-3.0).is_nan());
+        assert!(gamma(200.0).is_infinite());
+    }
+
+    #[test]
+    fn test_lgamma() {
+        assert!(close(lgamma(10.0), 362880f64.ln()));
+        assert!(close(lgamma(0.5), PI.sqrt().ln()));
+        // ln Γ(1000)，Γ(1000) 本身超出 f64 范围
+        assert!(close(lgamma(1000.0), 5905.220423209181));
+    }
+
+    #[test]
+    fn test_digamma() {
+        const EULER_GAMMA: f64 = 0.5772156649015329;
+        assert!(close(digamma(1.0), -EULER_GAMMA));
+        assert!(close(digamma(0.5), -EULER_GAMMA - 2.0 * 2f64.ln()));
+        assert!(close(digamma(5.0), 25.0 / 12.0 - EULER_GAMMA));
+        assert!(close(digamma(-0.5), 0.03648997397857652));
+        assert!(digamma(-1.0).is_nan());
+    }
+
+    #[test]
+    fn test_erf() {
+        assert_eq!(erf(0.0), 0.0);
+        assert!(close(erf(0.5), 0.5204998778130465));
+        assert!(close(erf(1.0), 0.8427007929497149));
+        assert!(close(erf(-2.0), -0.9953222650189527));
+        assert!(close(erf(3.5), 0.9999992569016276));
+        assert_eq!(erf(10.0), 1.0);
+    }
+}
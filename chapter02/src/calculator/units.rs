@@ -0,0 +1,525 @@
+// calculator/units.rs - 物理单位与量纲分析
+
+use std::fmt;
+
+use super::value::real_result;
+use crate::utils::Error;
+
+/// 基本量纲的个数：长度、质量、时间、电流、温度、物质的量
+const BASE_DIMENSIONS: usize = 6;
+
+/// 各基本量纲在国际单位制中的单位，用于显示量纲
+const BASE_UNITS: [&str; BASE_DIMENSIONS] = ["m", "kg", "s", "A", "K", "mol"];
+
+/// 量纲：各基本量纲的指数
+///
+/// 角度（`rad`、`deg`）按国际单位制视为无量纲，因此 `sin(30 deg)` 可以直接计算。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Dimension([i32; BASE_DIMENSIONS]);
+
+impl Dimension {
+    /// 无量纲
+    pub const NONE: Dimension = Dimension([0, 0, 0, 0, 0, 0]);
+
+    /// 是否无量纲
+    pub fn is_dimensionless(&self) -> bool {
+        *self == Self::NONE
+    }
+
+    /// 量纲相乘（指数相加）
+    pub fn mul(&self, other: &Dimension) -> Dimension {
+        let mut result = *self;
+        for (a, b) in result.0.iter_mut().zip(other.0) {
+            *a += b;
+        }
+        result
+    }
+
+    /// 量纲的整数次幂
+    pub fn pow(&self, n: i32) -> Dimension {
+        Dimension(self.0.map(|e| e * n))
+    }
+}
+
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut unit = UnitExpr::new();
+        for (name, power) in BASE_UNITS.iter().zip(self.0) {
+            unit.push(name, power);
+        }
+        write!(f, "{}", unit)
+    }
+}
+
+/// 单位表中的一个单位
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnitDef {
+    /// 单位符号，如 `km`
+    pub name: &'static str,
+    /// 换算为国际单位制基本单位的倍数，如 `km` 为 1000
+    pub factor: f64,
+    /// 量纲
+    pub dimension: Dimension,
+}
+
+const fn unit(name: &'static str, factor: f64, dimension: [i32; BASE_DIMENSIONS]) -> UnitDef {
+    UnitDef {
+        name,
+        factor,
+        dimension: Dimension(dimension),
+    }
+}
+
+const LENGTH: [i32; BASE_DIMENSIONS] = [1, 0, 0, 0, 0, 0];
+const MASS: [i32; BASE_DIMENSIONS] = [0, 1, 0, 0, 0, 0];
+const TIME: [i32; BASE_DIMENSIONS] = [0, 0, 1, 0, 0, 0];
+const VOLUME: [i32; BASE_DIMENSIONS] = [3, 0, 0, 0, 0, 0];
+const ANGLE: [i32; BASE_DIMENSIONS] = [0; BASE_DIMENSIONS];
+
+/// 单位表（区分大小写）
+static UNITS: &[UnitDef] = &[
+    // 长度
+    unit("m", 1.0, LENGTH),
+    unit("km", 1000.0, LENGTH),
+    unit("cm", 0.01, LENGTH),
+    unit("mm", 0.001, LENGTH),
+    unit("in", 0.0254, LENGTH),
+    unit("ft", 0.3048, LENGTH),
+    unit("yd", 0.9144, LENGTH),
+    unit("mi", 1609.344, LENGTH),
+    // 质量
+    unit("kg", 1.0, MASS),
+    unit("g", 0.001, MASS),
+    unit("mg", 1e-6, MASS),
+    unit("lb", 0.45359237, MASS),
+    unit("oz", 0.028349523125, MASS),
+    // 时间
+    unit("s", 1.0, TIME),
+    unit("ms", 0.001, TIME),
+    unit("min", 60.0, TIME),
+    unit("h", 3600.0, TIME),
+    unit("day", 86400.0, TIME),
+    // 其余基本单位
+    unit("A", 1.0, [0, 0, 0, 1, 0, 0]),
+    unit("K", 1.0, [0, 0, 0, 0, 1, 0]),
+    unit("mol", 1.0, [0, 0, 0, 0, 0, 1]),
+    // 体积
+    unit("L", 1e-3, VOLUME),
+    unit("mL", 1e-6, VOLUME),
+    // 导出单位
+    unit("Hz", 1.0, [0, 0, -1, 0, 0, 0]),
+    unit("N", 1.0, [1, 1, -2, 0, 0, 0]),
+    unit("Pa", 1.0, [-1, 1, -2, 0, 0, 0]),
+    unit("J", 1.0, [2, 1, -2, 0, 0, 0]),
+    unit("W", 1.0, [2, 1, -3, 0, 0, 0]),
+    // 角度（无量纲）
+    unit("rad", 1.0, ANGLE),
+    unit("deg", std::f64::consts::PI / 180.0, ANGLE),
+    unit("grad", std::f64::consts::PI / 200.0, ANGLE),
+];
+
+/// 按符号查找单位
+pub fn lookup(name: &str) -> Option<&'static UnitDef> {
+    UNITS.iter().find(|unit| unit.name == name)
+}
+
+/// 是否是单位表中的单位
+pub fn is_unit(name: &str) -> bool {
+    lookup(name).is_some()
+}
+
+/// 单位表中的所有单位
+pub fn units() -> &'static [UnitDef] {
+    UNITS
+}
+
+/// 单位表达式，如 `m/s^2`：单位符号及其指数，按出现顺序保存
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct UnitExpr {
+    terms: Vec<(String, i32)>,
+}
+
+impl UnitExpr {
+    /// 空单位（无单位）
+    pub fn new() -> Self {
+        Self { terms: Vec::new() }
+    }
+
+    /// 单个单位，不在单位表中时返回 `Error::UnknownUnit`
+    pub fn parse(name: &str) -> Result<Self, Error> {
+        if !is_unit(name) {
+            return Err(Error::UnknownUnit(name.to_string()));
+        }
+        let mut unit = Self::new();
+        unit.push(name, 1);
+        Ok(unit)
+    }
+
+    /// 乘上单位 `name` 的 `power` 次幂，同名单位的指数合并
+    pub fn push(&mut self, name: &str, power: i32) {
+        match self.terms.iter().position(|(n, _)| n == name) {
+            Some(index) => {
+                self.terms[index].1 += power;
+                if self.terms[index].1 == 0 {
+                    self.terms.remove(index);
+                }
+            }
+            None if power != 0 => self.terms.push((name.to_string(), power)),
+            None => {}
+        }
+    }
+
+    /// 单位符号及其指数
+    pub fn terms(&self) -> &[(String, i32)] {
+        &self.terms
+    }
+
+    /// 是否无单位
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// 换算为国际单位制基本单位的倍数
+    pub fn factor(&self) -> f64 {
+        self.terms
+            .iter()
+            .filter_map(|(name, power)| lookup(name).map(|unit| unit.factor.powi(*power)))
+            .product()
+    }
+
+    /// 量纲
+    pub fn dimension(&self) -> Dimension {
+        self.terms
+            .iter()
+            .filter_map(|(name, power)| lookup(name).map(|unit| unit.dimension.pow(*power)))
+            .fold(Dimension::NONE, |acc, d| acc.mul(&d))
+    }
+
+    /// 单位相乘
+    pub fn mul(&self, other: &UnitExpr) -> UnitExpr {
+        let mut result = self.clone();
+        for (name, power) in &other.terms {
+            result.push(name, *power);
+        }
+        result
+    }
+
+    /// 单位的整数次幂
+    pub fn pow(&self, n: i32) -> UnitExpr {
+        let mut result = UnitExpr::new();
+        for (name, power) in &self.terms {
+            result.push(name, power * n);
+        }
+        result
+    }
+
+    /// 开 n 次方，指数不能整除时返回 None
+    pub fn root(&self, n: i32) -> Option<UnitExpr> {
+        let mut result = UnitExpr::new();
+        for (name, power) in &self.terms {
+            if power % n != 0 {
+                return None;
+            }
+            result.push(name, power / n);
+        }
+        Some(result)
+    }
+}
+
+impl fmt::Display for UnitExpr {
+    /// 显示为可以重新解析的形式，如 `kg*m/s^2`；没有正指数时写作 `s^-1`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.terms.is_empty() {
+            return write!(f, "1");
+        }
+        let term = |name: &str, power: i32| {
+            if power == 1 {
+                name.to_string()
+            } else {
+                format!("{}^{}", name, power)
+            }
+        };
+
+        let numerator: Vec<String> = self
+            .terms
+            .iter()
+            .filter(|(_, p)| *p > 0)
+            .map(|(name, p)| term(name, *p))
+            .collect();
+        if numerator.is_empty() {
+            let all: Vec<String> = self.terms.iter().map(|(n, p)| term(n, *p)).collect();
+            return write!(f, "{}", all.join("*"));
+        }
+
+        write!(f, "{}", numerator.join("*"))?;
+        for (name, power) in self.terms.iter().filter(|(_, p)| *p < 0) {
+            write!(f, "/{}", term(name, -power))?;
+        }
+        Ok(())
+    }
+}
+
+/// 带单位的数值
+///
+/// 数值按国际单位制基本单位保存，`unit` 决定显示和换算时使用的单位。
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quantity {
+    value: f64,
+    unit: UnitExpr,
+}
+
+impl Quantity {
+    /// 以 `unit` 为单位、大小为 `amount` 的量，如 `Quantity::new(20.0, cm)`
+    pub fn new(amount: f64, unit: UnitExpr) -> Self {
+        Self {
+            value: amount * unit.factor(),
+            unit,
+        }
+    }
+
+    /// 不带单位的普通数
+    pub fn plain(value: f64) -> Self {
+        Self {
+            value,
+            unit: UnitExpr::new(),
+        }
+    }
+
+    /// 按国际单位制基本单位表示的数值（角度为弧度）
+    pub fn si_value(&self) -> f64 {
+        self.value
+    }
+
+    /// 按自身单位表示的数值
+    pub fn amount(&self) -> f64 {
+        self.value / self.unit.factor()
+    }
+
+    /// 显示用的单位
+    pub fn unit(&self) -> &UnitExpr {
+        &self.unit
+    }
+
+    /// 量纲
+    pub fn dimension(&self) -> Dimension {
+        self.unit.dimension()
+    }
+
+    /// 换算为另一个单位，量纲不同时返回 `Error::DimensionMismatch`
+    pub fn convert(&self, unit: UnitExpr) -> Result<Quantity, Error> {
+        if unit.dimension() != self.dimension() {
+            return Err(mismatch(&self.unit, &unit));
+        }
+        Ok(Quantity {
+            value: self.value,
+            unit,
+        })
+    }
+
+    /// 二元运算：加减要求量纲相同，乘除合并单位，幂的指数必须是无量纲的整数
+    pub fn binary(op: char, left: &Quantity, right: &Quantity) -> Result<Quantity, Error> {
+        match op {
+            '+' | '-' => {
+                // 角度等无量纲单位也不能和普通数相加，否则普通数会被当作弧度
+                if left.dimension() != right.dimension()
+                    || left.unit.is_empty() != right.unit.is_empty()
+                {
+                    return Err(mismatch(&left.unit, &right.unit));
+                }
+                let value = if op == '+' {
+                    left.value + right.value
+                } else {
+                    left.value - right.value
+                };
+                // 结果使用左边的单位
+                Ok(Quantity {
+                    value,
+                    unit: left.unit.clone(),
+                })
+            }
+            '*' => Ok(Quantity {
+                value: left.value * right.value,
+                unit: left.unit.mul(&right.unit),
+            }),
+            '/' => {
+                if right.value == 0.0 {
+                    return Err(Error::DivisionByZero);
+                }
+                Ok(Quantity {
+                    value: left.value / right.value,
+                    unit: left.unit.mul(&right.unit.pow(-1)),
+                })
+            }
+            '^' => {
+                if !right.dimension().is_dimensionless() {
+                    return Err(mismatch(&right.unit, &UnitExpr::new()));
+                }
+                let exponent = right.value;
+                if left.dimension().is_dimensionless() {
+                    let value = left.value.powf(exponent);
+                    return real_result("^", &[left.value, exponent], value).map(Quantity::plain);
+                }
+                if exponent.fract() != 0.0 || exponent.abs() > i32::MAX as f64 {
+                    return Err(Error::Other(format!(
+                        "带单位 {} 的数值只能取整数次幂",
+                        left.unit
+                    )));
+                }
+                Ok(Quantity {
+                    value: left.value.powf(exponent),
+                    unit: left.unit.pow(exponent as i32),
+                })
+            }
+            _ => Err(Error::InvalidOperator),
+        }
+    }
+
+    /// 取相反数
+    pub fn neg(&self) -> Quantity {
+        Quantity {
+            value: -self.value,
+            unit: self.unit.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.amount(), self.unit)
+    }
+}
+
+/// 保留单位的内置函数；其余函数要求参数无量纲，返回 None
+pub(crate) fn quantity_function(name: &str, args: &[Quantity]) -> Option<Result<Quantity, Error>> {
+    let result = match (name, args) {
+        ("abs", [x]) => Ok(Quantity {
+            value: x.value.abs(),
+            unit: x.unit.clone(),
+        }),
+        ("sqrt", [x]) => match x.unit.root(2) {
+            _ if x.value < 0.0 => Err(Error::NegativeSquareRoot),
+            Some(unit) => Ok(Quantity {
+                value: x.value.sqrt(),
+                unit,
+            }),
+            None => Err(Error::Other(format!("无法对单位 {} 开平方", x.unit))),
+        },
+        ("hypot", [x, y]) if x.dimension() != y.dimension() => Err(mismatch(&x.unit, &y.unit)),
+        ("hypot", [x, y]) => Ok(Quantity {
+            value: x.value.hypot(y.value),
+            unit: x.unit.clone(),
+        }),
+        ("max" | "min", [first, rest @ ..]) => {
+            let mut best = first.clone();
+            for x in rest {
+                if x.dimension() != first.dimension() {
+                    return Some(Err(mismatch(&first.unit, &x.unit)));
+                }
+                let better = if name == "max" {
+                    x.value > best.value
+                } else {
+                    x.value < best.value
+                };
+                if better {
+                    best = x.clone();
+                }
+            }
+            Ok(best)
+        }
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// 量纲不匹配的错误
+pub(crate) fn mismatch(left: &UnitExpr, right: &UnitExpr) -> Error {
+    Error::DimensionMismatch {
+        left: left.to_string(),
+        right: right.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quantity(amount: f64, unit: &str) -> Quantity {
+        Quantity::new(amount, UnitExpr::parse(unit).unwrap())
+    }
+
+    #[test]
+    fn test_unit_expr() {
+        let mut accel = UnitExpr::parse("m").unwrap();
+        accel.push("s", -2);
+        assert_eq!(accel.to_string(), "m/s^2");
+        assert_eq!(accel.dimension().to_string(), "m/s^2");
+
+        let force = UnitExpr::parse("kg").unwrap().mul(&accel);
+        assert_eq!(force.to_string(), "kg*m/s^2");
+        assert_eq!(force.dimension(), lookup("N").unwrap().dimension);
+
+        assert_eq!(
+            UnitExpr::parse("Hz").unwrap().dimension().to_string(),
+            "s^-1"
+        );
+        assert_eq!(
+            UnitExpr::parse("furlong"),
+            Err(Error::UnknownUnit("furlong".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_quantity_arithmetic() {
+        let sum = Quantity::binary('+', &quantity(3.0, "m"), &quantity(20.0, "cm")).unwrap();
+        assert_eq!(sum.to_string(), "3.2 m");
+
+        let time = Quantity::binary('-', &quantity(1.0, "h"), &quantity(30.0, "min")).unwrap();
+        assert_eq!(time.amount(), 0.5);
+
+        assert_eq!(
+            Quantity::binary('+', &quantity(1.0, "m"), &quantity(1.0, "s")),
+            Err(Error::DimensionMismatch {
+                left: "m".to_string(),
+                right: "s".to_string(),
+            })
+        );
+        assert!(matches!(
+            Quantity::binary('+', &quantity(1.0, "deg"), &Quantity::plain(1.0)),
+            Err(Error::DimensionMismatch { .. })
+        ));
+
+        let area = Quantity::binary('^', &quantity(3.0, "m"), &Quantity::plain(2.0)).unwrap();
+        assert_eq!(area.to_string(), "9 m^2");
+        assert!(Quantity::binary('^', &quantity(3.0, "m"), &Quantity::plain(0.5)).is_err());
+    }
+
+    #[test]
+    fn test_convert() {
+        let miles = quantity(5.0, "km")
+            .convert(UnitExpr::parse("mi").unwrap())
+            .unwrap();
+        assert!((miles.amount() - 3.106855961186669).abs() < 1e-12);
+        assert!(quantity(5.0, "km")
+            .convert(UnitExpr::parse("kg").unwrap())
+            .is_err());
+
+        let degrees = Quantity::plain(std::f64::consts::PI)
+            .convert(UnitExpr::parse("deg").unwrap())
+            .unwrap();
+        assert!((degrees.amount() - 180.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_quantity_function() {
+        let area = Quantity::binary('^', &quantity(4.0, "m"), &Quantity::plain(2.0)).unwrap();
+        let side = quantity_function("sqrt", &[area]);
+        assert_eq!(side.unwrap().unwrap().to_string(), "4 m");
+        assert!(quantity_function("sqrt", &[quantity(2.0, "m")])
+            .unwrap()
+            .is_err());
+        assert_eq!(quantity_function("sin", &[quantity(30.0, "deg")]), None);
+
+        let diagonal = quantity_function("hypot", &[quantity(3.0, "m"), quantity(400.0, "cm")]);
+        assert_eq!(diagonal.unwrap().unwrap().to_string(), "5 m");
+    }
+}
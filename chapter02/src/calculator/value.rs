@@ -0,0 +1,700 @@
+// calculator/value.rs - 数值类型与计算模式
+
+use std::fmt;
+
+use num_bigint::BigInt;
+use num_complex::Complex64;
+use num_rational::BigRational;
+use num_traits::{Pow, Signed, ToPrimitive, Zero};
+
+use super::complex::{self, ComplexFormat};
+use super::decimal::Decimal;
+use super::units::Quantity;
+use crate::utils::Error;
+
+/// 精确幂运算允许的最大指数绝对值，超出后退回浮点数
+const MAX_EXACT_EXPONENT: i64 = 10_000;
+
+/// 精确幂运算结果允许的最大位数（约 30 万位十进制数），超出后退回浮点数
+const MAX_EXACT_BITS: u64 = 1 << 20;
+
+/// 检查浮点运算的结果：参数都不是 NaN 而结果是 NaN 时，说明参数超出了实数定义域，
+/// 返回 `Error::DomainError` 而不是把 NaN 当作结果
+pub(crate) fn real_result(function: &str, args: &[f64], result: f64) -> Result<f64, Error> {
+    if result.is_nan() && !args.iter().any(|x| x.is_nan()) {
+        let reason = if function == "^" {
+            "负数的非整数次幂在实数范围内无定义"
+        } else {
+            "结果不是实数"
+        };
+        return Err(Error::DomainError {
+            function: function.to_string(),
+            reason: reason.to_string(),
+        });
+    }
+    Ok(result)
+}
+
+/// 估计 base^exponent 的位数是否在 `MAX_EXACT_BITS` 以内
+pub(crate) fn exact_power_fits(base: &BigInt, exponent: u64) -> bool {
+    base.bits().saturating_mul(exponent) <= MAX_EXACT_BITS
+}
+
+/// 表达式的数值模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumberMode {
+    /// 双精度浮点数（默认）
+    #[default]
+    Float,
+    /// 精确有理数：分子分母都是任意精度整数
+    Rational,
+    /// 任意精度整数：整数运算精确，除不尽时得到精确的分数
+    Integer,
+    /// 任意精度十进制数，有效位数由 `Calculator::set_precision` 决定
+    Decimal,
+    /// 复数：识别虚数单位 `i`，`sqrt(-1)`、`ln(-2)` 等得到复数结果
+    Complex,
+}
+
+/// 三角函数使用的角度单位
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AngleMode {
+    /// 弧度（默认）
+    #[default]
+    Radians,
+    /// 角度，一周 360°
+    Degrees,
+    /// 百分度，一周 400 grad
+    Gradians,
+}
+
+impl AngleMode {
+    /// 半周（π 弧度）在该单位下的数值
+    pub fn half_turn(self) -> f64 {
+        match self {
+            AngleMode::Radians => std::f64::consts::PI,
+            AngleMode::Degrees => 180.0,
+            AngleMode::Gradians => 200.0,
+        }
+    }
+
+    /// 把该单位下的角度换算为弧度
+    pub fn to_radians(self, angle: f64) -> f64 {
+        match self {
+            AngleMode::Radians => angle,
+            _ => angle * (std::f64::consts::PI / self.half_turn()),
+        }
+    }
+
+    /// 把弧度换算为该单位下的角度
+    pub fn radians_to_mode(self, radians: f64) -> f64 {
+        match self {
+            AngleMode::Radians => radians,
+            _ => radians * (self.half_turn() / std::f64::consts::PI),
+        }
+    }
+}
+
+impl fmt::Display for AngleMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            AngleMode::Radians => "RAD",
+            AngleMode::Degrees => "DEG",
+            AngleMode::Gradians => "GRAD",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for NumberMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            NumberMode::Float => "float",
+            NumberMode::Rational => "rational",
+            NumberMode::Integer => "integer",
+            NumberMode::Decimal => "decimal",
+            NumberMode::Complex => "complex",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl std::str::FromStr for NumberMode {
+    type Err = Error;
+
+    /// 解析 `Display` 的输出（float、rational 等），不区分大小写
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s.to_ascii_lowercase().as_str() {
+            "float" => Ok(NumberMode::Float),
+            "rational" => Ok(NumberMode::Rational),
+            "integer" => Ok(NumberMode::Integer),
+            "decimal" => Ok(NumberMode::Decimal),
+            "complex" => Ok(NumberMode::Complex),
+            _ => Err(Error::Other(format!("未知的数值模式: {}", s))),
+        }
+    }
+}
+
+impl std::str::FromStr for AngleMode {
+    type Err = Error;
+
+    /// 解析 `Display` 的输出（RAD、DEG、GRAD），不区分大小写
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s.to_ascii_uppercase().as_str() {
+            "RAD" => Ok(AngleMode::Radians),
+            "DEG" => Ok(AngleMode::Degrees),
+            "GRAD" => Ok(AngleMode::Gradians),
+            _ => Err(Error::Other(format!("未知的角度模式: {}", s))),
+        }
+    }
+}
+
+/// 表达式求值的结果
+///
+/// 有理数模式下，加减乘除、整数次幂以及 `abs/floor/ceil/round/max/min`
+/// 都保持精确；其余运算（如 `sqrt`、`sin`）退回浮点数。
+/// 十进制模式下所有运算都按指定的有效位数计算。
+/// 复数只在虚部不为 0 时出现，虚部为 0 的结果化简为浮点数；
+/// 带单位的数值同理，单位全部约去后化简为浮点数。
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// 浮点数
+    Float(f64),
+    /// 任意精度整数
+    Integer(BigInt),
+    /// 精确有理数
+    Rational(BigRational),
+    /// 任意精度十进制数
+    Decimal(Decimal),
+    /// 复数
+    Complex(Complex64),
+    /// 带单位的数值
+    Quantity(Quantity),
+}
+
+impl Value {
+    /// 按数值模式转换数字字面量的原文
+    ///
+    /// 有理数模式下按十进制写法精确转换，因此 `0.1` 就是 1/10；
+    /// 十进制模式下按 `precision` 位有效数字舍入。
+    pub fn from_literal(text: &str, mode: NumberMode, precision: u32) -> Result<Value, Error> {
+        let value = match mode {
+            NumberMode::Float | NumberMode::Complex => text.parse().ok().map(Value::Float),
+            NumberMode::Rational => parse_rational(text).map(Value::Rational),
+            NumberMode::Integer => parse_rational(text).map(|r| {
+                if r.is_integer() {
+                    Value::Integer(r.to_integer())
+                } else {
+                    Value::Rational(r)
+                }
+            }),
+            NumberMode::Decimal => Decimal::parse(text, precision).map(Value::Decimal),
+        };
+        value.ok_or_else(|| Error::InvalidNumber(text.to_string()))
+    }
+
+    /// 由复数构造：虚部为 0 时化简为浮点数
+    pub fn complex(z: Complex64) -> Value {
+        if z.im == 0.0 {
+            Value::Float(z.re)
+        } else {
+            Value::Complex(z)
+        }
+    }
+
+    /// 由带单位的数值构造：没有单位时化简为浮点数
+    pub fn quantity(q: Quantity) -> Value {
+        if q.unit().is_empty() {
+            Value::Float(q.si_value())
+        } else {
+            Value::Quantity(q)
+        }
+    }
+
+    /// 转换为浮点数（虚部不为 0 的复数得到 NaN，带单位的数值按自身单位取值）
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Value::Float(n) => *n,
+            Value::Integer(i) => i.to_f64().unwrap_or(f64::NAN),
+            Value::Rational(r) => r.to_f64().unwrap_or(f64::NAN),
+            Value::Decimal(d) => d.to_f64(),
+            Value::Complex(z) if z.im == 0.0 => z.re,
+            Value::Complex(_) => f64::NAN,
+            Value::Quantity(q) => q.amount(),
+        }
+    }
+
+    /// 转换为实数，复数（即使虚部为 0）返回 `Error::ComplexResult`
+    pub fn to_real(&self) -> Result<f64, Error> {
+        match self {
+            Value::Complex(_) => Err(Error::ComplexResult(self.to_string())),
+            value => Ok(value.to_f64()),
+        }
+    }
+
+    /// 转换为带单位的数值（普通数没有单位）
+    pub fn to_quantity(&self) -> Result<Quantity, Error> {
+        match self {
+            Value::Quantity(q) => Ok(q.clone()),
+            Value::Complex(_) => Err(Error::Other("带单位的数值不支持复数".to_string())),
+            value => Ok(Quantity::plain(value.to_f64())),
+        }
+    }
+
+    /// 转换为复数
+    pub fn to_complex(&self) -> Complex64 {
+        match self {
+            Value::Complex(z) => *z,
+            value => Complex64::new(value.to_f64(), 0.0),
+        }
+    }
+
+    /// 是否为精确值
+    pub fn is_exact(&self) -> bool {
+        matches!(self, Value::Integer(_) | Value::Rational(_))
+    }
+
+    /// 是否为浮点数（即已经退回到 f64；复数和带单位的数值也按 f64 计算）
+    pub fn is_float(&self) -> bool {
+        matches!(
+            self,
+            Value::Float(_) | Value::Complex(_) | Value::Quantity(_)
+        )
+    }
+
+    /// 转换为有理数（只有精确值可以转换）
+    pub fn to_rational(&self) -> Option<BigRational> {
+        match self {
+            Value::Integer(i) => Some(BigRational::from_integer(i.clone())),
+            Value::Rational(r) => Some(r.clone()),
+            _ => None,
+        }
+    }
+
+    /// 转换为整数（值不是整数或是浮点数时返回 None）
+    pub fn to_integer(&self) -> Option<BigInt> {
+        let rational = match self {
+            Value::Float(_) | Value::Complex(_) | Value::Quantity(_) => return None,
+            Value::Integer(i) => return Some(i.clone()),
+            Value::Rational(r) => r.clone(),
+            Value::Decimal(d) => d.to_rational()?,
+        };
+        rational.is_integer().then(|| rational.to_integer())
+    }
+
+    /// 转换为十进制数（浮点数、复数和带单位的数值返回 None）
+    pub fn to_decimal(&self, precision: u32) -> Option<Decimal> {
+        match self {
+            Value::Float(_) | Value::Complex(_) | Value::Quantity(_) => None,
+            Value::Integer(i) => Some(Decimal::from_integer(i.clone(), precision)),
+            Value::Rational(r) => Some(Decimal::from_rational(r, precision)),
+            Value::Decimal(d) => Some(d.clone()),
+        }
+    }
+
+    /// 二元运算：有单位时检查量纲；有复数时按复数计算；有浮点数时按浮点数计算；
+    /// 有十进制数时按十进制计算；两边都是整数时按整数计算；否则按有理数精确计算
+    pub fn binary(op: char, left: &Value, right: &Value) -> Result<Value, Error> {
+        match (left, right) {
+            (Value::Quantity(_), _) | (_, Value::Quantity(_)) => {
+                let (a, b) = (left.to_quantity()?, right.to_quantity()?);
+                return Quantity::binary(op, &a, &b).map(Value::quantity);
+            }
+            (Value::Complex(_), _) | (_, Value::Complex(_)) => {
+                return complex::binary(op, left.to_complex(), right.to_complex())
+                    .map(Value::complex);
+            }
+            (Value::Float(_), _) | (_, Value::Float(_)) => {}
+            (Value::Decimal(d), _) | (_, Value::Decimal(d)) => {
+                let a = left.to_decimal(d.precision()).unwrap_or_else(|| d.clone());
+                let b = right.to_decimal(d.precision()).unwrap_or_else(|| d.clone());
+                if let Some(result) = decimal_binary(op, &a, &b)? {
+                    return Ok(Value::Decimal(result));
+                }
+            }
+            (Value::Integer(a), Value::Integer(b)) => {
+                if let Some(result) = integer_binary(op, a, b)? {
+                    return Ok(result);
+                }
+            }
+            _ => {
+                if let (Some(a), Some(b)) = (left.to_rational(), right.to_rational()) {
+                    if let Some(result) = rational_binary(op, &a, &b)? {
+                        return Ok(Value::Rational(result));
+                    }
+                }
+            }
+        }
+
+        let (a, b) = (left.to_f64(), right.to_f64());
+        match op {
+            '+' => Ok(Value::Float(a + b)),
+            '-' => Ok(Value::Float(a - b)),
+            '*' => Ok(Value::Float(a * b)),
+            '/' => {
+                if b == 0.0 {
+                    Err(Error::DivisionByZero)
+                } else {
+                    Ok(Value::Float(a / b))
+                }
+            }
+            '^' => real_result("^", &[a, b], a.powf(b)).map(Value::Float),
+            _ => Err(Error::InvalidOperator),
+        }
+    }
+
+    /// 取相反数
+    pub fn neg(&self) -> Value {
+        match self {
+            Value::Float(n) => Value::Float(-n),
+            Value::Integer(i) => Value::Integer(-i),
+            Value::Rational(r) => Value::Rational(-r),
+            Value::Decimal(d) => Value::Decimal(d.neg()),
+            Value::Complex(z) => Value::Complex(-z),
+            Value::Quantity(q) => Value::Quantity(q.neg()),
+        }
+    }
+
+    /// 按分数形式显示，如 `1/3`、`-5`；浮点数按原样显示
+    pub fn fraction(&self) -> String {
+        match self {
+            Value::Float(n) => n.to_string(),
+            Value::Integer(i) => i.to_string(),
+            Value::Rational(r) if r.is_integer() => r.numer().to_string(),
+            Value::Rational(r) => format!("{}/{}", r.numer(), r.denom()),
+            Value::Decimal(d) => d.to_string(),
+            Value::Complex(z) => complex::format(*z, ComplexFormat::Rectangular),
+            Value::Quantity(q) => q.to_string(),
+        }
+    }
+
+    /// 按复数格式显示：极坐标格式下实数也显示为模长和辐角
+    pub fn format(&self, format: ComplexFormat) -> String {
+        match (format, self) {
+            (ComplexFormat::Polar, value) if !matches!(value, Value::Quantity(_)) => {
+                complex::format(self.to_complex(), format)
+            }
+            _ => self.fraction(),
+        }
+    }
+
+    /// 按小数形式显示，保留至多 `digits` 位小数（四舍五入，去掉末尾的 0）
+    pub fn to_decimal_string(&self, digits: usize) -> String {
+        match self {
+            Value::Float(n) => trim_zeros(format!("{:.*}", digits, n)),
+            Value::Integer(i) => i.to_string(),
+            Value::Decimal(d) => match d.to_rational() {
+                Some(r) => Value::Rational(r).to_decimal_string(digits),
+                None => d.to_string(),
+            },
+            Value::Complex(z) => complex::format_with(*z, ComplexFormat::Rectangular, |x| {
+                Value::Float(x).to_decimal_string(digits)
+            }),
+            Value::Quantity(q) => format!(
+                "{} {}",
+                Value::Float(q.amount()).to_decimal_string(digits),
+                q.unit()
+            ),
+            Value::Rational(r) => {
+                let scale = BigInt::from(10).pow(digits);
+                let scaled = (r.abs() * BigRational::from_integer(scale.clone()))
+                    .round()
+                    .to_integer();
+                let sign = if r.is_negative() && !scaled.is_zero() {
+                    "-"
+                } else {
+                    ""
+                };
+                let integer = &scaled / &scale;
+                let fraction = format!(
+                    "{:0>width$}",
+                    (&scaled % &scale).to_string(),
+                    width = digits
+                );
+                if digits == 0 {
+                    format!("{}{}", sign, integer)
+                } else {
+                    trim_zeros(format!("{}{}.{}", sign, integer, fraction))
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.fraction())
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::Float(n)
+    }
+}
+
+/// 整数二元运算：除不尽或负整数次幂时得到有理数；结果无法精确表示时返回 None
+fn integer_binary(op: char, a: &BigInt, b: &BigInt) -> Result<Option<Value>, Error> {
+    let result = match op {
+        '+' => a + b,
+        '-' => a - b,
+        '*' => a * b,
+        '/' => {
+            if b.is_zero() {
+                return Err(Error::DivisionByZero);
+            }
+            if !(a % b).is_zero() {
+                return Ok(Some(Value::Rational(BigRational::new(
+                    a.clone(),
+                    b.clone(),
+                ))));
+            }
+            a / b
+        }
+        '^' => match b.to_u32() {
+            Some(e) if i64::from(e) <= MAX_EXACT_EXPONENT && exact_power_fits(a, e.into()) => {
+                a.pow(e)
+            }
+            _ => {
+                let (a, b) = (
+                    BigRational::from_integer(a.clone()),
+                    BigRational::from_integer(b.clone()),
+                );
+                return Ok(rational_binary(op, &a, &b)?.map(Value::Rational));
+            }
+        },
+        _ => return Err(Error::InvalidOperator),
+    };
+    Ok(Some(Value::Integer(result)))
+}
+
+/// 精确计算有理数二元运算；结果无法精确表示时返回 None
+fn rational_binary(
+    op: char,
+    a: &BigRational,
+    b: &BigRational,
+) -> Result<Option<BigRational>, Error> {
+    let result = match op {
+        '+' => a + b,
+        '-' => a - b,
+        '*' => a * b,
+        '/' => {
+            if b.is_zero() {
+                return Err(Error::DivisionByZero);
+            }
+            a / b
+        }
+        '^' => {
+            // 只有整数次幂是精确的
+            if !b.is_integer() {
+                return Ok(None);
+            }
+            let exponent = b.to_integer().to_i64().filter(|e| {
+                let magnitude = e.unsigned_abs();
+                e.abs() <= MAX_EXACT_EXPONENT
+                    && exact_power_fits(a.numer(), magnitude)
+                    && exact_power_fits(a.denom(), magnitude)
+            });
+            // 结果过大时不做精确计算，避免分配无法满足的内存；只有浮点数结果有限（如趋于 0）
+            // 时才退回浮点数，否则报错而不是得到无穷大
+            let Some(exponent) = exponent else {
+                return match (a.to_f64(), b.to_f64()) {
+                    (Some(x), Some(y)) if x.powf(y).is_finite() => Ok(None),
+                    _ => Err(Error::ResultOverflow("^".to_string())),
+                };
+            };
+            if exponent < 0 && a.is_zero() {
+                return Err(Error::DivisionByZero);
+            }
+            a.pow(exponent as i32)
+        }
+        _ => return Err(Error::InvalidOperator),
+    };
+    Ok(Some(result))
+}
+
+/// 十进制二元运算；结果不是实数时返回 None
+fn decimal_binary(op: char, a: &Decimal, b: &Decimal) -> Result<Option<Decimal>, Error> {
+    match op {
+        '+' => Ok(Some(a.add(b))),
+        '-' => Ok(Some(a.sub(b))),
+        '*' => Ok(Some(a.mul(b))),
+        '/' => a.div(b).map(Some),
+        '^' => a.pow(b),
+        _ => Err(Error::InvalidOperator),
+    }
+}
+
+/// 精确计算支持有理数的内置函数；不支持时返回 None
+pub(crate) fn rational_function(name: &str, args: &[BigRational]) -> Option<BigRational> {
+    match (name, args) {
+        ("abs", [x]) => Some(x.abs()),
+        ("floor", [x]) => Some(x.floor()),
+        ("ceil", [x]) => Some(x.ceil()),
+        ("round", [x]) => Some(x.round()),
+        ("trunc", [x]) => Some(x.trunc()),
+        ("sign", [x]) => Some(x.signum()),
+        ("max", [first, rest @ ..]) => Some(rest.iter().fold(first, |a, b| a.max(b)).clone()),
+        ("min", [first, rest @ ..]) => Some(rest.iter().fold(first, |a, b| a.min(b)).clone()),
+        // 向下取整的整除与取模，余数与除数同号
+        ("idiv", [a, b]) if !b.is_zero() => Some((a / b).floor()),
+        ("mod", [a, b]) if !b.is_zero() => Some(a - b * (a / b).floor()),
+        _ => None,
+    }
+}
+
+/// 解析十进制字符串（如 `-12.5`）为有理数
+fn parse_rational(text: &str) -> Option<BigRational> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    if integer.is_empty() && fraction.is_empty() {
+        return None;
+    }
+
+    let numer: BigInt = format!("{}{}", integer, fraction).parse().ok()?;
+    let denom = BigInt::from(10).pow(fraction.len());
+    let value = BigRational::new(numer, denom);
+    Some(if negative { -value } else { value })
+}
+
+/// 去掉小数末尾多余的 0 和小数点
+fn trim_zeros(text: String) -> String {
+    if !text.contains('.') {
+        return text;
+    }
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ratio(n: i64, d: i64) -> Value {
+        Value::Rational(BigRational::new(n.into(), d.into()))
+    }
+
+    #[test]
+    fn test_literal_is_exact() {
+        let value = Value::from_literal("0.1", NumberMode::Rational, 10).unwrap();
+        assert_eq!(value, ratio(1, 10));
+        assert_eq!(
+            Value::from_literal("0.1", NumberMode::Float, 10).unwrap(),
+            Value::Float(0.1)
+        );
+
+        let value = Value::from_literal("3.14159265358979323846", NumberMode::Decimal, 30).unwrap();
+        assert_eq!(value.to_string(), "3.14159265358979323846");
+    }
+
+    #[test]
+    fn test_rational_arithmetic() {
+        let third = Value::binary('/', &ratio(1, 1), &ratio(3, 1)).unwrap();
+        let one = Value::binary('*', &third, &ratio(3, 1)).unwrap();
+        assert_eq!(one, ratio(1, 1));
+
+        let sum = Value::binary('+', &ratio(1, 10), &ratio(2, 10)).unwrap();
+        assert_eq!(sum, ratio(3, 10));
+
+        assert_eq!(
+            Value::binary('/', &ratio(1, 1), &ratio(0, 1)),
+            Err(Error::DivisionByZero)
+        );
+    }
+
+    #[test]
+    fn test_rational_power() {
+        assert_eq!(
+            Value::binary('^', &ratio(2, 3), &ratio(-2, 1)).unwrap(),
+            ratio(9, 4)
+        );
+        // 非整数次幂退回浮点数
+        let root = Value::binary('^', &ratio(4, 1), &ratio(1, 2)).unwrap();
+        assert_eq!(root, Value::Float(2.0));
+
+        // 结果过大时报错，而不是耗尽内存或得到无穷大
+        let overflow = Err(Error::ResultOverflow("^".to_string()));
+        let huge = Value::binary('^', &ratio(2, 1), &ratio(10_000, 1)).unwrap();
+        assert!(matches!(huge, Value::Rational(_)));
+        assert_eq!(Value::binary('^', &huge, &ratio(10_000, 1)), overflow);
+        assert_eq!(
+            Value::binary('^', &ratio(2, 1), &ratio(100_000_000, 1)),
+            overflow
+        );
+        let tiny = Value::binary('^', &ratio(1, 3), &ratio(-10_000, 1)).unwrap();
+        assert!(matches!(tiny, Value::Rational(_)));
+        // 浮点数结果有限时仍退回浮点数
+        assert_eq!(
+            Value::binary('^', &ratio(1, 2), &ratio(100_000_000, 1)),
+            Ok(Value::Float(0.0))
+        );
+    }
+
+    #[test]
+    fn test_integer_arithmetic() {
+        let int = |n: i64| Value::Integer(BigInt::from(n));
+        assert_eq!(Value::binary('*', &int(6), &int(7)).unwrap(), int(42));
+        assert_eq!(Value::binary('/', &int(8), &int(2)).unwrap(), int(4));
+        assert_eq!(Value::binary('/', &int(7), &int(2)).unwrap(), ratio(7, 2));
+        assert_eq!(Value::binary('^', &int(2), &int(-1)).unwrap(), ratio(1, 2));
+        assert_eq!(
+            Value::binary('^', &int(2), &int(100)).unwrap().to_string(),
+            "1267650600228229401496703205376"
+        );
+        let huge = Value::binary('^', &int(2), &int(10_000)).unwrap();
+        assert!(matches!(huge, Value::Integer(_)));
+        let overflow = Err(Error::ResultOverflow("^".to_string()));
+        assert_eq!(Value::binary('^', &huge, &int(10_000)), overflow);
+        assert_eq!(Value::binary('^', &int(2), &int(10_000_000_000)), overflow);
+        assert_eq!(
+            Value::binary('+', &int(1), &ratio(1, 2)).unwrap(),
+            ratio(3, 2)
+        );
+    }
+
+    #[test]
+    fn test_complex_arithmetic() {
+        let i = Value::Complex(Complex64::new(0.0, 1.0));
+        // i * i 的虚部为 0，化简为实数
+        assert_eq!(Value::binary('*', &i, &i).unwrap(), Value::Float(-1.0));
+
+        let z = Value::binary('+', &Value::Float(3.0), &i).unwrap();
+        assert_eq!(z.to_string(), "3 + i");
+        assert_eq!(z.neg().to_string(), "-3 - i");
+        assert!(z.to_f64().is_nan());
+        assert_eq!(
+            Value::binary('/', &i, &Value::Integer(BigInt::zero())),
+            Err(Error::DivisionByZero)
+        );
+        assert_eq!(
+            Value::Complex(Complex64::new(1.0 / 3.0, 2.0)).to_decimal_string(4),
+            "0.3333 + 2i"
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(ratio(-2, 6).to_string(), "-1/3");
+        assert_eq!(ratio(4, 2).to_string(), "2");
+        assert_eq!(ratio(1, 3).to_decimal_string(5), "0.33333");
+        assert_eq!(ratio(2, 3).to_decimal_string(3), "0.667");
+        assert_eq!(ratio(-1, 4).to_decimal_string(10), "-0.25");
+        assert_eq!(Value::Float(0.5).to_decimal_string(4), "0.5");
+    }
+
+    #[test]
+    fn test_rational_function() {
+        let x = BigRational::new((-7).into(), 2.into());
+        assert_eq!(
+            rational_function("floor", std::slice::from_ref(&x)),
+            Some(BigRational::from_integer((-4).into()))
+        );
+        assert_eq!(
+            rational_function("round", std::slice::from_ref(&x)),
+            Some(BigRational::from_integer((-4).into()))
+        );
+        assert_eq!(rational_function("sqrt", &[x]), None);
+    }
+}
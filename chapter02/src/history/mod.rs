@@ -1,40 +1,219 @@
 // history/mod.rs - 历史记录管理模块
 
+pub mod storage;
+
+use std::collections::VecDeque;
 use std::fmt;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+
+use crate::calculator::evaluator::DEFAULT_PRECISION;
+use crate::calculator::{AngleMode, Calculator, NumberMode, Outcome, ResultRef, Value};
+use crate::utils::Error;
+use storage::{Journal, Snapshot};
 
 /// 历史记录条目
 #[derive(Debug, Clone)]
 pub struct HistoryRecord {
     /// 表达式字符串
     pub expression: String,
-    /// 计算结果
-    pub result: f64,
+    /// 计算结果，求值失败时为错误
+    pub result: Result<f64, Error>,
+    /// 有理数、整数和十进制模式下保留精确值的结果（其他记录为 None）
+    pub exact: Option<Value>,
     /// 记录索引
     pub index: usize,
+    /// 计算时的角度模式
+    pub angle_mode: AngleMode,
+    /// 计算时的数值模式
+    pub number_mode: NumberMode,
+    /// 计算时的精度（十进制模式下的有效位数）
+    pub precision: u32,
+    /// 记录时间（从没有时间的旧版本文件读入的记录为 None）
+    pub timestamp: Option<DateTime<Local>>,
+    /// 求值耗时（未计时的记录为 0）
+    pub duration: Duration,
+    /// 用户标签
+    pub tags: Vec<String>,
+    /// 备注
+    pub note: Option<String>,
+    /// 是否固定（`EvictionPolicy::KeepPinned` 下不会被淘汰）
+    pub pinned: bool,
 }
 
 impl HistoryRecord {
-    /// 创建新的历史记录
+    /// 创建新的历史记录（角度模式为弧度）
     pub fn new(expression: String, result: f64, index: usize) -> Self {
+        Self::with_angle_mode(expression, result, index, AngleMode::Radians)
+    }
+
+    /// 创建记录了角度模式的历史记录
+    pub fn with_angle_mode(
+        expression: String,
+        result: f64,
+        index: usize,
+        angle_mode: AngleMode,
+    ) -> Self {
+        Self::with_result(expression, Ok(result), index, angle_mode)
+    }
+
+    /// 由按数值模式求出的结果创建历史记录，精确值另外保存在 `exact` 中
+    ///
+    /// 复数结果按 `Value::to_real` 记为 `Error::ComplexResult`。
+    pub fn with_value(
+        expression: String,
+        result: Result<Value, Error>,
+        index: usize,
+        angle_mode: AngleMode,
+    ) -> Self {
+        let exact = result
+            .as_ref()
+            .ok()
+            .filter(|value| !value.is_float())
+            .cloned();
+        Self {
+            exact,
+            ..Self::with_result(
+                expression,
+                result.and_then(|value| value.to_real()),
+                index,
+                angle_mode,
+            )
+        }
+    }
+
+    /// 创建成功或失败的历史记录
+    pub fn with_result(
+        expression: String,
+        result: Result<f64, Error>,
+        index: usize,
+        angle_mode: AngleMode,
+    ) -> Self {
         Self {
             expression,
             result,
+            exact: None,
             index,
+            angle_mode,
+            number_mode: NumberMode::Float,
+            precision: DEFAULT_PRECISION,
+            timestamp: Some(Local::now()),
+            duration: Duration::ZERO,
+            tags: Vec::new(),
+            note: None,
+            pinned: false,
         }
     }
+
+    /// 求值是否成功
+    pub fn is_ok(&self) -> bool {
+        self.result.is_ok()
+    }
+
+    /// 成功时的结果
+    pub fn value(&self) -> Option<f64> {
+        self.result.as_ref().ok().copied()
+    }
+
+    /// 成功时的结果，有精确值时返回精确值
+    pub fn exact_value(&self) -> Option<Value> {
+        let value = self.value()?;
+        Some(self.exact.clone().unwrap_or(Value::Float(value)))
+    }
+
+    /// 失败时的错误
+    pub fn error(&self) -> Option<&Error> {
+        self.result.as_ref().err()
+    }
+
+    /// 是否带有指定的标签
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
 }
 
 impl fmt::Display for HistoryRecord {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "[{}] {} = {}", self.index, self.expression, self.result)
+        match &self.result {
+            Ok(value) => write!(f, "[{}] {} = {}", self.index, self.expression, value)?,
+            Err(error) => write!(f, "[{}] {} ✗ {}", self.index, self.expression, error)?,
+        }
+        // 弧度、浮点数和默认精度是默认设置，只标注其他设置
+        let mut modes = Vec::new();
+        if self.angle_mode != AngleMode::Radians {
+            modes.push(self.angle_mode.to_string());
+        }
+        if self.number_mode != NumberMode::Float {
+            modes.push(self.number_mode.to_string());
+        }
+        if self.precision != DEFAULT_PRECISION {
+            modes.push(format!("{} 位", self.precision));
+        }
+        if !modes.is_empty() {
+            write!(f, " ({})", modes.join(", "))?;
+        }
+        for tag in &self.tags {
+            write!(f, " #{}", tag)?;
+        }
+        if let Some(note) = &self.note {
+            write!(f, " // {}", note)?;
+        }
+        Ok(())
+    }
+}
+
+/// 记录数超过容量时选择淘汰哪一条
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EvictionPolicy {
+    /// 淘汰最旧的记录
+    #[default]
+    DropOldest,
+    /// 先淘汰最旧的失败记录，没有失败记录时淘汰最旧的记录
+    DropFailuresFirst,
+    /// 淘汰最旧的未固定记录（最新的一条除外）；其余记录全部固定时不淘汰，记录数可以超过容量
+    KeepPinned,
+}
+
+impl fmt::Display for EvictionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            EvictionPolicy::DropOldest => "drop_oldest",
+            EvictionPolicy::DropFailuresFirst => "drop_failures_first",
+            EvictionPolicy::KeepPinned => "keep_pinned",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl std::str::FromStr for EvictionPolicy {
+    type Err = Error;
+
+    /// 解析 `Display` 的输出（drop_oldest 等），不区分大小写
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s.to_ascii_lowercase().as_str() {
+            "drop_oldest" => Ok(EvictionPolicy::DropOldest),
+            "drop_failures_first" => Ok(EvictionPolicy::DropFailuresFirst),
+            "keep_pinned" => Ok(EvictionPolicy::KeepPinned),
+            _ => Err(Error::Other(format!("未知的淘汰策略: {}", s))),
+        }
     }
 }
 
 /// 历史记录管理器
 pub struct HistoryManager {
-    records: Vec<HistoryRecord>,
+    /// 按编号严格递增排列的记录：淘汰最旧的记录不必移动其余记录，按编号查找用二分查找
+    records: VecDeque<HistoryRecord>,
+    /// 失败记录的编号（递增），用于先淘汰失败记录
+    failed: VecDeque<usize>,
     max_records: usize,
     next_index: usize,
+    eviction_policy: EvictionPolicy,
+    /// 日志模式下每条新记录立即追加到日志文件
+    journal: Option<Journal>,
+    /// 最近一次写日志失败的错误
+    journal_error: Option<Error>,
 }
 
 impl HistoryManager {
@@ -46,59 +225,294 @@ impl HistoryManager {
     /// 创建指定容量的历史记录管理器
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
-            records: Vec::new(),
+            records: VecDeque::new(),
+            failed: VecDeque::new(),
             max_records: capacity,
             next_index: 1,
+            eviction_policy: EvictionPolicy::DropOldest,
+            journal: None,
+            journal_error: None,
+        }
+    }
+
+    /// 设置淘汰策略，只影响之后的淘汰
+    ///
+    /// 策略随 `save` 保存；日志模式下写入日志的文件头，重新打开时按同样的策略截断。
+    pub fn set_eviction_policy(&mut self, policy: EvictionPolicy) {
+        self.eviction_policy = policy;
+        if let Some(journal) = &mut self.journal {
+            journal.eviction_policy = policy;
+            if let Err(error) = journal.rewrite(&self.records) {
+                self.journal_error = Some(error);
+            }
         }
     }
 
-    /// 添加新的历史记录
+    /// 当前的淘汰策略
+    pub fn eviction_policy(&self) -> EvictionPolicy {
+        self.eviction_policy
+    }
+
+    /// 添加新的历史记录（角度模式为弧度）
     pub fn add_record(&mut self, expression: &str, result: f64) {
-        let record = HistoryRecord::new(expression.to_string(), result, self.next_index);
+        self.add_record_with_mode(expression, result, AngleMode::Radians);
+    }
+
+    /// 添加新的历史记录，并记录计算时的角度模式
+    pub fn add_record_with_mode(&mut self, expression: &str, result: f64, angle_mode: AngleMode) {
+        let record = HistoryRecord::with_angle_mode(expression.to_string(), result, 0, angle_mode);
+        self.add(record);
+    }
+
+    /// 添加求值失败的记录（角度模式为弧度）
+    pub fn add_error(&mut self, expression: &str, error: Error) {
+        let record =
+            HistoryRecord::with_result(expression.to_string(), Err(error), 0, AngleMode::Radians);
+        self.add(record);
+    }
 
-        self.records.push(record);
+    /// 求值表达式并记录结果（或错误）、耗时以及计算时的角度模式、数值模式和精度
+    ///
+    /// 表达式中可以用 `ans`（或 `_`）引用最近一次成功的结果，用 `$n` 引用编号为 n 的记录。
+    /// 函数定义（如 `f(x) = x^2`）没有数值，不记入历史，返回 `Ok(None)`。
+    pub fn evaluate_and_record(
+        &mut self,
+        calc: &Calculator,
+        expression: &str,
+    ) -> Result<Option<f64>, Error> {
+        let start = Instant::now();
+        let result = match calc.execute_with_recall(expression, |reference| self.recall(reference))
+        {
+            Ok(Outcome::Defined(_)) => return Ok(None),
+            Ok(Outcome::Value(value)) => Ok(value),
+            Err(error) => Err(error),
+        };
+        let duration = start.elapsed();
+
+        let record = HistoryRecord {
+            number_mode: calc.number_mode(),
+            precision: calc.precision() as u32,
+            duration,
+            ..HistoryRecord::with_value(expression.to_string(), result, 0, calc.angle_mode())
+        };
+        let result = record.result.clone();
+        self.add(record);
+        result.map(Some)
+    }
+
+    /// 添加一条完整的记录（保留时间、标签等元数据），编号由管理器重新分配，返回新编号
+    pub fn add(&mut self, mut record: HistoryRecord) -> usize {
+        // 读入记录时保证了 next_index 大于已有的全部编号，新记录追加在末尾仍然有序
+        debug_assert!(self
+            .records
+            .back()
+            .is_none_or(|r| r.index < self.next_index));
+        record.index = self.next_index;
+        if let Some(journal) = &mut self.journal {
+            if let Err(error) = journal.append(&record) {
+                self.journal_error = Some(error);
+            }
+        }
+        if !record.is_ok() {
+            self.failed.push_back(record.index);
+        }
+        self.records.push_back(record);
         self.next_index += 1;
 
         // 保持记录数量限制
-        if self.records.len() > self.max_records {
-            self.records.remove(0);
-        }
+        self.evict();
+        self.next_index - 1
+    }
+
+    /// 为指定编号的记录添加标签（已有的标签不重复添加），记录不存在时返回 false
+    pub fn add_tag(&mut self, index: usize, tag: &str) -> bool {
+        self.update(index, |record| {
+            if !record.has_tag(tag) {
+                record.tags.push(tag.to_string());
+            }
+        })
+    }
+
+    /// 设置指定编号的记录的备注（None 表示删除备注），记录不存在时返回 false
+    pub fn set_note(&mut self, index: usize, note: Option<&str>) -> bool {
+        self.update(index, |record| record.note = note.map(str::to_string))
+    }
+
+    /// 固定或取消固定指定编号的记录，记录不存在时返回 false
+    pub fn set_pinned(&mut self, index: usize, pinned: bool) -> bool {
+        self.update(index, |record| record.pinned = pinned)
     }
 
-    /// 获取最近的 n 条记录
-    pub fn get_recent_records(&self, count: usize) -> &[HistoryRecord] {
-        let start = if self.records.len() > count {
-            self.records.len() - count
-        } else {
-            0
+    /// 修改指定编号的记录；日志模式下把修改后的记录追加到日志，重新打开时覆盖旧的一行
+    fn update<F>(&mut self, index: usize, f: F) -> bool
+    where
+        F: FnOnce(&mut HistoryRecord),
+    {
+        let Some(position) = self.position(index) else {
+            return false;
         };
-        &self.records[start..]
+        let record = &mut self.records[position];
+        f(record);
+        if let Some(journal) = &mut self.journal {
+            if let Err(error) = journal.append(record) {
+                self.journal_error = Some(error);
+            }
+        }
+        true
+    }
+
+    /// 获取最近的 n 条记录（按编号递增）
+    pub fn get_recent_records(
+        &self,
+        count: usize,
+    ) -> impl DoubleEndedIterator<Item = &HistoryRecord> + ExactSizeIterator {
+        self.records
+            .range(self.records.len().saturating_sub(count)..)
     }
 
-    /// 获取所有记录
-    pub fn get_all_records(&self) -> &[HistoryRecord] {
-        &self.records
+    /// 获取所有记录（按编号递增）
+    ///
+    /// 记录存放在环形缓冲区中，不保证连续，因此返回迭代器而不是切片。
+    pub fn get_all_records(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = &HistoryRecord> + ExactSizeIterator {
+        self.records.iter()
     }
 
-    /// 搜索包含指定查询字符串的记录
+    /// 搜索表达式、结果或错误信息中包含指定查询字符串的记录
     pub fn search_records(&self, query: &str) -> Vec<&HistoryRecord> {
         self.records
             .iter()
             .filter(|record| {
-                record.expression.contains(query) || record.result.to_string().contains(query)
+                record.expression.contains(query)
+                    || match &record.result {
+                        Ok(value) => value.to_string().contains(query),
+                        Err(error) => error.to_string().contains(query),
+                    }
+            })
+            .collect()
+    }
+
+    /// 求值成功的记录
+    pub fn successes(&self) -> Vec<&HistoryRecord> {
+        self.records.iter().filter(|r| r.is_ok()).collect()
+    }
+
+    /// 求值失败的记录
+    pub fn failures(&self) -> Vec<&HistoryRecord> {
+        self.records.iter().filter(|r| !r.is_ok()).collect()
+    }
+
+    /// 按错误种类（见 [`Error::kind`]）统计失败次数，按次数从多到少排列
+    pub fn error_counts(&self) -> Vec<(&str, usize)> {
+        let mut counts: Vec<(&str, usize)> = Vec::new();
+        for error in self.records.iter().filter_map(HistoryRecord::error) {
+            match counts.iter_mut().find(|(kind, _)| *kind == error.kind()) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((error.kind(), 1)),
+            }
+        }
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        counts
+    }
+
+    /// 查找记录时间在 [start, end) 内的记录（没有记录时间的记录不在其中）
+    pub fn records_between(
+        &self,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> Vec<&HistoryRecord> {
+        self.records
+            .iter()
+            .filter(|record| {
+                record
+                    .timestamp
+                    .is_some_and(|time| start <= time && time < end)
             })
             .collect()
     }
 
+    /// 查找带有指定标签的记录
+    pub fn records_with_tag(&self, tag: &str) -> Vec<&HistoryRecord> {
+        self.records.iter().filter(|r| r.has_tag(tag)).collect()
+    }
+
+    /// 查找求值耗时超过 threshold 的记录
+    pub fn records_slower_than(&self, threshold: Duration) -> Vec<&HistoryRecord> {
+        self.records
+            .iter()
+            .filter(|record| record.duration > threshold)
+            .collect()
+    }
+
+    /// 查出历史引用对应的数值，供 `Calculator::evaluate_with_recall` 使用
+    ///
+    /// 有精确值的记录返回精确值；`ans` 跳过失败的记录；`$n` 指向的记录不存在、已被删除或超出容量、求值失败时返回
+    /// `Error::HistoryReference`。
+    pub fn recall(&self, reference: ResultRef) -> Result<Value, Error> {
+        let error = |reason: String| Error::HistoryReference {
+            reference: reference.to_string(),
+            reason,
+        };
+        match reference {
+            ResultRef::Last => self
+                .records
+                .iter()
+                .rev()
+                .find_map(HistoryRecord::exact_value)
+                .ok_or_else(|| error("还没有成功的计算结果".to_string())),
+            ResultRef::Index(index) => match self.get_record_by_index(index) {
+                Some(record) => record
+                    .result
+                    .as_ref()
+                    .map(|&n| record.exact.clone().unwrap_or(Value::Float(n)))
+                    .map_err(|e| error(format!("该记录求值失败（{}）", e))),
+                None if index == 0 || index >= self.next_index => {
+                    Err(error(format!("记录编号 {} 不存在", index)))
+                }
+                None => Err(error("该记录已被删除或超出历史容量".to_string())),
+            },
+        }
+    }
+
     /// 获取指定索引的记录
     pub fn get_record_by_index(&self, index: usize) -> Option<&HistoryRecord> {
-        self.records.iter().find(|r| r.index == index)
+        self.position(index).map(|position| &self.records[position])
+    }
+
+    /// 指定编号的记录在 `records` 中的位置
+    fn position(&self, index: usize) -> Option<usize> {
+        self.records
+            .binary_search_by_key(&index, |record| record.index)
+            .ok()
+    }
+
+    /// 删除指定编号的记录（日志模式下同时重写日志文件），记录不存在时返回 false
+    ///
+    /// 其他记录的编号不变，之后引用 `$index` 会报错。
+    pub fn remove_record(&mut self, index: usize) -> bool {
+        let Some(position) = self.position(index) else {
+            return false;
+        };
+        self.remove_at(position);
+        if let Some(journal) = &mut self.journal {
+            if let Err(error) = journal.rewrite(&self.records) {
+                self.journal_error = Some(error);
+            }
+        }
+        true
     }
 
-    /// 清除所有历史记录
+    /// 清除所有历史记录（日志模式下同时清空日志文件）
     pub fn clear(&mut self) {
         self.records.clear();
+        self.failed.clear();
         self.next_index = 1;
+        if let Some(journal) = &mut self.journal {
+            if let Err(error) = journal.rewrite(&self.records) {
+                self.journal_error = Some(error);
+            }
+        }
     }
 
     /// 获取记录总数
@@ -111,6 +525,140 @@ impl HistoryManager {
         self.records.is_empty()
     }
 
+    // ===== 持久化 =====
+
+    /// 保存为版本化的 JSON 文件
+    ///
+    /// 先写入临时文件再改名，保存过程中崩溃不会损坏已有的文件。
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let snapshot = Snapshot {
+            records: self.records.iter().cloned().collect(),
+            max_records: self.max_records,
+            next_index: self.next_index,
+            eviction_policy: self.eviction_policy,
+        };
+        storage::save_json(path.as_ref(), &snapshot)
+    }
+
+    /// 从 `save` 保存的 JSON 文件恢复历史记录
+    ///
+    /// 文件无法读取时返回 `Error::IoError`；损坏、被截断或版本过高时返回 `Error::HistoryFormat`。
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let snapshot = storage::load_json(path.as_ref())?;
+        let mut manager = Self::with_capacity(snapshot.max_records);
+        manager.next_index = snapshot.next_index;
+        manager.eviction_policy = snapshot.eviction_policy;
+        manager
+            .replace_records(snapshot.records)
+            .map_err(|reason| storage::format_error(path.as_ref(), reason))?;
+        Ok(manager)
+    }
+
+    /// 导出为 CSV 文件，列见 [`storage::CSV_HEADER`]，时间为 RFC 3339 格式，多个标签用 `;` 分隔
+    pub fn export_csv(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        storage::export_csv(path.as_ref(), &self.records)
+    }
+
+    /// 从 CSV 文件导入记录，追加在已有记录之后并重新编号，返回导入的条数
+    ///
+    /// 只要有一行无效就不导入任何记录。
+    pub fn import_csv(&mut self, path: impl AsRef<Path>) -> Result<usize, Error> {
+        let records = storage::import_csv(path.as_ref())?;
+        let count = records.len();
+        for record in records {
+            self.add(record);
+        }
+        Ok(count)
+    }
+
+    /// 以日志模式打开历史记录：读出日志文件中已有的记录，之后每条新记录立即追加到文件
+    ///
+    /// 打开时会按日志中保存的淘汰策略压缩日志，去掉超出容量的旧记录以及崩溃时写了一半的最后一行。
+    pub fn open_journal(path: impl AsRef<Path>, capacity: usize) -> Result<Self, Error> {
+        let (mut journal, records) = Journal::open(path.as_ref())?;
+        let mut manager = Self::with_capacity(capacity);
+        manager.eviction_policy = journal.eviction_policy;
+        // 日志中的记录已按编号排序
+        let last_index = records.last().map_or(0, |r| r.index);
+        manager.next_index = storage::next_index_after(last_index)
+            .map_err(|reason| storage::format_error(path.as_ref(), reason))?;
+        manager
+            .replace_records(records)
+            .map_err(|reason| storage::format_error(path.as_ref(), reason))?;
+        journal.rewrite(&manager.records)?;
+        manager.journal = Some(journal);
+        Ok(manager)
+    }
+
+    /// 最近一次写日志失败的错误（添加记录本身不会因此失败）
+    pub fn journal_error(&self) -> Option<&Error> {
+        self.journal_error.as_ref()
+    }
+
+    /// 用读出的记录替换全部记录，按编号排序后按淘汰策略截断到容量
+    ///
+    /// 编号重复或不小于 `next_index` 时返回错误，保证按编号二分查找和追加新记录都是正确的。
+    fn replace_records(&mut self, mut records: Vec<HistoryRecord>) -> Result<(), String> {
+        records.sort_by_key(|record| record.index);
+        if let Some(pair) = records
+            .windows(2)
+            .find(|pair| pair[0].index == pair[1].index)
+        {
+            return Err(format!("记录编号 {} 重复", pair[0].index));
+        }
+        if records.last().is_some_and(|r| r.index >= self.next_index) {
+            return Err(format!(
+                "next_index {} 不大于已有的记录编号",
+                self.next_index
+            ));
+        }
+        self.failed = records
+            .iter()
+            .filter(|record| !record.is_ok())
+            .map(|record| record.index)
+            .collect();
+        self.records = records.into();
+        self.evict();
+        Ok(())
+    }
+
+    /// 按淘汰策略删除记录，直到不超过容量
+    ///
+    /// 淘汰最旧的记录不移动其余记录；其他策略淘汰中间的记录时需要移动它一侧的记录。
+    fn evict(&mut self) {
+        while self.records.len() > self.max_records {
+            let newest = self.records.len() - 1;
+            // 刚添加的最新记录不参与按失败或固定状态的挑选
+            let victim = match self.eviction_policy {
+                EvictionPolicy::DropOldest => Some(0),
+                EvictionPolicy::DropFailuresFirst => Some(
+                    self.failed
+                        .front()
+                        .and_then(|&index| self.position(index))
+                        .filter(|&position| position < newest)
+                        .unwrap_or(0),
+                ),
+                EvictionPolicy::KeepPinned => self.records.range(..newest).position(|r| !r.pinned),
+            };
+            match victim {
+                Some(position) => self.remove_at(position),
+                None => break,
+            }
+        }
+    }
+
+    /// 删除 `records` 中指定位置的记录
+    fn remove_at(&mut self, position: usize) {
+        let Some(record) = self.records.remove(position) else {
+            return;
+        };
+        if !record.is_ok() {
+            if let Ok(i) = self.failed.binary_search(&record.index) {
+                self.failed.remove(i);
+            }
+        }
+    }
+
     /// 显示历史记录
     pub fn display(&self) {
         if self.records.is_empty() {
@@ -122,6 +670,13 @@ impl HistoryManager {
         for record in &self.records {
             println!("{}", record);
         }
+        let failures = self.failures().len();
+        if failures > 0 {
+            println!("--- 失败 {} 条 ---", failures);
+            for (kind, count) in self.error_counts() {
+                println!("{}: {}", kind, count);
+            }
+        }
         println!("===============\n");
     }
 
@@ -129,7 +684,7 @@ impl HistoryManager {
     pub fn display_recent(&self, count: usize) {
         let records = self.get_recent_records(count);
 
-        if records.is_empty() {
+        if records.len() == 0 {
             println!("暂无计算历史");
             return;
         }
@@ -151,6 +706,7 @@ impl Default for HistoryManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::TimeZone;
 
     #[test]
     fn test_add_record() {
@@ -173,9 +729,9 @@ mod tests {
         // 应该只保留最后 3 条
         assert_eq!(manager.count(), 3);
 
-        let records = manager.get_all_records();
-        assert_eq!(records[0].result, 4.0);
-        assert_eq!(records[2].result, 8.0);
+        let records: Vec<_> = manager.get_all_records().collect();
+        assert_eq!(records[0].result, Ok(4.0));
+        assert_eq!(records[2].result, Ok(8.0));
     }
 
     #[test]
@@ -189,6 +745,104 @@ mod tests {
         assert_eq!(results.len(), 2);
     }
 
+    #[test]
+    fn test_failures() {
+        let calc = Calculator::new();
+        let mut manager = HistoryManager::new();
+        for expression in ["1 + 1", "1 / 0", "x + 1", "2 / (1 - 1)", "sqrt(4)"] {
+            let _ = manager.evaluate_and_record(&calc, expression);
+        }
+        manager.add_error("y", Error::UndefinedVariable("y".to_string()));
+
+        assert_eq!(manager.successes().len(), 2);
+        assert_eq!(manager.failures().len(), 4);
+        assert_eq!(manager.get_all_records().next().unwrap().value(), Some(2.0));
+        assert_eq!(
+            manager.error_counts(),
+            [("DivisionByZero", 2), ("UndefinedVariable", 2)]
+        );
+        assert_eq!(manager.search_records("除零").len(), 2);
+
+        // 保存后错误的种类和消息保留下来
+        let path = temp_path("failures.json");
+        manager.save(&path).unwrap();
+        let loaded = HistoryManager::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.error_counts(), manager.error_counts());
+        assert_eq!(
+            loaded.get_all_records().nth(1).unwrap().to_string(),
+            manager.get_all_records().nth(1).unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn test_recall() {
+        let calc = Calculator::new();
+        let mut manager = HistoryManager::with_capacity(4);
+        let mut eval = |expression| manager.evaluate_and_record(&calc, expression);
+        assert_eq!(eval("2 + 3"), Ok(Some(5.0)));
+        assert_eq!(eval("ans * 2"), Ok(Some(10.0)));
+        assert_eq!(eval("_ + $1"), Ok(Some(15.0)));
+        assert!(eval("1 / 0").is_err());
+        // ans 跳过失败的记录
+        assert_eq!(eval("ans"), Ok(Some(15.0)));
+
+        let reason = |result: Result<Option<f64>, Error>| match result {
+            Err(Error::HistoryReference { reason, .. }) => reason,
+            other => panic!("期望 HistoryReference，实际为 {:?}", other),
+        };
+        // $1 已超出容量，$4 求值失败，$9 还不存在
+        assert!(reason(eval("$1")).contains("删除"));
+        assert!(reason(eval("$4 + 1")).contains("失败"));
+        assert!(reason(eval("$9")).contains("不存在"));
+
+        assert!(manager.remove_record(5));
+        assert!(!manager.remove_record(5));
+        assert_eq!(
+            manager.recall(ResultRef::Index(5)).unwrap_err().to_string(),
+            "无法引用历史结果 $5: 该记录已被删除或超出历史容量"
+        );
+        assert!(HistoryManager::new().recall(ResultRef::Last).is_err());
+    }
+
+    #[test]
+    fn test_recall_exact() {
+        let mut calc = Calculator::new();
+        calc.set_number_mode(NumberMode::Rational);
+        let mut manager = HistoryManager::new();
+        manager.evaluate_and_record(&calc, "1/3").unwrap();
+        assert_eq!(manager.recall(ResultRef::Last).unwrap().to_string(), "1/3");
+        let value = calc
+            .evaluate_value_with_recall("ans * 3 - $1 * 3", |r| manager.recall(r))
+            .unwrap();
+        assert_eq!(value.to_string(), "0");
+        manager.evaluate_and_record(&calc, "ans * 3").unwrap();
+        assert_eq!(
+            manager
+                .get_record_by_index(2)
+                .unwrap()
+                .exact_value()
+                .unwrap()
+                .to_string(),
+            "1"
+        );
+
+        // 十进制结果按全部有效位数代入
+        calc.set_number_mode(NumberMode::Decimal);
+        manager.evaluate_and_record(&calc, "1/3").unwrap();
+        let third = calc.evaluate_value("1/3").unwrap();
+        assert_eq!(manager.recall(ResultRef::Index(3)), Ok(third));
+        let value = calc
+            .evaluate_value_with_recall("$3 - ans", |r| manager.recall(r))
+            .unwrap();
+        assert!(matches!(value, Value::Decimal(d) if d.is_zero()));
+
+        // 浮点结果没有精确值
+        calc.set_number_mode(NumberMode::Float);
+        manager.evaluate_and_record(&calc, "1/4").unwrap();
+        assert!(manager.get_record_by_index(4).unwrap().exact.is_none());
+    }
+
     #[test]
     fn test_clear() {
         let mut manager = HistoryManager::new();
@@ -202,6 +856,391 @@ mod tests {
         assert!(manager.is_empty());
     }
 
+    #[test]
+    fn test_angle_mode_recorded() {
+        let mut manager = HistoryManager::new();
+        manager.add_record("sin(1)", 1f64.sin());
+        manager.add_record_with_mode("sin(30)", 0.5, AngleMode::Degrees);
+
+        let records: Vec<_> = manager.get_all_records().collect();
+        assert_eq!(records[0].angle_mode, AngleMode::Radians);
+        assert_eq!(records[1].angle_mode, AngleMode::Degrees);
+        assert_eq!(records[1].to_string(), "[2] sin(30) = 0.5 (DEG)");
+
+        // 非默认的数值模式和精度与角度模式一起标注
+        let mut calc = Calculator::new();
+        calc.set_number_mode(NumberMode::Decimal);
+        calc.set_precision(20);
+        manager.evaluate_and_record(&calc, "1 / 4").unwrap();
+        calc.set_angle_mode(AngleMode::Degrees);
+        calc.set_number_mode(NumberMode::Rational);
+        calc.set_precision(DEFAULT_PRECISION as u8);
+        manager.evaluate_and_record(&calc, "1 / 4").unwrap();
+        let records: Vec<_> = manager.get_all_records().collect();
+        assert_eq!(records[2].to_string(), "[3] 1 / 4 = 0.25 (decimal, 20 位)");
+        assert_eq!(records[3].to_string(), "[4] 1 / 4 = 0.25 (DEG, rational)");
+    }
+
+    /// 测试用的临时文件路径
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("chapter02-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = temp_path("history.json");
+        let mut manager = HistoryManager::with_capacity(5);
+        manager.add_record("1 + 1", 2.0);
+        manager.add_record_with_mode("sin(30)", 0.5, AngleMode::Degrees);
+        manager.add_record("1 / 0", f64::INFINITY);
+        manager.save(&path).unwrap();
+
+        let mut loaded = HistoryManager::load(&path).unwrap();
+        assert_eq!(loaded.count(), 3);
+        assert_eq!(
+            loaded.get_all_records().nth(1).unwrap().to_string(),
+            "[2] sin(30) = 0.5 (DEG)"
+        );
+        assert_eq!(
+            loaded.get_all_records().nth(2).unwrap().result,
+            Ok(f64::INFINITY)
+        );
+        // 编号和容量也被恢复
+        loaded.add_record("2 + 2", 4.0);
+        assert_eq!(loaded.get_record_by_index(4).unwrap().result, Ok(4.0));
+
+        // 编号重复或下一个编号不大于已有的编号
+        let mut manager = HistoryManager::new();
+        let record = |index: usize| HistoryRecord::new(index.to_string(), 0.0, index);
+        assert!(manager.replace_records(vec![record(2), record(2)]).is_err());
+        assert!(manager.replace_records(vec![record(1)]).is_err());
+        manager.next_index = 3;
+        assert!(manager.replace_records(vec![record(2), record(1)]).is_ok());
+        assert_eq!(manager.get_record_by_index(2).unwrap().expression, "2");
+        let text = std::fs::read_to_string(&path).unwrap();
+        for broken in [
+            text.replacen("\"index\": 2", "\"index\": 1", 1),
+            text.replacen("\"next_index\": 4", "\"next_index\": 2", 1),
+            text.replacen("\"index\": 3", &format!("\"index\": {}", u64::MAX), 1),
+        ] {
+            assert_ne!(broken, text);
+            std::fs::write(&path, broken).unwrap();
+            assert!(matches!(
+                HistoryManager::load(&path),
+                Err(Error::HistoryFormat { .. })
+            ));
+        }
+
+        // 被截断的文件
+        std::fs::write(&path, &text[..text.len() / 2]).unwrap();
+        assert!(matches!(
+            HistoryManager::load(&path),
+            Err(Error::HistoryFormat { .. })
+        ));
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            HistoryManager::load(&path),
+            Err(Error::IoError(_))
+        ));
+    }
+
+    #[test]
+    fn test_csv_export_and_import() {
+        let path = temp_path("history.csv");
+        let mut manager = HistoryManager::new();
+        manager.add_record("max(1, 2)", 2.0);
+        manager.add_record_with_mode("asin(1)", 90.0, AngleMode::Degrees);
+        manager.add_error("1 / 0", Error::DivisionByZero);
+        manager.add_tag(1, "a");
+        manager.add_tag(1, "b;c\\");
+        manager.set_note(2, Some("直角, \"度\""));
+        manager.set_pinned(2, true);
+        manager.export_csv(&path).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with(
+            "index,expression,result,error_kind,error,angle_mode,number_mode,precision,timestamp,duration_ms,tags,note,pinned\n\
+             1,\"max(1, 2)\",2,,,RAD,float,"
+        ));
+        assert!(text.contains(",a;b\\;c\\\\,,false\n"));
+        assert!(text.contains("\n3,1 / 0,,DivisionByZero,除零错误,RAD,"));
+
+        let mut imported = HistoryManager::new();
+        imported.add_record("0", 0.0);
+        assert_eq!(imported.import_csv(&path).unwrap(), 3);
+        let records: Vec<_> = imported.get_all_records().collect();
+        assert_eq!(records[1].expression, "max(1, 2)");
+        assert_eq!(
+            (records[2].index, records[2].angle_mode),
+            (3, AngleMode::Degrees)
+        );
+        assert_eq!(records[1].tags, ["a", "b;c\\"]);
+        assert_eq!(records[2].note.as_deref(), Some("直角, \"度\""));
+        assert!(records[2].pinned && !records[1].pinned);
+        assert_eq!(
+            records[2].timestamp,
+            manager.get_all_records().nth(1).unwrap().timestamp
+        );
+        assert_eq!(records[3].error().unwrap().kind(), "DivisionByZero");
+        assert_eq!(records[3].to_string(), "[4] 1 / 0 ✗ 除零错误");
+
+        // 无效的行不导入任何记录
+        std::fs::write(&path, "expression,result\n1 + 1,2\n2 + 2,four\n").unwrap();
+        assert!(matches!(
+            imported.import_csv(&path),
+            Err(Error::HistoryFormat { .. })
+        ));
+        assert_eq!(imported.count(), 4);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_metadata_queries() {
+        let day = |d: u32| Local.with_ymd_and_hms(2024, 3, d, 12, 0, 0).unwrap();
+        let mut manager = HistoryManager::new();
+        for (d, ms) in [(1, 5), (2, 50), (3, 500)] {
+            manager.add(HistoryRecord {
+                timestamp: Some(day(d)),
+                duration: Duration::from_millis(ms),
+                ..HistoryRecord::new(format!("{} + 1", d), d as f64 + 1.0, 0)
+            });
+        }
+        assert!(manager.add_tag(2, "slow"));
+        assert!(manager.add_tag(3, "slow"));
+        assert!(manager.add_tag(3, "slow"));
+        assert!(!manager.add_tag(9, "slow"));
+        assert!(manager.set_note(3, Some("最慢")));
+
+        let between = manager.records_between(day(2), day(3));
+        assert_eq!(between.len(), 1);
+        assert_eq!(between[0].index, 2);
+        assert_eq!(manager.records_with_tag("slow").len(), 2);
+        assert_eq!(manager.records_with_tag("fast").len(), 0);
+        let slow = manager.records_slower_than(Duration::from_millis(50));
+        assert_eq!(slow.len(), 1);
+        assert_eq!(slow[0].to_string(), "[3] 3 + 1 = 4 #slow // 最慢");
+    }
+
+    #[test]
+    fn test_evaluate_and_record() {
+        let mut calc = Calculator::new();
+        calc.set_angle_mode(AngleMode::Degrees);
+        let mut manager = HistoryManager::new();
+        let before = Local::now();
+        assert_eq!(manager.evaluate_and_record(&calc, "2 * 21"), Ok(Some(42.0)));
+        assert!(manager.evaluate_and_record(&calc, "1 +").is_err());
+        // 函数定义不记入历史
+        assert_eq!(manager.evaluate_and_record(&calc, "f(x) = x + 1"), Ok(None));
+        assert_eq!(manager.evaluate_and_record(&calc, "f(1)"), Ok(Some(2.0)));
+        assert!(manager.remove_record(3));
+
+        // 求值失败的表达式也被记录
+        assert_eq!(manager.count(), 2);
+        assert!(!manager.get_all_records().nth(1).unwrap().is_ok());
+        let record = manager.get_all_records().next().unwrap();
+        assert_eq!(record.angle_mode, AngleMode::Degrees);
+        assert_eq!(record.number_mode, calc.number_mode());
+        let timestamp = record.timestamp.unwrap();
+        assert!(timestamp >= before && timestamp <= Local::now());
+    }
+
+    #[test]
+    fn test_journal() {
+        let path = temp_path("history.journal");
+        let _ = std::fs::remove_file(&path);
+        {
+            let mut manager = HistoryManager::open_journal(&path, 3).unwrap();
+            for i in 1..=4 {
+                manager.add_record(&format!("{} * 2", i), (i * 2) as f64);
+            }
+            assert!(manager.journal_error().is_none());
+        }
+
+        // 模拟崩溃：最后一行只写了一半
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        std::io::Write::write_all(&mut file, b"{\"index\": 5, \"expre").unwrap();
+        drop(file);
+
+        let mut manager = HistoryManager::open_journal(&path, 3).unwrap();
+        assert_eq!(manager.count(), 3);
+        assert_eq!(
+            manager.get_all_records().next().unwrap().expression,
+            "2 * 2"
+        );
+        manager.add_record("5 * 2", 10.0);
+        assert_eq!(manager.get_record_by_index(5).unwrap().result, Ok(10.0));
+        drop(manager);
+
+        // 修改过的记录重新打开后保留最新的内容
+        let mut manager = HistoryManager::open_journal(&path, 3).unwrap();
+        manager.add_tag(4, "x");
+        manager.set_note(4, Some("y"));
+        drop(manager);
+        let manager = HistoryManager::open_journal(&path, 3).unwrap();
+        assert_eq!(manager.count(), 3);
+        assert_eq!(
+            manager.get_record_by_index(4).unwrap().to_string(),
+            "[4] 4 * 2 = 8 #x // y"
+        );
+        drop(manager);
+
+        // 中间的行损坏时报错
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, text.replacen("\"result\"", "\"resu", 1)).unwrap();
+        assert!(matches!(
+            HistoryManager::open_journal(&path, 3),
+            Err(Error::HistoryFormat { .. })
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_eviction_policy_persisted() {
+        // 全部固定时记录数超过容量，重新读入后仍按固定策略保留
+        let fill = |manager: &mut HistoryManager| {
+            manager.set_eviction_policy(EvictionPolicy::KeepPinned);
+            for i in 1..=3 {
+                manager.add_record(&i.to_string(), i as f64);
+                manager.set_pinned(i, true);
+            }
+        };
+        let indices = |manager: &HistoryManager| -> Vec<usize> {
+            manager.get_all_records().map(|r| r.index).collect()
+        };
+
+        let path = temp_path("pinned.json");
+        let mut manager = HistoryManager::with_capacity(2);
+        fill(&mut manager);
+        assert_eq!(indices(&manager), [1, 2, 3]);
+        manager.save(&path).unwrap();
+        let loaded = HistoryManager::load(&path).unwrap();
+        assert_eq!(loaded.eviction_policy(), EvictionPolicy::KeepPinned);
+        assert_eq!(indices(&loaded), [1, 2, 3]);
+        std::fs::remove_file(&path).unwrap();
+
+        let path = temp_path("pinned.journal");
+        let _ = std::fs::remove_file(&path);
+        fill(&mut HistoryManager::open_journal(&path, 2).unwrap());
+        let manager = HistoryManager::open_journal(&path, 2).unwrap();
+        assert_eq!(manager.eviction_policy(), EvictionPolicy::KeepPinned);
+        assert_eq!(indices(&manager), [1, 2, 3]);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            "drop_failures_first".parse::<EvictionPolicy>(),
+            Ok(EvictionPolicy::DropFailuresFirst)
+        );
+        assert!("newest".parse::<EvictionPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_eviction_policies() {
+        let add_all = |manager: &mut HistoryManager| {
+            manager.add_record("1", 1.0);
+            manager.add_error("1 / 0", Error::DivisionByZero);
+            manager.add_record("3", 3.0);
+            manager.add_record("4", 4.0);
+            manager.add_record("5", 5.0);
+        };
+        let indices = |manager: &HistoryManager| -> Vec<usize> {
+            manager.get_all_records().map(|r| r.index).collect()
+        };
+
+        let mut manager = HistoryManager::with_capacity(3);
+        add_all(&mut manager);
+        assert_eq!(indices(&manager), [3, 4, 5]);
+
+        // 先淘汰失败的记录
+        let mut manager = HistoryManager::with_capacity(3);
+        manager.set_eviction_policy(EvictionPolicy::DropFailuresFirst);
+        add_all(&mut manager);
+        assert_eq!(indices(&manager), [3, 4, 5]);
+        let mut manager = HistoryManager::with_capacity(4);
+        manager.set_eviction_policy(EvictionPolicy::DropFailuresFirst);
+        add_all(&mut manager);
+        assert_eq!(indices(&manager), [1, 3, 4, 5]);
+        assert!(manager.failures().is_empty());
+        // 刚添加的失败记录不会被立即淘汰
+        let mut manager = HistoryManager::with_capacity(2);
+        manager.set_eviction_policy(EvictionPolicy::DropFailuresFirst);
+        manager.add_record("1", 1.0);
+        manager.add_record("2", 2.0);
+        manager.add_error("1 / 0", Error::DivisionByZero);
+        assert_eq!(indices(&manager), [2, 3]);
+        manager.add_record("4", 4.0);
+        assert_eq!(indices(&manager), [2, 4]);
+
+        // 固定的记录不被淘汰
+        let mut manager = HistoryManager::with_capacity(3);
+        manager.set_eviction_policy(EvictionPolicy::KeepPinned);
+        manager.add_record("1", 1.0);
+        assert!(manager.set_pinned(1, true));
+        manager.add_error("1 / 0", Error::DivisionByZero);
+        manager.add_record("3", 3.0);
+        manager.add_record("4", 4.0);
+        manager.add_record("5", 5.0);
+        assert_eq!(indices(&manager), [1, 4, 5]);
+        assert_eq!(manager.recall(ResultRef::Index(1)), Ok(Value::Float(1.0)));
+
+        // 全部固定时允许超出容量
+        for index in [4, 5] {
+            manager.set_pinned(index, true);
+        }
+        manager.add_record("6", 6.0);
+        assert_eq!(indices(&manager), [1, 4, 5, 6]);
+        manager.add_record("7", 7.0);
+        assert_eq!(indices(&manager), [1, 4, 5, 7]);
+    }
+
+    #[test]
+    fn test_lookup_after_eviction() {
+        let mut manager = HistoryManager::with_capacity(1000);
+        for i in 1..=5000 {
+            manager.add_record(&i.to_string(), i as f64);
+        }
+        assert_eq!(manager.count(), 1000);
+        assert!(manager.get_record_by_index(4000).is_none());
+        assert_eq!(
+            manager.get_record_by_index(4001).unwrap().value(),
+            Some(4001.0)
+        );
+        assert!(manager.remove_record(4500));
+        assert!(manager.get_record_by_index(4500).is_none());
+        assert_eq!(
+            manager.get_record_by_index(4501).unwrap().value(),
+            Some(4501.0)
+        );
+        assert_eq!(manager.get_recent_records(2).next().unwrap().index, 4999);
+    }
+
+    #[test]
+    fn test_add_at_capacity_does_not_move_records() {
+        // 容量为 2^k - 1 时环形缓冲区没有空闲位置，每次添加都会绕回；
+        // 淘汰最旧的记录必须是 O(1)，不能在每次添加后整理整个缓冲区
+        let capacity = 65_535;
+        let mut manager = HistoryManager::with_capacity(capacity);
+        for i in 0..capacity {
+            manager.add_record("x + 1", i as f64);
+        }
+        let start = Instant::now();
+        for i in 0..20_000 {
+            manager.add_record("x + 1", i as f64);
+        }
+        assert!(
+            start.elapsed() < Duration::from_secs(2),
+            "{:?}",
+            start.elapsed()
+        );
+        assert_eq!(manager.count(), capacity);
+        assert_eq!(manager.get_all_records().next().unwrap().index, 20_000 + 1);
+        assert_eq!(
+            manager.get_all_records().next_back().unwrap().index,
+            capacity + 20_000
+        );
+    }
+
     #[test]
     fn test_get_recent() {
         let mut manager = HistoryManager::new();
@@ -209,9 +1248,9 @@ mod tests {
             manager.add_record(&format!("{} + {}", i, i), (i * 2) as f64);
         }
 
-        let recent = manager.get_recent_records(3);
+        let recent: Vec<_> = manager.get_recent_records(3).collect();
         assert_eq!(recent.len(), 3);
-        assert_eq!(recent[0].result, 16.0); // 8 + 8
-        assert_eq!(recent[2].result, 20.0); // 10 + 10
+        assert_eq!(recent[0].result, Ok(16.0)); // 8 + 8
+        assert_eq!(recent[2].result, Ok(20.0)); // 10 + 10
     }
 }
@@ -0,0 +1,768 @@
+// history/storage.rs - 历史记录的持久化：版本化的 JSON 文件、CSV 导入导出与追加写入的日志
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use num_rational::BigRational;
+use serde_json::{json, Value};
+
+use super::{EvictionPolicy, HistoryRecord};
+use crate::calculator::decimal::Decimal;
+use crate::calculator::{AngleMode, NumberMode, Value as NumberValue};
+use crate::utils::Error;
+
+/// 文件格式的标识
+pub const FORMAT_NAME: &str = "chapter02-history";
+
+/// 当前的文件格式版本，读取时拒绝更高的版本
+///
+/// 版本 2 增加了记录时间、耗时、数值模式、精度、标签和备注，读取版本 1 时这些字段取默认值。
+/// 版本 3 增加了求值失败的记录，用 `error` 字段（种类和消息）代替 `result`。
+/// 版本 4 增加了记录是否固定的 `pinned` 字段。
+/// 版本 5 增加了保存精确结果的 `exact` 字段（分数形式的字符串）。
+/// 版本 6 在文件头（日志的第一行）增加了淘汰策略 `eviction_policy`，缺少时按 `drop_oldest` 处理。
+pub const FORMAT_VERSION: u64 = 6;
+
+/// CSV 文件的表头，失败的记录 result 列为空
+pub const CSV_HEADER: [&str; 13] = [
+    "index",
+    "expression",
+    "result",
+    "error_kind",
+    "error",
+    "angle_mode",
+    "number_mode",
+    "precision",
+    "timestamp",
+    "duration_ms",
+    "tags",
+    "note",
+    "pinned",
+];
+
+/// 记录编号的上限（JSON 中能精确表示的最大整数），更大的编号视为文件损坏
+const MAX_INDEX: u64 = (1 << 53) - 1;
+
+/// CSV 中多个标签之间的分隔符，标签中的分隔符和反斜杠用反斜杠转义
+const TAG_SEPARATOR: char = ';';
+
+/// 保存到 JSON 文件中的历史记录状态
+#[derive(Debug, Clone)]
+pub(super) struct Snapshot {
+    pub records: Vec<HistoryRecord>,
+    pub max_records: usize,
+    pub next_index: usize,
+    pub eviction_policy: EvictionPolicy,
+}
+
+pub(super) fn format_error(path: &Path, reason: impl Into<String>) -> Error {
+    Error::HistoryFormat {
+        path: path.display().to_string(),
+        reason: reason.into(),
+    }
+}
+
+/// 先写入同目录下的临时文件再改名，写到一半崩溃时原文件保持完整
+fn write_atomically(path: &Path, contents: &str) -> Result<(), Error> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+
+    let mut file = File::create(&temp)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&temp, path)?;
+    Ok(())
+}
+
+// ===== JSON =====
+
+/// 非有限的结果（NaN、±inf）在 JSON 中没有对应的数值，保存为字符串
+fn number_to_json(x: f64) -> Value {
+    if x.is_finite() {
+        json!(x)
+    } else {
+        json!(x.to_string())
+    }
+}
+
+fn number_from_json(value: &Value) -> Option<f64> {
+    value.as_f64().or_else(|| value.as_str()?.parse().ok())
+}
+
+/// 精确结果保存为分数形式的字符串（十进制数也按精确值保存）
+fn exact_to_json(value: &NumberValue) -> Value {
+    let rational = match value {
+        NumberValue::Decimal(d) => d.to_rational(),
+        value => value.to_rational(),
+    };
+    rational.map_or(Value::Null, |r| json!(r.to_string()))
+}
+
+/// 按记录的数值模式和精度还原精确结果
+fn exact_from_json(text: &str, mode: NumberMode, precision: u32) -> Option<NumberValue> {
+    let rational: BigRational = text.parse().ok()?;
+    Some(match mode {
+        NumberMode::Decimal => NumberValue::Decimal(Decimal::from_rational(&rational, precision)),
+        _ if rational.is_integer() => NumberValue::Integer(rational.to_integer()),
+        _ => NumberValue::Rational(rational),
+    })
+}
+
+fn duration_ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn parse_duration_ms(ms: f64) -> Option<Duration> {
+    Duration::try_from_secs_f64(ms / 1000.0).ok()
+}
+
+fn parse_timestamp(text: &str) -> Option<DateTime<Local>> {
+    DateTime::parse_from_rfc3339(text)
+        .ok()
+        .map(|t| t.with_timezone(&Local))
+}
+
+/// 错误只保存种类和消息，读回时成为 `Error::Recorded`
+fn error_to_json(error: &Error) -> Value {
+    json!({ "kind": error.kind(), "message": error.to_string() })
+}
+
+fn error_from_json(value: &Value) -> Option<Error> {
+    Some(Error::Recorded {
+        kind: value.get("kind")?.as_str()?.to_string(),
+        message: value.get("message")?.as_str()?.to_string(),
+    })
+}
+
+fn record_to_json(record: &HistoryRecord) -> Value {
+    let (key, result) = match &record.result {
+        Ok(value) => ("result", number_to_json(*value)),
+        Err(error) => ("error", error_to_json(error)),
+    };
+    json!({
+        "index": record.index,
+        "expression": record.expression,
+        key: result,
+        "angle_mode": record.angle_mode.to_string(),
+        "number_mode": record.number_mode.to_string(),
+        "precision": record.precision,
+        "timestamp": record.timestamp.map(|time| time.to_rfc3339()),
+        "duration_ms": duration_ms(record.duration),
+        "tags": record.tags,
+        "note": record.note,
+        "pinned": record.pinned,
+        "exact": record.exact.as_ref().map_or(Value::Null, exact_to_json),
+    })
+}
+
+fn record_from_json(value: &Value) -> Result<HistoryRecord, String> {
+    let field = |name: &str| value.get(name).ok_or(format!("记录缺少字段 {}", name));
+    let index = field("index")?
+        .as_u64()
+        .filter(|index| (1..=MAX_INDEX).contains(index))
+        .and_then(|index| usize::try_from(index).ok())
+        .ok_or(format!("记录的 index 必须是 1 到 {} 之间的整数", MAX_INDEX))?;
+    let expression = field("expression")?
+        .as_str()
+        .ok_or("记录的 expression 必须是字符串")?;
+    let result = match value.get("error") {
+        Some(error) => Err(error_from_json(error).ok_or("记录的 error 必须包含 kind 和 message")?),
+        None => Ok(number_from_json(field("result")?).ok_or("记录的 result 必须是数值")?),
+    };
+    // 缺少角度模式时按默认的弧度处理
+    let angle_mode = match value.get("angle_mode") {
+        None => AngleMode::Radians,
+        Some(mode) => mode
+            .as_str()
+            .and_then(|mode| mode.parse().ok())
+            .ok_or(format!("无效的角度模式: {}", mode))?,
+    };
+    let mut record = HistoryRecord::with_result(expression.to_string(), result, index, angle_mode);
+
+    // 版本 2 的字段，缺少时保留默认值
+    if let Some(mode) = value.get("number_mode") {
+        record.number_mode = mode
+            .as_str()
+            .and_then(|mode| mode.parse().ok())
+            .ok_or(format!("无效的数值模式: {}", mode))?;
+    }
+    if let Some(precision) = value.get("precision") {
+        record.precision = precision
+            .as_u64()
+            .and_then(|p| u32::try_from(p).ok())
+            .ok_or("记录的 precision 必须是非负整数")?;
+    }
+    // 版本 1 的记录没有时间
+    record.timestamp = match value.get("timestamp") {
+        None | Some(Value::Null) => None,
+        Some(timestamp) => Some(
+            timestamp
+                .as_str()
+                .and_then(parse_timestamp)
+                .ok_or(format!("无效的时间: {}", timestamp))?,
+        ),
+    };
+    if let Some(duration) = value.get("duration_ms") {
+        record.duration = duration
+            .as_f64()
+            .and_then(parse_duration_ms)
+            .ok_or(format!("无效的耗时: {}", duration))?;
+    }
+    if let Some(tags) = value.get("tags") {
+        record.tags = tags
+            .as_array()
+            .and_then(|tags| {
+                tags.iter()
+                    .map(|t| t.as_str().map(str::to_string))
+                    .collect()
+            })
+            .ok_or("记录的 tags 必须是字符串数组")?;
+    }
+    match value.get("note") {
+        None | Some(Value::Null) => {}
+        Some(note) => {
+            record.note = Some(note.as_str().ok_or("记录的 note 必须是字符串")?.to_string());
+        }
+    }
+    if let Some(pinned) = value.get("pinned") {
+        record.pinned = pinned.as_bool().ok_or("记录的 pinned 必须是布尔值")?;
+    }
+    // 版本 5 的字段，只有成功的记录才有精确结果
+    match value.get("exact") {
+        None | Some(Value::Null) => {}
+        Some(exact) => {
+            record.exact = exact
+                .as_str()
+                .filter(|_| record.is_ok())
+                .and_then(|text| exact_from_json(text, record.number_mode, record.precision));
+            if record.exact.is_none() {
+                return Err(format!("无效的精确结果: {}", exact));
+            }
+        }
+    }
+    Ok(record)
+}
+
+/// 检查记录的编号互不相同，返回最大的编号（没有记录时为 0）
+fn check_indices(records: &[HistoryRecord]) -> Result<usize, String> {
+    let mut indices: Vec<usize> = records.iter().map(|r| r.index).collect();
+    indices.sort_unstable();
+    match indices.windows(2).find(|pair| pair[0] == pair[1]) {
+        Some(pair) => Err(format!("编号 {} 重复", pair[0])),
+        None => Ok(indices.last().copied().unwrap_or(0)),
+    }
+}
+
+/// 最大编号之后的下一个编号
+pub(super) fn next_index_after(last_index: usize) -> Result<usize, String> {
+    last_index
+        .checked_add(1)
+        .filter(|&n| n as u64 <= MAX_INDEX + 1)
+        .ok_or(format!("编号 {} 过大", last_index))
+}
+
+/// 文件头中的淘汰策略，缺少时为默认策略
+fn eviction_policy_from_json(header: &Value) -> Result<EvictionPolicy, String> {
+    match header.get("eviction_policy") {
+        None => Ok(EvictionPolicy::default()),
+        Some(policy) => policy
+            .as_str()
+            .and_then(|policy| policy.parse().ok())
+            .ok_or(format!("无效的淘汰策略: {}", policy)),
+    }
+}
+
+/// 检查文件头中的格式标识和版本
+fn check_header(header: &Value) -> Result<(), String> {
+    if header.get("format").and_then(Value::as_str) != Some(FORMAT_NAME) {
+        return Err("不是历史记录文件".to_string());
+    }
+    match header.get("version").and_then(Value::as_u64) {
+        Some(version) if version <= FORMAT_VERSION => Ok(()),
+        Some(version) => Err(format!(
+            "不支持的版本 {}（最高支持 {}）",
+            version, FORMAT_VERSION
+        )),
+        None => Err("缺少版本号".to_string()),
+    }
+}
+
+/// 把历史记录保存为版本化的 JSON 文件
+pub(super) fn save_json(path: &Path, snapshot: &Snapshot) -> Result<(), Error> {
+    let document = json!({
+        "format": FORMAT_NAME,
+        "version": FORMAT_VERSION,
+        "max_records": snapshot.max_records,
+        "next_index": snapshot.next_index,
+        "eviction_policy": snapshot.eviction_policy.to_string(),
+        "records": snapshot.records.iter().map(record_to_json).collect::<Vec<_>>(),
+    });
+    let text =
+        serde_json::to_string_pretty(&document).map_err(|e| Error::IoError(e.to_string()))?;
+    write_atomically(path, &(text + "\n"))
+}
+
+/// 读取 JSON 格式的历史记录文件
+pub(super) fn load_json(path: &Path) -> Result<Snapshot, Error> {
+    let text = fs::read_to_string(path)?;
+    let document: Value = serde_json::from_str(&text)
+        .map_err(|e| format_error(path, format!("JSON 无效（文件可能被截断）: {}", e)))?;
+    check_header(&document).map_err(|reason| format_error(path, reason))?;
+
+    let records = document
+        .get("records")
+        .and_then(Value::as_array)
+        .ok_or_else(|| format_error(path, "缺少记录列表 records"))?
+        .iter()
+        .enumerate()
+        .map(|(i, record)| {
+            record_from_json(record)
+                .map_err(|reason| format_error(path, format!("第 {} 条{}", i + 1, reason)))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let max_records = document
+        .get("max_records")
+        .and_then(Value::as_u64)
+        .map_or(records.len().max(1), |n| n as usize);
+    let last_index = check_indices(&records).map_err(|reason| format_error(path, reason))?;
+    let next_index = next_index_after(last_index).map_err(|reason| format_error(path, reason))?;
+    // 文件中的下一个编号必须比已有的最大编号大，缺少时取最大编号加 1
+    let next_index = match document.get("next_index") {
+        None => next_index,
+        Some(n) => n
+            .as_u64()
+            .filter(|&n| n >= next_index as u64 && n <= MAX_INDEX + 1)
+            .map(|n| n as usize)
+            .ok_or_else(|| format_error(path, format!("无效的 next_index: {}", n)))?,
+    };
+
+    let eviction_policy =
+        eviction_policy_from_json(&document).map_err(|reason| format_error(path, reason))?;
+
+    Ok(Snapshot {
+        records,
+        max_records,
+        next_index,
+        eviction_policy,
+    })
+}
+
+// ===== CSV =====
+
+/// 按 RFC 4180 转义字段：含有逗号、引号或换行时加引号，引号写成两个
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// 把历史记录导出为 CSV 文件
+pub(super) fn export_csv<'a>(
+    path: &Path,
+    records: impl IntoIterator<Item = &'a HistoryRecord>,
+) -> Result<(), Error> {
+    let mut text = CSV_HEADER.join(",") + "\n";
+    for record in records {
+        let tags = join_tags(&record.tags);
+        let fields = [
+            record.index.to_string(),
+            csv_field(&record.expression),
+            record
+                .value()
+                .map_or(String::new(), |value| value.to_string()),
+            record
+                .error()
+                .map_or(String::new(), |e| e.kind().to_string()),
+            csv_field(&record.error().map_or(String::new(), |e| e.to_string())),
+            record.angle_mode.to_string(),
+            record.number_mode.to_string(),
+            record.precision.to_string(),
+            record
+                .timestamp
+                .map_or(String::new(), |time| time.to_rfc3339()),
+            duration_ms(record.duration).to_string(),
+            csv_field(&tags),
+            csv_field(record.note.as_deref().unwrap_or("")),
+            record.pinned.to_string(),
+        ];
+        text += &(fields.join(",") + "\n");
+    }
+    write_atomically(path, &text)
+}
+
+/// 用分隔符连接标签，标签中的分隔符和反斜杠前加反斜杠
+fn join_tags(tags: &[String]) -> String {
+    tags.iter()
+        .map(|tag| {
+            tag.replace('\\', "\\\\")
+                .replace(TAG_SEPARATOR, &format!("\\{}", TAG_SEPARATOR))
+        })
+        .collect::<Vec<_>>()
+        .join(&TAG_SEPARATOR.to_string())
+}
+
+/// 按未转义的分隔符拆分标签，去掉首尾空白和空标签
+fn split_tags(text: &str) -> Vec<String> {
+    let mut tags = Vec::new();
+    let mut tag = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(next @ ('\\' | TAG_SEPARATOR)) => tag.push(next),
+                Some(next) => {
+                    tag.push(c);
+                    tag.push(next);
+                }
+                None => tag.push(c),
+            },
+            TAG_SEPARATOR => tags.push(std::mem::take(&mut tag)),
+            c => tag.push(c),
+        }
+    }
+    tags.push(tag);
+    tags.into_iter()
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+/// 解析 CSV 文本，返回每一行的起始行号和字段（跳过空行）
+fn parse_csv(text: &str) -> Result<Vec<(usize, Vec<String>)>, String> {
+    let mut rows = Vec::new();
+    let (mut row, mut field) = (Vec::new(), String::new());
+    let (mut line, mut row_line) = (1, 1);
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, ',') => row.push(std::mem::take(&mut field)),
+            (false, '\r') if chars.peek() == Some(&'\n') => {}
+            (false, '\n') => {
+                row.push(std::mem::take(&mut field));
+                if row.len() > 1 || !row[0].is_empty() {
+                    rows.push((row_line, std::mem::take(&mut row)));
+                }
+                row.clear();
+                line += 1;
+                row_line = line;
+            }
+            (_, c) => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+        }
+    }
+    if quoted {
+        return Err(format!(
+            "第 {} 行的引号没有闭合（文件可能被截断）",
+            row_line
+        ));
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push((row_line, row));
+    }
+    Ok(rows)
+}
+
+/// 读取 CSV 文件中的记录（编号为 0，由调用者重新分配）
+///
+/// 表头中必须有 expression 和 result 列，其余列都是可选的，缺少或为空时取默认值；
+/// index 列和不认识的列被忽略。error_kind 列不为空的行是失败的记录，此时忽略 result 列。
+pub(super) fn import_csv(path: &Path) -> Result<Vec<HistoryRecord>, Error> {
+    let text = fs::read_to_string(path)?;
+    let rows = parse_csv(&text).map_err(|reason| format_error(path, reason))?;
+    let Some(((_, header), rows)) = rows.split_first() else {
+        return Err(format_error(path, "缺少表头"));
+    };
+    let column = |name: &str| header.iter().position(|h| h.trim() == name);
+    let (Some(expression), Some(result)) = (column("expression"), column("result")) else {
+        return Err(format_error(path, "表头中缺少 expression 或 result 列"));
+    };
+
+    rows.iter()
+        .map(|(line, fields)| {
+            let error = |reason: String| format_error(path, format!("第 {} 行: {}", line, reason));
+            if fields.len() != header.len() {
+                return Err(error(format!(
+                    "有 {} 个字段，表头有 {} 列",
+                    fields.len(),
+                    header.len()
+                )));
+            }
+            // 可选列的非空值
+            let optional = |name: &str| {
+                column(name)
+                    .map(|i| fields[i].trim())
+                    .filter(|field| !field.is_empty())
+            };
+            let invalid = |name: &str, field: &str| error(format!("无效的 {}: {}", name, field));
+
+            let value = match optional("error_kind") {
+                Some(kind) => Err(Error::Recorded {
+                    kind: kind.to_string(),
+                    message: column("error").map_or(String::new(), |i| fields[i].clone()),
+                }),
+                None => Ok(fields[result]
+                    .trim()
+                    .parse::<f64>()
+                    .map_err(|_| invalid("result", &fields[result]))?),
+            };
+            let mut record = HistoryRecord::with_result(
+                fields[expression].clone(),
+                value,
+                0,
+                AngleMode::Radians,
+            );
+            if let Some(mode) = optional("angle_mode") {
+                record.angle_mode = mode.parse().map_err(|_| invalid("angle_mode", mode))?;
+            }
+            if let Some(mode) = optional("number_mode") {
+                record.number_mode = mode.parse().map_err(|_| invalid("number_mode", mode))?;
+            }
+            if let Some(precision) = optional("precision") {
+                record.precision = precision
+                    .parse()
+                    .map_err(|_| invalid("precision", precision))?;
+            }
+            record.timestamp = match optional("timestamp") {
+                Some(timestamp) => Some(
+                    parse_timestamp(timestamp).ok_or_else(|| invalid("timestamp", timestamp))?,
+                ),
+                None => None,
+            };
+            if let Some(ms) = optional("duration_ms") {
+                record.duration = ms
+                    .parse()
+                    .ok()
+                    .and_then(parse_duration_ms)
+                    .ok_or_else(|| invalid("duration_ms", ms))?;
+            }
+            if let Some(tags) = optional("tags") {
+                record.tags = split_tags(tags);
+            }
+            if let Some(pinned) = optional("pinned") {
+                record.pinned = pinned.parse().map_err(|_| invalid("pinned", pinned))?;
+            }
+            record.note = column("note")
+                .map(|i| fields[i].clone())
+                .filter(|note| !note.is_empty());
+            Ok(record)
+        })
+        .collect()
+}
+
+// ===== 日志 =====
+
+/// 日志中的记录按编号递增，同编号的记录覆盖先前的
+fn replace_or_push(records: &mut Vec<HistoryRecord>, record: HistoryRecord) {
+    match records.binary_search_by_key(&record.index, |r| r.index) {
+        Ok(position) => records[position] = record,
+        Err(position) => records.insert(position, record),
+    }
+}
+
+/// 追加写入的日志文件：第一行是文件头，之后每行一条 JSON 记录
+///
+/// 每条记录写入后立即同步到磁盘，进程崩溃时最多丢失正在写入的那一条。
+/// 修改过的记录（如添加标签）再追加一行，读取时后出现的同编号记录覆盖先前的。
+#[derive(Debug)]
+pub(super) struct Journal {
+    path: PathBuf,
+    file: File,
+    /// 写在日志第一行的淘汰策略
+    pub eviction_policy: EvictionPolicy,
+}
+
+impl Journal {
+    /// 打开日志文件并读出其中的记录；文件不存在时创建
+    ///
+    /// 最后一行没有换行符且无法解析时视为崩溃时写了一半的记录，将其丢弃。
+    pub fn open(path: &Path) -> Result<(Journal, Vec<HistoryRecord>), Error> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        let complete = text.ends_with('\n');
+        let lines: Vec<&str> = text.lines().collect();
+        let mut records = Vec::new();
+        let mut eviction_policy = EvictionPolicy::default();
+        for (i, line) in lines.iter().enumerate() {
+            let truncated_tail = !complete && i + 1 == lines.len();
+            let parsed = serde_json::from_str::<Value>(line).map_err(|e| e.to_string());
+            let result = match i {
+                0 => parsed.and_then(|header| {
+                    check_header(&header)?;
+                    eviction_policy = eviction_policy_from_json(&header)?;
+                    Ok(())
+                }),
+                _ => parsed
+                    .and_then(|record| record_from_json(&record))
+                    .map(|record| replace_or_push(&mut records, record)),
+            };
+            match result {
+                Ok(()) => {}
+                Err(_) if truncated_tail && i > 0 => break,
+                Err(reason) => {
+                    return Err(format_error(path, format!("第 {} 行: {}", i + 1, reason)))
+                }
+            }
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let journal = Journal {
+            path: path.to_path_buf(),
+            file,
+            eviction_policy,
+        };
+        Ok((journal, records))
+    }
+
+    /// 追加一条记录
+    pub fn append(&mut self, record: &HistoryRecord) -> Result<(), Error> {
+        writeln!(self.file, "{}", record_to_json(record))?;
+        self.file.sync_data()?;
+        Ok(())
+    }
+
+    /// 用给定的记录重写整个日志（压缩掉已被淘汰的记录和写了一半的行）
+    pub fn rewrite<'a>(
+        &mut self,
+        records: impl IntoIterator<Item = &'a HistoryRecord>,
+    ) -> Result<(), Error> {
+        let header = json!({
+            "format": FORMAT_NAME,
+            "version": FORMAT_VERSION,
+            "eviction_policy": self.eviction_policy.to_string(),
+        });
+        let mut text = header.to_string() + "\n";
+        for record in records {
+            text += &(record_to_json(record).to_string() + "\n");
+        }
+        write_atomically(&self.path, &text)?;
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculator::evaluator::DEFAULT_PRECISION;
+    use crate::calculator::Calculator;
+
+    #[test]
+    fn test_parse_csv() {
+        let rows = parse_csv("a,b\r\n\"max(1, 2)\",\"say \"\"hi\"\"\"\n\n\"x\ny\",3").unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(
+            rows[1],
+            (2, vec!["max(1, 2)".to_string(), "say \"hi\"".to_string()])
+        );
+        assert_eq!(rows[2], (4, vec!["x\ny".to_string(), "3".to_string()]));
+
+        assert!(parse_csv("a,b\n\"unterminated,1\n").is_err());
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("plain"), "plain");
+
+        let tags = ["a;b".to_string(), "c\\".to_string(), "d".to_string()];
+        assert_eq!(join_tags(&tags), "a\\;b;c\\\\;d");
+        assert_eq!(split_tags(&join_tags(&tags)), tags);
+        assert_eq!(split_tags(" x ;; y\\z"), ["x", "y\\z"]);
+    }
+
+    #[test]
+    fn test_record_json() {
+        let record =
+            HistoryRecord::with_angle_mode("sin(30)".to_string(), 0.5, 3, AngleMode::Degrees);
+        let record = HistoryRecord {
+            number_mode: NumberMode::Rational,
+            precision: 40,
+            duration: Duration::from_micros(1500),
+            tags: vec!["trig".to_string()],
+            note: Some("半角".to_string()),
+            pinned: true,
+            ..record
+        };
+        let parsed = record_from_json(&record_to_json(&record)).unwrap();
+        assert_eq!(
+            (parsed.index, parsed.result, parsed.angle_mode),
+            (3, Ok(0.5), AngleMode::Degrees)
+        );
+        assert_eq!(
+            (parsed.number_mode, parsed.precision, parsed.duration),
+            (NumberMode::Rational, 40, Duration::from_micros(1500))
+        );
+        assert_eq!(parsed.timestamp, record.timestamp);
+        assert_eq!((parsed.tags, parsed.note), (record.tags, record.note));
+        assert!(parsed.pinned);
+        assert!(parsed.exact.is_none());
+
+        // 精确结果按数值模式还原
+        for (mode, expression) in [
+            (NumberMode::Rational, "-1/3"),
+            (NumberMode::Integer, "2^70"),
+            (NumberMode::Decimal, "1/3"),
+        ] {
+            let mut calc = Calculator::new();
+            calc.set_number_mode(mode);
+            let result = calc.evaluate_value(expression);
+            let record = HistoryRecord {
+                number_mode: mode,
+                ..HistoryRecord::with_value(expression.to_string(), result, 1, AngleMode::Radians)
+            };
+            let parsed = record_from_json(&record_to_json(&record)).unwrap();
+            assert!(parsed.exact.is_some());
+            assert_eq!(parsed.exact, record.exact);
+        }
+        let mut json = record_to_json(&HistoryRecord::new("1".to_string(), 1.0, 1));
+        json["exact"] = json!("1/0");
+        assert!(record_from_json(&json).is_err());
+
+        // 版本 1 的记录没有元数据字段
+        let parsed = record_from_json(&json!({
+            "index": 1, "expression": "1 + 1", "result": 2.0, "angle_mode": "RAD"
+        }))
+        .unwrap();
+        assert_eq!(
+            (parsed.precision, parsed.duration, parsed.timestamp),
+            (DEFAULT_PRECISION, Duration::ZERO, None)
+        );
+
+        // 非有限的结果保存为字符串
+        let record = HistoryRecord::new("1 / 0".to_string(), f64::INFINITY, 1);
+        assert_eq!(record_to_json(&record)["result"], "inf");
+        assert_eq!(
+            record_from_json(&record_to_json(&record)).unwrap().result,
+            Ok(f64::INFINITY)
+        );
+
+        assert!(record_from_json(&json!({ "index": 1, "expression": "x" })).is_err());
+        for index in [json!(0), json!(u64::MAX), json!(-1)] {
+            let record = json!({ "index": index, "expression": "1", "result": 1.0 });
+            assert!(record_from_json(&record).is_err());
+        }
+        assert_eq!(next_index_after(2), Ok(3));
+        assert!(next_index_after(usize::MAX).is_err());
+        assert!(check_header(&json!({ "format": FORMAT_NAME, "version": 99 })).is_err());
+    }
+}
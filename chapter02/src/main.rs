@@ -4,7 +4,7 @@ mod data;
 mod history;
 mod utils;
 
-use calculator::{Calculator, Operation};
+use calculator::{AngleMode, Calculator, Operation};
 use data::Statistics;
 use history::HistoryManager;
 use utils::Error;
@@ -39,9 +39,12 @@ fn run_example_calculations(
     println!("√16 = {}", result3);
     history.add_record("√16", result3);
 
-    let result4 = calc.sin(30.0_f64.to_radians())?;
+    // 角度模式下三角函数的参数按度解释
+    calc.set_angle_mode(AngleMode::Degrees);
+    let result4 = calc.sin(30.0)?;
     println!("sin(30°) = {}", result4);
-    history.add_record("sin(30°)", result4);
+    history.add_record_with_mode("sin(30)", result4, calc.angle_mode());
+    calc.set_angle_mode(AngleMode::Radians);
 
     // 表达式求值
     let expr_result = calc.evaluate_expression("(10 + 5) * 2 - √16")?;
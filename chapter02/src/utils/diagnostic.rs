@@ -0,0 +1,144 @@
+// utils/diagnostic.rs - 源码位置与错误提示渲染
+
+use std::fmt;
+
+use super::error::Error;
+
+/// 源码区间（按字符计数的半开区间 `[start, end)`）
+///
+/// 使用字符而不是字节偏移，这样 `π`、`√` 等多字节字符也只占一列。
+/// 需要字节偏移时可以用 [`Span::byte_range`] 换算。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    /// 起始字符位置（包含）
+    pub start: usize,
+    /// 结束字符位置（不包含）
+    pub end: usize,
+}
+
+impl Span {
+    /// 创建新的区间
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// 长度为零的区间（如输入末尾）
+    pub fn point(position: usize) -> Self {
+        Self::new(position, position)
+    }
+
+    /// 覆盖两个区间的最小区间
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    /// 区间包含的字符数
+    pub fn len(&self) -> usize {
+        self.end.saturating_sub(self.start)
+    }
+
+    /// 判断区间是否为空
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 换算成 source 中的字节区间
+    pub fn byte_range(&self, source: &str) -> std::ops::Range<usize> {
+        let offset = |chars: usize| {
+            source
+                .char_indices()
+                .nth(chars)
+                .map_or(source.len(), |(i, _)| i)
+        };
+        offset(self.start)..offset(self.end)
+    }
+
+    /// 取出区间对应的源码文本
+    pub fn slice<'a>(&self, source: &'a str) -> &'a str {
+        &source[self.byte_range(source)]
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "第 {} 列", self.start + 1)
+    }
+}
+
+/// 渲染带下划线标记的错误提示
+///
+/// ```text
+/// 错误: 无效字符: @
+///   | 1 + @ 2
+///   |     ^
+/// ```
+///
+/// 没有位置信息的错误只输出第一行。
+pub fn render_error(source: &str, error: &Error) -> String {
+    let mut output = format!("错误: {}", error.inner());
+
+    if let Some(span) = error.span() {
+        output.push('\n');
+        output.push_str(&render_span(source, span));
+    }
+
+    output
+}
+
+/// 渲染源码及其下方的 `^~~~` 标记
+pub fn render_span(source: &str, span: Span) -> String {
+    let line: String = source
+        .chars()
+        .map(|c| if c == '\n' { ' ' } else { c })
+        .collect();
+    let width = span.len().max(1);
+
+    let mut marker = " ".repeat(span.start);
+    marker.push('^');
+    marker.push_str(&"~".repeat(width - 1));
+
+    format!("  | {}\n  | {}", line, marker)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_span_to() {
+        let span = Span::new(4, 5).to(Span::new(0, 2));
+        assert_eq!(span, Span::new(0, 5));
+        assert_eq!(span.len(), 5);
+    }
+
+    #[test]
+    fn test_span_byte_range() {
+        let source = "π + 12";
+        let span = Span::new(4, 6);
+        assert_eq!(span.slice(source), "12");
+        assert_eq!(Span::new(0, 1).slice(source), "π");
+    }
+
+    #[test]
+    fn test_render_span() {
+        let rendered = render_span("sqrt(16 + @)", Span::new(10, 11));
+        assert_eq!(rendered, "  | sqrt(16 + @)\n  |           ^");
+
+        let rendered = render_span("1 + foo", Span::new(4, 7));
+        assert_eq!(rendered, "  | 1 + foo\n  |     ^~~");
+    }
+
+    #[test]
+    fn test_render_error() {
+        let error = Error::InvalidCharacter('@').with_span(Span::new(2, 3));
+        assert_eq!(
+            render_error("1 @ 2", &error),
+            "错误: 无效字符: @\n  | 1 @ 2\n  |   ^"
+        );
+
+        assert_eq!(
+            render_error("1 / 0", &Error::DivisionByZero),
+            "错误: 除零错误"
+        );
+    }
+}
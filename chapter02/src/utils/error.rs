@@ -2,6 +2,8 @@
 
 use std::fmt;
 
+use super::diagnostic::Span;
+
 /// 计算器错误类型
 #[derive(Debug, Clone, PartialEq)]
 pub enum Error {
@@ -20,9 +22,46 @@ pub enum Error {
     /// 阶乘值过大
     FactorialTooLarge,
 
+    /// 函数或运算的结果过大，超出可表示的范围（如 ncr(2000, 1000)、2^(10^10)）
+    ResultOverflow(String),
+
+    /// 整数函数的参数不是整数（阶乘和排列组合还要求非负）
+    InvalidIntegerArgument(String),
+
+    /// 函数参数超出定义域（如 acosh(0.5)、gamma(-1)）
+    DomainError { function: String, reason: String },
+
+    /// 函数无法符号求导（如 max、gcd）
+    NotDifferentiable(String),
+
+    /// 表达式中有无法编译为字节码的部分（如带单位的数值、求和）
+    NotCompilable(String),
+
+    /// 数值求根或积分的迭代没有收敛
+    NoConvergence { method: String, iterations: usize },
+
+    /// 求根区间两端的函数值同号
+    NoSignChange { lower: f64, upper: f64 },
+
+    /// 积分、求和或连乘的结果不是有限值
+    Divergent(String),
+
     /// 空数据集
     EmptyDataSet,
 
+    /// 批量求值时某一列的行数与其它列不同
+    ColumnLength {
+        column: String,
+        expected: usize,
+        actual: usize,
+    },
+
+    /// 批量求值时多个列使用了同一个列名
+    DuplicateColumn(String),
+
+    /// 批量求值时某一行出错（row 是从 0 开始的行下标）
+    Row { row: usize, error: Box<Error> },
+
     /// 无效数字
     InvalidNumber(String),
 
@@ -57,6 +96,47 @@ pub enum Error {
         actual: usize,
     },
 
+    /// 函数参数数量不在允许范围内（max 为 None 表示不限上限）
+    InvalidArgumentRange {
+        function: String,
+        min: usize,
+        max: Option<usize>,
+        actual: usize,
+    },
+
+    /// 用户函数直接或间接地调用了自身
+    RecursiveFunction(String),
+
+    /// 函数调用嵌套过深
+    RecursionLimitExceeded(usize),
+
+    /// 严格精确模式下遇到无法精确计算的运算
+    InexactResult(String),
+
+    /// 结果是复数，无法表示为实数
+    ComplexResult(String),
+
+    /// 单位的量纲不匹配（如长度加时间）
+    DimensionMismatch { left: String, right: String },
+
+    /// 未知的单位
+    UnknownUnit(String),
+
+    /// 带有源码位置的错误
+    ///
+    /// 只由 `tokenize_spanned`、`parse_spanned`、`Calculator::parse_expression` 和 `diagnose`
+    /// 等给出位置的接口返回，其他公开接口返回的错误不带这层包装。
+    Spanned { error: Box<Error>, span: Span },
+
+    /// 历史记录文件的格式错误（损坏、被截断或版本不受支持）
+    HistoryFormat { path: String, reason: String },
+
+    /// 表达式中的 `ans`、`_` 或 `$n` 无法引用历史结果（记录不存在、已被删除或求值失败）
+    HistoryReference { reference: String, reason: String },
+
+    /// 从历史记录文件读回的错误，只保存了种类（见 [`Error::kind`]）和消息
+    Recorded { kind: String, message: String },
+
     /// IO 错误
     IoError(String),
 
@@ -75,8 +155,43 @@ impl fmt::Display for Error {
             Error::InvalidFactorialArgument => {
                 write!(f, "阶乘参数无效: 必须是非负整数")
             }
-            Error::FactorialTooLarge => write!(f, "阶乘值过大: n > 20"),
+            Error::FactorialTooLarge => write!(f, "阶乘值过大: 超出可表示的范围"),
+            Error::ResultOverflow(function) => {
+                write!(f, "{} 的结果过大: 超出可表示的范围", function)
+            }
+            Error::InvalidIntegerArgument(function) => {
+                write!(
+                    f,
+                    "函数 {} 的参数必须是整数（阶乘和排列组合要求非负整数）",
+                    function
+                )
+            }
+            Error::DomainError { function, reason } => {
+                write!(f, "函数 {} 的参数超出定义域: {}", function, reason)
+            }
+            Error::NotDifferentiable(function) => write!(f, "函数 {} 无法求导", function),
+            Error::NotCompilable(what) => write!(f, "{}无法编译为字节码", what),
+            Error::NoConvergence { method, iterations } => {
+                write!(f, "{}迭代 {} 次后仍未收敛", method, iterations)
+            }
+            Error::NoSignChange { lower, upper } => write!(
+                f,
+                "区间 [{}, {}] 两端的函数值同号，无法确定其中有根",
+                lower, upper
+            ),
+            Error::Divergent(function) => write!(f, "{} 发散，结果不是有限值", function),
             Error::EmptyDataSet => write!(f, "空数据集"),
+            Error::DuplicateColumn(column) => write!(f, "列名重复: {}", column),
+            Error::ColumnLength {
+                column,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "列 {} 有 {} 行，与其它列的 {} 行不一致",
+                column, actual, expected
+            ),
+            Error::Row { row, error } => write!(f, "第 {} 行: {}", row + 1, error),
             Error::InvalidNumber(s) => write!(f, "无效数字: {}", s),
             Error::InvalidCharacter(c) => write!(f, "无效字符: {}", c),
             Error::MismatchedParen => write!(f, "括号不匹配"),
@@ -95,12 +210,145 @@ impl fmt::Display for Error {
                 "函数 {} 参数数量错误: 期望 {}, 实际 {}",
                 function, expected, actual
             ),
+            Error::InvalidArgumentRange {
+                function,
+                min,
+                max: Some(max),
+                actual,
+            } => write!(
+                f,
+                "函数 {} 参数数量错误: 期望 {} 到 {}, 实际 {}",
+                function, min, max, actual
+            ),
+            Error::InvalidArgumentRange {
+                function,
+                min,
+                max: None,
+                actual,
+            } => write!(
+                f,
+                "函数 {} 参数数量错误: 期望至少 {}, 实际 {}",
+                function, min, actual
+            ),
+            Error::RecursiveFunction(name) => {
+                write!(f, "函数 {} 直接或间接地调用了自身", name)
+            }
+            Error::RecursionLimitExceeded(depth) => {
+                write!(f, "函数调用嵌套过深: 超过 {} 层", depth)
+            }
+            Error::InexactResult(what) => {
+                write!(f, "无法精确计算: {}（严格模式下不允许退回浮点数）", what)
+            }
+            Error::ComplexResult(value) => {
+                write!(
+                    f,
+                    "结果是复数: {}（请使用 evaluate_value 获取复数结果）",
+                    value
+                )
+            }
+            Error::DimensionMismatch { left, right } => {
+                write!(f, "单位不匹配: {} 与 {}", left, right)
+            }
+            Error::UnknownUnit(name) => write!(f, "未知单位: {}", name),
+            Error::Spanned { error, span } => write!(f, "{} ({})", error, span),
+            Error::HistoryFormat { path, reason } => {
+                write!(f, "历史记录文件 {} 格式错误: {}", path, reason)
+            }
+            Error::HistoryReference { reference, reason } => {
+                write!(f, "无法引用历史结果 {}: {}", reference, reason)
+            }
+            Error::Recorded { message, .. } => write!(f, "{}", message),
             Error::IoError(s) => write!(f, "IO 错误: {}", s),
             Error::Other(s) => write!(f, "错误: {}", s),
         }
     }
 }
 
+impl Error {
+    /// 为错误附加源码位置（已有位置的错误保持不变）
+    pub fn with_span(self, span: Span) -> Error {
+        match self {
+            Error::Spanned { .. } => self,
+            error => Error::Spanned {
+                error: Box::new(error),
+                span,
+            },
+        }
+    }
+
+    /// 获取错误的源码位置
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Error::Spanned { span, .. } => Some(*span),
+            _ => None,
+        }
+    }
+
+    /// 去掉位置信息后的错误
+    pub fn inner(&self) -> &Error {
+        match self {
+            Error::Spanned { error, .. } => error,
+            error => error,
+        }
+    }
+
+    /// 错误的种类，即变体名（如 `"DivisionByZero"`），用于统计各类错误出现的次数
+    ///
+    /// 带位置的错误返回内部错误的种类，读回的错误返回保存时的种类。
+    pub fn kind(&self) -> &str {
+        match self {
+            Error::DivisionByZero => "DivisionByZero",
+            Error::NegativeSquareRoot => "NegativeSquareRoot",
+            Error::InvalidLogarithm => "InvalidLogarithm",
+            Error::InvalidFactorialArgument => "InvalidFactorialArgument",
+            Error::FactorialTooLarge => "FactorialTooLarge",
+            Error::ResultOverflow(_) => "ResultOverflow",
+            Error::InvalidIntegerArgument(_) => "InvalidIntegerArgument",
+            Error::DomainError { .. } => "DomainError",
+            Error::NotDifferentiable(_) => "NotDifferentiable",
+            Error::NotCompilable(_) => "NotCompilable",
+            Error::NoConvergence { .. } => "NoConvergence",
+            Error::NoSignChange { .. } => "NoSignChange",
+            Error::Divergent(_) => "Divergent",
+            Error::EmptyDataSet => "EmptyDataSet",
+            Error::ColumnLength { .. } => "ColumnLength",
+            Error::DuplicateColumn(_) => "DuplicateColumn",
+            Error::Row { .. } => "Row",
+            Error::InvalidNumber(_) => "InvalidNumber",
+            Error::InvalidCharacter(_) => "InvalidCharacter",
+            Error::MismatchedParen => "MismatchedParen",
+            Error::InvalidExpression => "InvalidExpression",
+            Error::ParseError(_) => "ParseError",
+            Error::InsufficientOperands => "InsufficientOperands",
+            Error::InvalidOperator => "InvalidOperator",
+            Error::UndefinedVariable(_) => "UndefinedVariable",
+            Error::UndefinedFunction(_) => "UndefinedFunction",
+            Error::InvalidArgumentCount { .. } => "InvalidArgumentCount",
+            Error::InvalidArgumentRange { .. } => "InvalidArgumentRange",
+            Error::RecursiveFunction(_) => "RecursiveFunction",
+            Error::RecursionLimitExceeded(_) => "RecursionLimitExceeded",
+            Error::InexactResult(_) => "InexactResult",
+            Error::ComplexResult(_) => "ComplexResult",
+            Error::DimensionMismatch { .. } => "DimensionMismatch",
+            Error::UnknownUnit(_) => "UnknownUnit",
+            Error::HistoryFormat { .. } => "HistoryFormat",
+            Error::HistoryReference { .. } => "HistoryReference",
+            Error::IoError(_) => "IoError",
+            Error::Other(_) => "Other",
+            Error::Spanned { error, .. } => error.kind(),
+            Error::Recorded { kind, .. } => kind,
+        }
+    }
+
+    /// 去掉位置信息，取出内部错误
+    pub fn into_inner(self) -> Error {
+        match self {
+            Error::Spanned { error, .. } => *error,
+            error => error,
+        }
+    }
+}
+
 impl std::error::Error for Error {}
 
 // 从 std::io::Error 转换
@@ -121,6 +369,49 @@ mod tests {
 
         let err = Error::InvalidNumber("abc".to_string());
         assert_eq!(err.to_string(), "无效数字: abc");
+
+        let err = Error::DomainError {
+            function: "acosh".to_string(),
+            reason: "参数必须 ≥ 1".to_string(),
+        };
+        assert_eq!(err.to_string(), "函数 acosh 的参数超出定义域: 参数必须 ≥ 1");
+
+        let err = Error::ResultOverflow("ncr".to_string());
+        assert_eq!(err.to_string(), "ncr 的结果过大: 超出可表示的范围");
+        assert_eq!(err.kind(), "ResultOverflow");
+    }
+
+    #[test]
+    fn test_error_span() {
+        let err = Error::InvalidCharacter('@').with_span(Span::new(2, 3));
+        assert_eq!(err.span(), Some(Span::new(2, 3)));
+        assert_eq!(err.inner(), &Error::InvalidCharacter('@'));
+        assert_eq!(err.to_string(), "无效字符: @ (第 3 列)");
+
+        // 重复附加位置时保留最初的位置
+        let err = err.with_span(Span::new(0, 1));
+        assert_eq!(err.span(), Some(Span::new(2, 3)));
+        assert_eq!(err.into_inner(), Error::InvalidCharacter('@'));
+    }
+
+    #[test]
+    fn test_error_kind() {
+        assert_eq!(Error::DivisionByZero.kind(), "DivisionByZero");
+        assert_eq!(
+            Error::UndefinedVariable("x".to_string()).kind(),
+            "UndefinedVariable"
+        );
+        let err = Error::InvalidCharacter('@').with_span(Span::new(0, 1));
+        assert_eq!(err.kind(), "InvalidCharacter");
+
+        let err = Error::Recorded {
+            kind: "DivisionByZero".to_string(),
+            message: "除零错误".to_string(),
+        };
+        assert_eq!(
+            (err.kind(), err.to_string()),
+            ("DivisionByZero", "除零错误".to_string())
+        );
     }
 
     #[test]
@@ -1,6 +1,8 @@
 // utils/mod.rs - 工具模块
 
+pub mod diagnostic;
 pub mod error;
 
 // 重新导出 Error 类型
+pub use diagnostic::{render_error, Span};
 pub use error::Error;